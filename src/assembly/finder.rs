@@ -1,36 +1,12 @@
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-use glob::{self, MatchOptions};
-use regex::Regex;
 use walkdir::WalkDir;
 
-use crate::assembly::parser::SeqDirs;
+use crate::sheet::{self, NamingScheme, ReadFinder, Sample};
 
-/// Match Read 1 from file name
-pub const READ1_REGEX: &str = r"^(.+?)(_|-)(?i)(R1|1|read1|read_1|read-1)(?:.*)$";
-
-/// Match Read 2 from file name
-pub const READ2_REGEX: &str = r"^(.+?)(_|-)(?i)(R2|2|read2|read_2|read-2)(?:.*)$";
-
-/// Lazy static regex matcher
-///
-/// Matches a file name with a given pattern
-/// Returns true if the file name matches the pattern
-macro_rules! re_match {
-    ($pattern: ident, $path: ident) => {{
-        lazy_static! {
-            static ref RE: Regex = Regex::new($pattern).unwrap();
-        }
-        let file_name = $path.file_name().expect("Failed to get file name");
-        RE.is_match(
-            file_name
-                .to_str()
-                .expect("Failed to convert file name to string"),
-        )
-    }};
-}
-
-pub fn auto_find_cleaned_fastq(path: &Path, dirname: &str) -> Vec<SeqReads> {
+/// Find the clean reads in a `yap qc` output directory.
+/// The sample metadata comes from the qc manifest if there is one.
+pub fn auto_find_cleaned_fastq(path: &Path, dirname: &str, scheme: &NamingScheme) -> Vec<Sample> {
     let mut entries = Vec::new();
 
     WalkDir::new(path)
@@ -40,98 +16,59 @@ pub fn auto_find_cleaned_fastq(path: &Path, dirname: &str) -> Vec<SeqReads> {
         .for_each(|e| {
             let dir = e.path().to_string_lossy();
             if dir.contains(dirname) {
-                let target = None;
-                get_files(&dir, &mut entries, target);
+                get_files(e.path(), &mut entries, scheme);
             }
         });
 
+    let mut metadata = sheet::read_manifest_metadata(path);
+    entries.iter_mut().for_each(|s| {
+        if let Some(values) = metadata.remove(&s.id) {
+            s.metadata = values;
+        }
+    });
     entries
 }
 
-pub fn find_cleaned_fastq(dirs: &[SeqDirs]) -> Vec<SeqReads> {
-    let mut entries = Vec::new();
-
-    dirs.iter()
-        .for_each(|s| get_files(&s.dir, &mut entries, Some(s.id.clone())));
-    entries
-}
-
-fn get_files(dir: &str, entries: &mut Vec<SeqReads>, target: Option<String>) {
-    let mut files = SeqReads::new(dir);
-    let fastq = files.glob_fastq();
-    files.match_reads(&fastq);
-    files.get_id(target);
-
-    if !files.read_1.as_os_str().is_empty() {
-        entries.push(files);
+fn get_files(dir: &Path, entries: &mut Vec<Sample>, scheme: &NamingScheme) {
+    let id = get_id(dir);
+    let mut sample = Sample::new(&id, dir);
+    let fastq = ReadFinder::new(dir, &id).glob_fastq();
+    sample
+        .match_reads(&fastq, scheme)
+        .and_then(|_| sample.infer_layout())
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    if !sample.read_1.as_os_str().is_empty() {
+        entries.push(sample);
     }
 }
 
-pub struct SeqReads {
-    pub dir: PathBuf,
-    pub id: String,
-    pub read_1: PathBuf,
-    pub read_2: PathBuf,
-    pub singleton: Option<PathBuf>,
+fn get_id(dir: &Path) -> String {
+    let dirs: Vec<_> = dir.components().map(|d| d.as_os_str()).collect();
+    assert!(dirs.len() > 1, "INVALID FOLDER STRUCTURE TO USE AUTO");
+    String::from(dirs[1].to_string_lossy())
 }
 
-impl SeqReads {
-    fn new(dir: &str) -> Self {
-        Self {
-            dir: PathBuf::from(dir),
-            id: String::new(),
-            read_1: PathBuf::new(),
-            read_2: PathBuf::new(),
-            singleton: None,
-        }
-    }
-
-    fn glob_fastq(&self) -> Vec<PathBuf> {
-        let pattern = format!("{}/*", self.dir.to_string_lossy());
-        let opts = MatchOptions {
-            case_sensitive: true,
-            ..Default::default()
-        };
-        glob::glob_with(&pattern, opts)
-            .unwrap()
-            .filter_map(|ok| ok.ok())
-            .collect()
-    }
-
-    fn match_reads(&mut self, dirs: &[PathBuf]) {
-        dirs.iter().for_each(|e| {
-            if re_match!(READ1_REGEX, e) {
-                self.read_1 = PathBuf::from(e);
-            } else if re_match!(READ2_REGEX, e) {
-                self.read_2 = PathBuf::from(e);
-            } else {
-                self.singleton = Some(PathBuf::from(e));
-            }
-        });
-    }
-
-    fn get_id(&mut self, target: Option<String>) {
-        if target.is_none() {
-            let dirs: Vec<_> = self.dir.components().map(|d| d.as_os_str()).collect();
-            assert!(dirs.len() > 1, "INVALID FOLDER STRUCTURE TO USE AUTO");
-            self.id = String::from(dirs[1].to_string_lossy());
-        } else {
-            self.id = String::from(target.as_ref().unwrap());
-        }
-    }
+/// Use the clean reads from the `yap qc` output directory
+/// instead of the reads listed in the sample sheet.
+pub fn find_cleaned_fastq(samples: &mut [Sample], clean_dir: &Path, scheme: &NamingScheme) {
+    samples.iter_mut().for_each(|s| {
+        s.find_clean_reads(clean_dir, scheme)
+            .unwrap_or_else(|e| panic!("{}", e))
+    });
 }
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use super::*;
 
     #[test]
     fn glob_test() {
-        let input = "test_files/assembly/trimmed_test";
+        let input = Path::new("test_files/assembly/trimmed_test");
 
-        let seq = SeqReads::new(&input);
-
-        let res = seq.glob_fastq();
+        let res = ReadFinder::new(input, "").glob_fastq();
         assert_eq!(2, res.len());
     }
 
@@ -140,7 +77,7 @@ mod test {
         let input = Path::new("test_files/assembly");
         let dirname = "trimmed";
 
-        let res = auto_find_cleaned_fastq(&input, &dirname);
+        let res = auto_find_cleaned_fastq(input, dirname, &NamingScheme::Auto);
 
         assert_eq!(1, res.len());
     }
@@ -150,7 +87,7 @@ mod test {
         let input = Path::new("test_files/assembly");
         let dirname = "trimmed";
 
-        let res = auto_find_cleaned_fastq(&input, &dirname);
+        let res = auto_find_cleaned_fastq(input, dirname, &NamingScheme::Auto);
 
         let path = PathBuf::from(input).join("trimmed_test");
         let r1 = path.join("some_seq_ABC123_R1.fq.gz");
@@ -162,15 +99,23 @@ mod test {
         })
     }
 
+    #[test]
+    fn find_cleaned_fastq_metadata_test() {
+        let input = Path::new("test_files/assembly");
+        let res = auto_find_cleaned_fastq(input, "trimmed", &NamingScheme::Auto);
+
+        let voucher = (String::from("voucher"), String::from("KU 1"));
+        assert_eq!(vec![voucher], res[0].metadata);
+    }
+
     #[test]
     fn get_cleaned_fastq_test() {
-        let dir = "test_files/trimmed_test";
+        let dir = Path::new("test_files/trimmed_test");
         let mut res = Vec::new();
 
-        get_files(&dir, &mut res, None);
-        let path = PathBuf::from(dir);
-        let r1 = path.join("some_seq_ABC123_R1.fq.gz");
-        let r2 = path.join("some_seq_ABC123_R2.fq.gz");
+        get_files(dir, &mut res, &NamingScheme::Auto);
+        let r1 = dir.join("some_seq_ABC123_R1.fq.gz");
+        let r2 = dir.join("some_seq_ABC123_R2.fq.gz");
         res.iter().for_each(|e| {
             assert_eq!(r1, e.read_1);
             assert_eq!(r2, e.read_2);
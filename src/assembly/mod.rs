@@ -1,6 +1,6 @@
 pub mod cleaner;
 mod finder;
-mod parser;
+pub mod options;
 mod runner;
 
 use std::io::Result;
@@ -8,59 +8,145 @@ use std::path::Path;
 
 use colored::Colorize;
 
-use crate::assembly::finder::SeqReads;
+use crate::qc;
+use crate::sheet::{self, NamingScheme, Sample};
 use crate::utils::utils;
 
+pub use options::{SpadesMode, SpadesOptions};
+pub use runner::{is_complete, AssemblySettings};
+
 pub fn auto_process_input(
     path: &Path,
     dirname: &str,
-    threads: Option<usize>,
-    output_dir: Option<&Path>,
-    args: Option<&str>,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
 ) {
-    let samples = finder::auto_find_cleaned_fastq(path, dirname);
-    runner::assemble_reads(&samples, threads, output_dir, args);
+    let samples = finder::auto_find_cleaned_fastq(path, dirname, scheme);
+    let samples = exclude_failed(samples, path, settings);
+    runner::assemble_reads(&samples, scheme, settings);
 }
 
 pub fn process_input(
     input: &Path,
-    threads: Option<usize>,
-    output_dir: Option<&Path>,
-    args: Option<&str>,
+    clean_dir: Option<&Path>,
+    is_rename: bool,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
 ) {
-    let dirs = parser::parse_sequence_dir(input);
-    let samples = finder::find_cleaned_fastq(&dirs);
-    runner::assemble_reads(&samples, threads, output_dir, args);
+    let samples = get_samples(input, clean_dir, is_rename, scheme, settings);
+    runner::assemble_reads(&samples, scheme, settings);
 }
 
-pub fn auto_dry_run(path: &Path, dirname: &str) {
-    let samples = finder::auto_find_cleaned_fastq(path, dirname);
+pub fn auto_dry_run(
+    path: &Path,
+    dirname: &str,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
+) {
+    let samples = finder::auto_find_cleaned_fastq(path, dirname, scheme);
+    let samples = exclude_failed(samples, path, settings);
     utils::get_system_info().unwrap();
-    print_dry_run(&samples).unwrap();
+    print_dry_run(&samples, scheme, settings).unwrap();
 }
 
-pub fn dry_run(input: &Path) {
-    let dirs = parser::parse_sequence_dir(input);
-    let samples = finder::find_cleaned_fastq(&dirs);
+pub fn dry_run(
+    input: &Path,
+    clean_dir: Option<&Path>,
+    is_rename: bool,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
+) {
+    let samples = get_samples(input, clean_dir, is_rename, scheme, settings);
     utils::get_system_info().unwrap();
-    print_dry_run(&samples).unwrap();
+    print_dry_run(&samples, scheme, settings).unwrap();
 }
 
-fn print_dry_run(dirs: &[SeqReads]) -> Result<()> {
+fn get_samples(
+    input: &Path,
+    clean_dir: Option<&Path>,
+    is_rename: bool,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
+) -> Vec<Sample> {
+    let mut samples = sheet::parse_sample_sheet(input, is_rename, scheme);
+    if let Some(dir) = clean_dir {
+        finder::find_cleaned_fastq(&mut samples, dir, scheme);
+        samples = exclude_failed(samples, dir, settings);
+    }
+    samples
+}
+
+// Samples marked as failed in the qc summary of the clean-read dir
+// are not assembled.
+fn exclude_failed(
+    samples: Vec<Sample>,
+    clean_dir: &Path,
+    settings: &AssemblySettings,
+) -> Vec<Sample> {
+    if settings.include_failed {
+        return samples;
+    }
+    let failed = qc::read_failed_samples(clean_dir);
+    samples
+        .into_iter()
+        .filter(|s| {
+            let is_failed = failed.contains(s.target_dir.to_string_lossy().as_ref());
+            if is_failed {
+                log::warn!(
+                    "{:18}: {} failed the QC thresholds. Use --include-failed to assemble it.",
+                    "Skipped",
+                    s.id
+                );
+            }
+            !is_failed
+        })
+        .collect()
+}
+
+fn print_dry_run(
+    dirs: &[Sample],
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
+) -> Result<()> {
+    settings.check_args(dirs);
     log::info!("{} {}", dirs.len(), "Total samples:".yellow());
     dirs.iter().for_each(|e| {
         log::info!("{:18}: {}", "ID".yellow(), e.id.yellow());
+        log::info!("{:18}: {}", "Layout", e.layout.name());
         log::info!("{:18}: {}", "Dir", e.dir.to_string_lossy());
-        log::info!("{:18}: {}", "Read 1", e.read_1.to_string_lossy());
-        log::info!("{:18}: {}", "Read 2", e.read_2.to_string_lossy());
+        if e.is_multi_lane() {
+            e.lanes.iter().enumerate().for_each(|(i, (r1, r2))| {
+                log::info!(
+                    "{:18}: {}",
+                    format!("Lane {} Read 1", i + 1),
+                    r1.to_string_lossy()
+                );
+                if e.has_read_2() {
+                    log::info!(
+                        "{:18}: {}",
+                        format!("Lane {} Read 2", i + 1),
+                        r2.to_string_lossy()
+                    );
+                }
+            });
+        } else {
+            log::info!("{:18}: {}", "Read 1", e.read_1.to_string_lossy());
+            if e.has_read_2() {
+                log::info!("{:18}: {}", "Read 2", e.read_2.to_string_lossy());
+            }
+        }
 
-        if e.singleton.is_some() {
-            log::info!(
-                "{:18}: {}",
-                "Singleton",
-                e.singleton.as_ref().unwrap().to_string_lossy()
-            );
+        if let Some(singleton) = &e.singleton {
+            log::info!("{:18}: {}", "Singleton", singleton.to_string_lossy());
         }
+        e.metadata.iter().for_each(|(key, value)| {
+            log::info!("{:18}: {}", key, value);
+        });
+        log::info!(
+            "{:18}: {}",
+            "Command",
+            runner::get_spades_command(e, scheme, settings)
+        );
 
         println!();
     });
@@ -0,0 +1,264 @@
+//! Typed SPAdes options.
+//!
+//! The common options can be set with yap flags. The same options in
+//! `--opts` or in the `spades_opts` column are read into the same model,
+//! so their values are checked before any sample runs.
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::utils::opts::{self, OptionValues};
+
+// SPAdes options, used to catch typos. Numbered library options,
+// such as --pe2-1, are matched by `LIBRARY_FLAG`.
+const SPADES_FLAGS: [&str; 46] = [
+    "-o",
+    "--isolate",
+    "--sc",
+    "--meta",
+    "--bio",
+    "--corona",
+    "--rna",
+    "--plasmid",
+    "--metaviral",
+    "--metaplasmid",
+    "--rnaviral",
+    "--iontorrent",
+    "--test",
+    "-h",
+    "--help",
+    "--help-hidden",
+    "-v",
+    "--version",
+    "--12",
+    "-1",
+    "-2",
+    "--merged",
+    "-s",
+    "--sanger",
+    "--pacbio",
+    "--nanopore",
+    "--trusted-contigs",
+    "--untrusted-contigs",
+    "--assembly-graph",
+    "--dataset",
+    "--custom-hmms",
+    "--continue",
+    "--restart-from",
+    "--checkpoints",
+    "--only-error-correction",
+    "--only-assembler",
+    "--careful",
+    "--disable-gzip-output",
+    "--disable-rr",
+    "-t",
+    "--threads",
+    "-m",
+    "--memory",
+    "--tmp-dir",
+    "-k",
+    "--cov-cutoff",
+];
+
+lazy_static! {
+    static ref LIBRARY_FLAG: Regex =
+        Regex::new(r"^--((pe|mp|hqmp)\d*-(12|1|2|s|m|or|fr|rf|ff)|s\d+)$").unwrap();
+}
+
+// SPAdes only accepts odd k-mer sizes below 128.
+const MAX_KMER: usize = 127;
+
+/// Assembly mode. `Careful` is the yap default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpadesMode {
+    Careful,
+    Isolate,
+    SingleCell,
+    Meta,
+    Plasmid,
+    Rna,
+    Metaviral,
+    Metaplasmid,
+    Rnaviral,
+}
+
+impl SpadesMode {
+    pub const ALL: [Self; 9] = [
+        Self::Careful,
+        Self::Isolate,
+        Self::SingleCell,
+        Self::Meta,
+        Self::Plasmid,
+        Self::Rna,
+        Self::Metaviral,
+        Self::Metaplasmid,
+        Self::Rnaviral,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|mode| mode.name() == name.to_lowercase())
+            .copied()
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Careful => "careful",
+            Self::Isolate => "isolate",
+            Self::SingleCell => "sc",
+            Self::Meta => "meta",
+            Self::Plasmid => "plasmid",
+            Self::Rna => "rna",
+            Self::Metaviral => "metaviral",
+            Self::Metaplasmid => "metaplasmid",
+            Self::Rnaviral => "rnaviral",
+        }
+    }
+
+    fn flag(&self) -> String {
+        format!("--{}", self.name())
+    }
+
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        Self::from_name(flag.strip_prefix("--")?)
+    }
+}
+
+/// The common SPAdes options. Unset options use the SPAdes defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpadesOptions {
+    pub mode: Option<SpadesMode>,
+    /// K-mer sizes. SPAdes picks them when empty.
+    pub kmers: Vec<usize>,
+    /// Memory limit in GB.
+    pub memory: Option<usize>,
+}
+
+impl SpadesOptions {
+    /// Read the typed options from SPAdes arguments.
+    /// Other options are left to SPAdes.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        for OptionValues { name, values } in opts::get_option_values(args) {
+            match name {
+                "-k" => options.kmers = parse_kmers(&opts::parse_value::<String>(name, &values)?)?,
+                "-m" | "--memory" => options.memory = Some(opts::parse_value(name, &values)?),
+                _ => {
+                    if let Some(mode) = SpadesMode::from_flag(name) {
+                        options.set_mode(mode)?;
+                    }
+                }
+            }
+        }
+        options.validate()?;
+        Ok(options)
+    }
+
+    fn set_mode(&mut self, mode: SpadesMode) -> Result<(), String> {
+        match self.mode {
+            Some(other) if other != mode => {
+                Err(format!("USE EITHER {} OR {}", other.flag(), mode.flag()))
+            }
+            _ => {
+                self.mode = Some(mode);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(k) = self.kmers.iter().find(|k| **k % 2 == 0 || **k > MAX_KMER) {
+            return Err(format!("K-MER SIZE {} IS NOT AN ODD NUMBER BELOW 128", k));
+        }
+        if self.memory == Some(0) {
+            return Err(String::from("--memory MUST BE ABOVE 0"));
+        }
+        Ok(())
+    }
+
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(mode) = self.mode {
+            args.push(mode.flag());
+        }
+        if !self.kmers.is_empty() {
+            let kmers: Vec<String> = self.kmers.iter().map(|k| k.to_string()).collect();
+            args.extend([String::from("-k"), kmers.join(",")]);
+        }
+        if let Some(memory) = self.memory {
+            args.extend([String::from("--memory"), memory.to_string()]);
+        }
+        args
+    }
+}
+
+/// Comma separated k-mer sizes, or `auto`.
+pub fn parse_kmers(kmers: &str) -> Result<Vec<usize>, String> {
+    if kmers.eq_ignore_ascii_case("auto") {
+        return Ok(Vec::new());
+    }
+    kmers
+        .split(',')
+        .map(|k| {
+            k.trim()
+                .parse()
+                .map_err(|_| format!("INVALID K-MER SIZE {}", k))
+        })
+        .collect()
+}
+
+/// True if SPAdes has the option.
+pub fn is_spades_flag(name: &str) -> bool {
+    SPADES_FLAGS.contains(&name) || LIBRARY_FLAG.is_match(name)
+}
+
+/// The SPAdes options known to yap, to catch typos.
+pub fn get_spades_flags() -> &'static [&'static str] {
+    &SPADES_FLAGS
+}
+
+/// Options that set the same thing. The modes replace each other.
+pub fn get_spades_key(name: &str) -> &str {
+    match name {
+        "-t" => "--threads",
+        "-m" => "--memory",
+        _ if SpadesMode::from_flag(name).is_some() => "mode",
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn spades_options_test() {
+        let options = SpadesOptions::from_args(&to_args("--isolate -k 21,33,55 -m 16")).unwrap();
+
+        assert_eq!(Some(SpadesMode::Isolate), options.mode);
+        assert_eq!(vec![21, 33, 55], options.kmers);
+        assert_eq!(
+            to_args("--isolate -k 21,33,55 --memory 16"),
+            options.to_args()
+        );
+        assert!(is_spades_flag("--pe2-12"));
+        assert!(!is_spades_flag("--carefull"));
+    }
+
+    #[test]
+    fn invalid_spades_options_test() {
+        assert_eq!(
+            Err(String::from("K-MER SIZE 22 IS NOT AN ODD NUMBER BELOW 128")),
+            SpadesOptions::from_args(&to_args("-k 21,22"))
+        );
+        assert_eq!(
+            Err(String::from("USE EITHER --careful OR --meta")),
+            SpadesOptions::from_args(&to_args("--careful --meta"))
+        );
+        assert!(SpadesOptions::from_args(&to_args("--memory lots")).is_err());
+    }
+}
@@ -1,5 +1,5 @@
-use std::fs;
-use std::io::{self, Result, Write};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Result, Write};
 #[cfg(not(target_os = "windows"))]
 use std::os::unix;
 use std::path::{Path, PathBuf};
@@ -7,30 +7,90 @@ use std::process::{Command, Output};
 
 use colored::Colorize;
 
-use crate::assembly::finder::SeqReads;
+use crate::assembly::options::{SpadesMode, SpadesOptions};
+use crate::sheet::{self, NamingScheme, Sample};
+use crate::utils::opts::{self, Tool};
 use crate::utils::utils::{self, PrettyHeader};
 
-pub fn assemble_reads(
-    reads: &[SeqReads],
-    threads: Option<usize>,
-    output_dir: Option<&Path>,
-    args: Option<&str>,
-) {
-    let dir = get_output_dir(output_dir);
-    utils::check_dir_exist(&dir);
+/// Settings shared by every sample in a run.
+pub struct AssemblySettings<'a> {
+    pub threads: Option<usize>,
+    pub output_dir: Option<&'a Path>,
+    /// Raw SPAdes options. They win over the typed options.
+    pub args: Option<&'a str>,
+    pub spades_options: SpadesOptions,
+    /// Keep the output dir and only assemble samples without complete outputs.
+    pub resume: bool,
+    /// Assemble samples that failed the qc thresholds.
+    pub include_failed: bool,
+    /// Split interleaved reads into read 1 and read 2 files for SPAdes.
+    pub deinterleave: bool,
+}
+
+impl AssemblySettings<'_> {
+    /// The options for all samples. Without a mode in the typed
+    /// options or in `--opts`, SPAdes runs in careful mode.
+    pub fn get_global_args(&self) -> Vec<String> {
+        let mut typed = self.spades_options.clone();
+        if typed.mode.is_none() && !self.has_raw_mode() {
+            typed.mode = Some(SpadesMode::Careful);
+        }
+        opts::get_global_opts(Tool::Spades, typed.to_args(), self.args)
+            .unwrap_or_else(|e| panic!("INVALID --opts FOR SPAdes: {}", e))
+    }
+
+    fn has_raw_mode(&self) -> bool {
+        opts::get_opts(Tool::Spades, self.args.unwrap_or_default())
+            .unwrap_or_else(|e| panic!("INVALID --opts FOR SPAdes: {}", e))
+            .iter()
+            .any(|word| SpadesMode::from_flag(word).is_some())
+    }
+
+    /// Check the options of every sample before any job starts.
+    pub fn check_args(&self, reads: &[Sample]) {
+        opts::check_sample_opts(Tool::Spades, &self.get_global_args(), reads);
+    }
+}
+
+pub fn assemble_reads(reads: &[Sample], scheme: &NamingScheme, settings: &AssemblySettings) {
+    settings.check_args(reads);
+    let dir = get_output_dir(settings.output_dir);
+    if !settings.resume {
+        utils::check_dir_exist(&dir);
+    }
     fs::create_dir_all(&dir).expect("CAN'T CREATE ASSEMBLY DIR");
+    let manifest =
+        sheet::write_manifest(&dir, reads, |s| dir.join(&s.id)).expect("CAN'T WRITE MANIFEST");
+    log::info!("{:18}: {}", "Manifest", manifest.display());
     let contig_dir = dir.join("contig_symlinks");
     fs::create_dir_all(&contig_dir).unwrap();
+    let pending = if settings.resume {
+        get_pending_samples(&dir, &contig_dir, reads, settings)
+    } else {
+        reads.to_vec()
+    };
     log::info!(
         "{:18} {}\n",
         "Total samples:".blue(),
-        reads.len().to_string().blue()
+        pending.len().to_string().blue()
     );
-    let sample_count = reads.len();
+    let sample_count = pending.len();
     let mut processed = 0;
-    reads.iter().for_each(|r| {
-        let mut run = Runner::new(&dir, &contig_dir, r, threads, args);
-        run.run_spades();
+    pending.iter().for_each(|r| {
+        if r.is_multi_lane() {
+            // SPAdes gets a single read pair per library.
+            let merged_dir = dir.join("merged_reads").join(&r.id);
+            let merged = sheet::merge_lanes(r, &merged_dir, scheme);
+            Runner::new(&dir, &contig_dir, &merged, settings).run_spades();
+            fs::remove_dir_all(&merged_dir).expect("CAN'T REMOVE MERGED READ DIR");
+        } else if r.is_interleaved() && settings.deinterleave {
+            let split_dir = dir.join("deinterleaved_reads").join(&r.id);
+            let paired = sheet::deinterleave(r, &split_dir);
+            Runner::new(&dir, &contig_dir, &paired, settings).run_spades();
+            fs::remove_dir_all(&split_dir).expect("CAN'T REMOVE DEINTERLEAVED READ DIR");
+        } else {
+            Runner::new(&dir, &contig_dir, r, settings).run_spades();
+        }
         processed += 1;
         let processed_info = format!("Processed {} of {} samples", processed, sample_count);
         log::info!("{}", processed_info.blue());
@@ -40,6 +100,78 @@ pub fn assemble_reads(
     log::info!("");
 }
 
+// Skip samples with complete outputs and restore their contig symlinks.
+// The outputs of the other samples are removed, so SPAdes starts over.
+fn get_pending_samples(
+    dir: &Path,
+    contig_dir: &Path,
+    reads: &[Sample],
+    settings: &AssemblySettings,
+) -> Vec<Sample> {
+    let pending: Vec<Sample> = reads
+        .iter()
+        .filter(|r| {
+            let runner = Runner::new(dir, contig_dir, r, settings);
+            // Left behind if the run stopped before the temporary reads were removed.
+            ["merged_reads", "deinterleaved_reads"]
+                .iter()
+                .map(|name| dir.join(name).join(&r.id))
+                .filter(|tmp_dir| tmp_dir.is_dir())
+                .for_each(|tmp_dir| {
+                    fs::remove_dir_all(tmp_dir).expect("CAN'T REMOVE TEMPORARY READ DIR")
+                });
+            if is_complete(&runner.output) {
+                log::info!("{:18}: {}", "Skipped (complete)", r.id);
+                if !runner.has_symlink() {
+                    runner.create_symlink();
+                }
+                return false;
+            }
+            runner
+                .remove_output()
+                .expect("CAN'T REMOVE INCOMPLETE OUTPUT");
+            true
+        })
+        .cloned()
+        .collect();
+    log::info!(
+        "{:18}: {} of {} samples\n",
+        "Resuming",
+        pending.len(),
+        reads.len()
+    );
+    pending
+}
+
+/// The SPAdes command line of a sample, for the dry run. The merged
+/// or deinterleaved reads are only written when the sample runs.
+pub fn get_spades_command(
+    sample: &Sample,
+    scheme: &NamingScheme,
+    settings: &AssemblySettings,
+) -> String {
+    let dir = get_output_dir(settings.output_dir);
+    let contig_dir = dir.join("contig_symlinks");
+    let input = if sample.is_multi_lane() {
+        let merged_dir = dir.join("merged_reads").join(&sample.id);
+        sheet::get_merged_sample(sample, &merged_dir, scheme)
+    } else if sample.is_interleaved() && settings.deinterleave {
+        let split_dir = dir.join("deinterleaved_reads").join(&sample.id);
+        sheet::get_deinterleaved_sample(sample, &split_dir)
+    } else {
+        sample.clone()
+    };
+    let runner = Runner::new(&dir, &contig_dir, &input, settings);
+    opts::format_command(&runner.build_spades_command())
+}
+
+/// SPAdes writes `SPAdes pipeline finished` to the end of its log.
+/// The contigs and a finished log make a complete assembly.
+pub fn is_complete(output: &Path) -> bool {
+    let log = fs::read_to_string(output.join("spades.log")).unwrap_or_default();
+    output.join("contigs.fasta").is_file() && log.contains("SPAdes pipeline finished")
+}
+
 fn get_output_dir(output_dir: Option<&Path>) -> PathBuf {
     match output_dir {
         Some(dir) => dir.to_path_buf(),
@@ -48,27 +180,26 @@ fn get_output_dir(output_dir: Option<&Path>) -> PathBuf {
 }
 
 struct Runner<'a> {
-    reads: &'a SeqReads,
+    reads: &'a Sample,
     output: PathBuf,
     symlink_dir: &'a Path,
     threads: Option<usize>,
-    args: Option<&'a str>,
+    args: Vec<String>,
 }
 
 impl<'a> Runner<'a> {
     fn new(
         dir: &Path,
         contig_dir: &'a Path,
-        input: &'a SeqReads,
-        threads: Option<usize>,
-        args: Option<&'a str>,
+        input: &'a Sample,
+        settings: &AssemblySettings<'a>,
     ) -> Self {
         Self {
             reads: input,
             output: dir.join(&input.id),
             symlink_dir: contig_dir,
-            threads,
-            args,
+            threads: settings.threads,
+            args: settings.get_global_args(),
         }
     }
 
@@ -79,28 +210,49 @@ impl<'a> Runner<'a> {
         let spin = utils::set_spinner();
         spin.set_message("SPAdes is processing...");
         let out = self.call_spades();
-        self.check_spades_success(&out);
+        if !self.check_spades_success(&out) {
+            spin.finish_with_message(format!("{} SPAdes has failed", "✘".red()));
+            log::error!(
+                "{:18}: {}. Check {}",
+                "SPAdes failed",
+                self.reads.id,
+                self.output.join("spades.log").display()
+            );
+            return;
+        }
         spin.finish_with_message(format!("{} SPAdes has finished", "✔".green()));
         self.create_symlink();
     }
 
-    fn check_spades_success(&self, out: &Output) {
+    // A failed sample gets no contig symlink,
+    // so later steps skip it.
+    fn check_spades_success(&self, out: &Output) -> bool {
         if !out.status.success() {
             println!();
             io::stdout().write_all(&out.stdout).unwrap();
             io::stdout().write_all(&out.stderr).unwrap();
         }
+        out.status.success()
     }
 
     fn call_spades(&self) -> Output {
+        self.build_spades_command().output().unwrap()
+    }
+
+    fn build_spades_command(&self) -> Command {
         let mut out = Command::new("spades.py");
-        out.arg("--pe1-1")
-            .arg(&self.reads.read_1)
-            .arg("--pe1-2")
-            .arg(&self.reads.read_2)
-            .arg("-o")
-            .arg(&self.output.clone());
-        self.get_spades_args(&mut out);
+        if self.reads.is_single_end() {
+            out.arg("-s").arg(&self.reads.read_1);
+        } else if self.reads.is_interleaved() {
+            out.arg("--pe1-12").arg(&self.reads.read_1);
+        } else {
+            out.arg("--pe1-1")
+                .arg(&self.reads.read_1)
+                .arg("--pe1-2")
+                .arg(&self.reads.read_2);
+        }
+        out.arg("-o").arg(self.output.clone());
+        out.args(self.get_spades_args());
 
         if self.reads.singleton.is_some() {
             self.get_singleton(&mut out);
@@ -110,34 +262,12 @@ impl<'a> Runner<'a> {
             self.get_thread_num(&mut out);
         }
 
-        out.output().unwrap()
+        out
     }
 
-    fn get_spades_args(&self, out: &mut Command) {
-        if self.args.is_some() {
-            self.get_opt_args(out);
-        } else {
-            self.get_default_args(out);
-        }
-    }
-    fn get_default_args(&self, out: &mut Command) {
-        out.arg("--careful");
-    }
-
-    fn get_opt_args(&self, out: &mut Command) {
-        let args: Vec<&str> = self
-            .args
-            .as_ref()
-            .expect("Invalid args")
-            .split_whitespace()
-            .collect();
-        if args.len() > 1 {
-            args.iter().for_each(|&a| {
-                out.arg(a);
-            });
-        } else {
-            out.arg(self.args.as_ref().unwrap());
-        }
+    // The sample options in the sheet are merged with the global options.
+    fn get_spades_args(&self) -> Vec<String> {
+        opts::get_sample_opts(Tool::Spades, &self.args, self.reads)
     }
 
     fn get_singleton(&self, out: &mut Command) {
@@ -152,6 +282,7 @@ impl<'a> Runner<'a> {
 
     fn print_settings(&self) -> Result<()> {
         log::info!("{:18}: {}", "ID", &self.reads.id);
+        log::info!("{:18}: {}", "Layout", self.reads.layout.name());
         log::info!("{:18}: {}", "Input dir", &self.reads.dir.to_string_lossy());
         log::info!(
             "{:18}: {}",
@@ -163,54 +294,67 @@ impl<'a> Runner<'a> {
                 .expect("NO FILE")
                 .to_string_lossy()
         );
-        log::info!(
-            "{:18}: {}",
-            "Input R2",
-            &self
-                .reads
-                .read_2
-                .file_name()
-                .expect("NO FILE")
-                .to_string_lossy()
-        );
-
-        if self.reads.singleton.is_some() {
+        if self.reads.has_read_2() {
             log::info!(
                 "{:18}: {}",
-                "Singleton",
+                "Input R2",
                 &self
                     .reads
-                    .singleton
-                    .as_ref()
+                    .read_2
+                    .file_name()
                     .expect("NO FILE")
                     .to_string_lossy()
             );
         }
 
+        if let Some(singleton) = &self.reads.singleton {
+            log::info!("{:18}: {}", "Singleton", singleton.to_string_lossy());
+        }
+
         log::info!("{:18}: {}", "Output", &self.output.display());
 
-        if self.args.is_some() {
-            log::info!(
-                "{:18}: {}",
-                "Opt params",
-                &self.args.as_ref().expect("NO ARGS")
-            );
-        }
+        log::info!("{:18}: {}", "Opt params", self.get_spades_args().join(" "));
         log::info!("");
 
         Ok(())
     }
 
+    fn get_symlink(&self) -> PathBuf {
+        self.symlink_dir
+            .join(format!("{}_contigs.fasta", self.reads.id))
+    }
+
+    // Also true for a broken symlink.
+    fn has_symlink(&self) -> bool {
+        self.get_symlink().symlink_metadata().is_ok()
+    }
+
+    fn remove_output(&self) -> Result<()> {
+        if self.output.exists() {
+            fs::remove_dir_all(&self.output)?;
+        }
+        if self.has_symlink() {
+            fs::remove_file(self.get_symlink())?;
+        }
+        Ok(())
+    }
+
+    // Samples with metadata get an annotated copy of the contigs
+    // instead of a symlink.
     fn create_symlink(&self) {
-        let contig_sym = format!("{}_contigs.fasta", self.reads.id);
         let contigs_path = self.output.join("contigs.fasta");
 
         if contigs_path.is_file() {
-            #[cfg(not(target_os = "windows"))]
-            let path = contigs_path.canonicalize().expect("NO FILE");
-            let symlink = self.symlink_dir.join(contig_sym);
-            #[cfg(not(target_os = "windows"))]
-            unix::fs::symlink(path, &symlink).expect("CAN'T CREATE SYMLINK");
+            let symlink = self.get_symlink();
+            if self.reads.metadata.is_empty() {
+                #[cfg(not(target_os = "windows"))]
+                let path = contigs_path.canonicalize().expect("NO FILE");
+                #[cfg(not(target_os = "windows"))]
+                unix::fs::symlink(path, &symlink).expect("CAN'T CREATE SYMLINK");
+            } else {
+                self.write_annotated_contigs(&contigs_path, &symlink)
+                    .expect("CAN'T WRITE ANNOTATED CONTIGS");
+            }
             self.print_contig_path(&contigs_path, &symlink);
         } else {
             log::warn!(
@@ -221,6 +365,23 @@ impl<'a> Runner<'a> {
         }
     }
 
+    // Add the sample id and metadata to the description of every contig,
+    // e.g. `>NODE_1_length_500_cov_10.5 id=ABC1 voucher=KU_1`.
+    fn write_annotated_contigs(&self, contigs: &Path, output: &Path) -> Result<()> {
+        let reader = BufReader::new(File::open(contigs)?);
+        let mut writer = BufWriter::new(File::create(output)?);
+        let description = get_contig_description(self.reads);
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('>') {
+                writeln!(writer, "{} {}", line, description)?;
+            } else {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        writer.flush()
+    }
+
     fn print_contig_path(&self, path: &Path, symlink: &Path) {
         println!();
         log::info!("{}", "Contig Path".yellow());
@@ -230,15 +391,91 @@ impl<'a> Runner<'a> {
     }
 }
 
+// Spaces are not allowed in a fasta description field value.
+fn get_contig_description(sample: &Sample) -> String {
+    let mut fields = vec![format!("id={}", sample.id)];
+    sample.metadata.iter().for_each(|(key, value)| {
+        fields.push(format!("{}={}", key, value.replace(' ', "_")));
+    });
+    fields.join(" ")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn contig_description_test() {
+        let mut sample = Sample::new("ABC1", Path::new("."));
+        sample.metadata = vec![
+            (String::from("voucher"), String::from("KU 1")),
+            (String::from("locality"), String::from("Sulawesi")),
+        ];
+
+        assert_eq!(
+            "id=ABC1 voucher=KU_1 locality=Sulawesi",
+            get_contig_description(&sample)
+        );
+    }
+
+    #[test]
+    fn complete_assembly_test() {
+        let dir = std::env::temp_dir().join("yap_complete_assembly_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("contigs.fasta"), ">NODE_1\nACGT\n").unwrap();
+        fs::write(dir.join("spades.log"), "Assembling\n").unwrap();
+        let is_running = is_complete(&dir);
+        fs::write(
+            dir.join("spades.log"),
+            "======= SPAdes pipeline finished.\n",
+        )
+        .unwrap();
+        let is_finished = is_complete(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!is_running);
+        assert!(is_finished);
+    }
+
     #[test]
     fn output_dir_test() {
         let path = PathBuf::from("test/assemblies/");
         let output_dir = get_output_dir(Some(Path::new("test/assemblies")));
 
-        assert_eq!(PathBuf::from(path), output_dir);
+        assert_eq!(path, output_dir);
+    }
+
+    #[test]
+    fn spades_command_test() {
+        let mut sample = Sample::new("ABC1", Path::new("."));
+        sample.layout = sheet::Layout::Single;
+        sample.read_1 = PathBuf::from("ABC1_R1.fq.gz");
+        sample.spades_opts = Some(String::from("--cov-cutoff auto"));
+        let mut settings = AssemblySettings {
+            threads: Some(4),
+            output_dir: None,
+            args: None,
+            spades_options: SpadesOptions::default(),
+            resume: false,
+            include_failed: false,
+            deinterleave: false,
+        };
+        let default_cmd = get_spades_command(&sample, &NamingScheme::Auto, &settings);
+        settings.spades_options.kmers = vec![21, 33];
+        settings.spades_options.memory = Some(16);
+        let typed_cmd = get_spades_command(&sample, &NamingScheme::Auto, &settings);
+        settings.args = Some("--cov-cutoff 10");
+        let raw_cmd = get_spades_command(&sample, &NamingScheme::Auto, &settings);
+        settings.args = Some("--isolate");
+        sample.spades_opts = Some(String::from("replace: --meta"));
+        let replaced_cmd = get_spades_command(&sample, &NamingScheme::Auto, &settings);
+
+        assert_eq!(
+            "spades.py -s ABC1_R1.fq.gz -o assemblies/ABC1 --careful --cov-cutoff auto --threads 4",
+            default_cmd
+        );
+        assert!(typed_cmd.contains("--careful -k 21,33 --memory 16 --cov-cutoff auto"));
+        assert!(raw_cmd.contains("--careful"));
+        assert!(replaced_cmd.contains("-o assemblies/ABC1 --meta --threads"));
     }
 }
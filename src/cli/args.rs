@@ -3,6 +3,9 @@ use std::path::PathBuf;
 use clap::{crate_authors, crate_description, crate_name, crate_version, Args, Parser, Subcommand};
 
 use crate::cli;
+use crate::qc;
+use crate::report;
+use crate::sheet;
 
 #[derive(Parser)]
 #[command(name = crate_name!())]
@@ -37,6 +40,30 @@ pub(crate) enum MainSubcommand {
         long_about = None
     )]
     Qc(QcSubcommand),
+    #[command(
+        name = "validate",
+        about = "Checks a sample sheet and its reads before a run",
+        long_about = None
+    )]
+    Validate(ValidateSubcommand),
+    #[command(
+        name = "adapters",
+        about = "Detects the adapters of samples without adapters from their first reads",
+        long_about = None
+    )]
+    Adapters(AdaptersSubcommand),
+    #[command(
+        name = "summary",
+        about = "Summarizes the fastp reports of a yap qc output dir",
+        long_about = None
+    )]
+    Summary(SummarySubcommand),
+    #[command(
+        name = "report",
+        about = "Builds a single html report of the qc and assembly outputs",
+        long_about = None
+    )]
+    Report(ReportSubcommand),
     #[command(
         subcommand,
         name = "assembly",
@@ -60,8 +87,247 @@ pub(crate) struct NewSubcommand {
     pub(crate) len: usize,
     #[arg(short, long, default_value = "_", value_name = "SEP")]
     pub(crate) sep: char,
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Builds sample IDs from the named groups of a regex, joined by SEP"
+    )]
+    pub(crate) id_regex: Option<String>,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Builds sample IDs from a template, e.g. {genus}_{species}_{voucher}"
+    )]
+    pub(crate) id_template: Option<String>,
+    #[arg(
+        long,
+        help = "Shows the sample ID of every file without writing the config"
+    )]
+    pub(crate) preview: bool,
+    #[arg(
+        long,
+        default_value = "suffix",
+        value_parser = ["suffix", "merge"],
+        help = "Resolves duplicate sample IDs by adding a suffix or by merging them as runs of one sample"
+    )]
+    pub(crate) duplicates: String,
+    #[arg(
+        long,
+        default_value = "_{n}",
+        value_name = "TEMPLATE",
+        help = "Suffix for duplicate sample IDs. {n} is the copy number, starting from 2"
+    )]
+    pub(crate) dup_suffix: String,
     #[arg(long, help = "Save as csv")]
     pub(crate) csv: bool,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+}
+
+#[derive(Args)]
+pub(crate) struct ValidateSubcommand {
+    #[arg(short, long, default_value = "yap-qc_input.conf", value_name = "INPUT")]
+    pub(crate) input: PathBuf,
+    #[arg(long, help = "Expects an output name column")]
+    pub(crate) rename: bool,
+    #[arg(
+        long,
+        default_value_t = sheet::MIN_INDEX_DISTANCE,
+        value_name = "MISMATCHES",
+        help = "Reports samples whose i7 and i5 indexes are fewer mismatches apart"
+    )]
+    pub(crate) min_index_distance: usize,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+}
+
+#[derive(Args)]
+pub(crate) struct AdaptersSubcommand {
+    #[arg(short, long, default_value = "yap-qc_input.conf", value_name = "INPUT")]
+    pub(crate) input: PathBuf,
+    #[arg(
+        short,
+        long,
+        value_name = "OUTPUT",
+        help = "Sample sheet with the detected adapters. Default is the input name with .adapters.csv"
+    )]
+    pub(crate) output: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = qc::DETECTION_READS,
+        value_name = "N",
+        help = "Reads or read pairs checked per sample"
+    )]
+    pub(crate) reads: usize,
+    #[arg(long, help = "Expects an output name column")]
+    pub(crate) rename: bool,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+}
+
+#[derive(Args)]
+pub(crate) struct SummarySubcommand {
+    #[arg(
+        short,
+        long,
+        default_value = "clean_reads",
+        value_name = "CLEAN-READ DIR"
+    )]
+    pub(crate) dir: PathBuf,
+    #[arg(
+        short,
+        long,
+        value_name = "OUTPUT",
+        help = "Summary file. Tab separated if it ends with .tsv. Default is yap-qc_summary.csv in the clean-read dir"
+    )]
+    pub(crate) output: Option<PathBuf>,
+    #[command(flatten)]
+    pub(crate) thresholds: ThresholdArgs,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "QC thresholds")]
+pub(crate) struct ThresholdArgs {
+    #[arg(
+        long,
+        value_name = "READS",
+        help = "Fails samples with fewer reads after filtering"
+    )]
+    pub(crate) min_reads: Option<u64>,
+    #[arg(
+        long,
+        value_name = "RATE",
+        help = "Fails samples with a lower Q30 rate after filtering, e.g. 0.8"
+    )]
+    pub(crate) min_q30: Option<f64>,
+    #[arg(
+        long,
+        value_name = "RATE",
+        help = "Fails samples with a higher duplication rate, e.g. 0.3"
+    )]
+    pub(crate) max_dup: Option<f64>,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Native engine")]
+pub(crate) struct NativeArgs {
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_name = "BASES",
+        help = "Sliding window size for quality trimming"
+    )]
+    pub(crate) window_size: usize,
+    #[arg(
+        long,
+        default_value_t = 20,
+        value_name = "PHRED",
+        help = "Cuts the read at the first window with a lower mean quality"
+    )]
+    pub(crate) window_quality: u8,
+    #[arg(
+        long,
+        default_value_t = 15,
+        value_name = "BASES",
+        help = "Drops read pairs with a read shorter than this after trimming"
+    )]
+    pub(crate) min_len: usize,
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_name = "BASES",
+        help = "Drops read pairs with a read with more N bases than this"
+    )]
+    pub(crate) max_n: usize,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "fastp options")]
+pub(crate) struct FastpArgs {
+    #[arg(
+        long,
+        value_name = "PHRED",
+        value_parser = clap::value_parser!(u8).range(0..=93),
+        help = "Phred quality of a qualified base"
+    )]
+    pub(crate) qualified_quality: Option<u8>,
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Drops reads with a higher percent of unqualified bases"
+    )]
+    pub(crate) unqualified_limit: Option<u8>,
+    #[arg(
+        long,
+        value_name = "BASES",
+        help = "Drops reads shorter than this after trimming"
+    )]
+    pub(crate) length_required: Option<usize>,
+    #[arg(
+        long,
+        help = "Trims reads with a sliding window from the 5' end to the 3' end"
+    )]
+    pub(crate) cut_right: bool,
+    #[arg(
+        long,
+        value_name = "PHRED",
+        value_parser = clap::value_parser!(u8).range(1..=36),
+        help = "Mean quality of the sliding windows"
+    )]
+    pub(crate) cut_mean_quality: Option<u8>,
+    #[arg(long, help = "Removes duplicated reads")]
+    pub(crate) dedup: bool,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "SPAdes options")]
+pub(crate) struct SpadesArgs {
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = ["careful", "isolate", "sc", "meta", "plasmid", "rna", "metaviral", "metaplasmid", "rnaviral"],
+        help = "Assembly mode. Default is careful"
+    )]
+    pub(crate) mode: Option<String>,
+    #[arg(
+        long,
+        value_name = "SIZES",
+        help = "Comma separated odd k-mer sizes below 128, or auto"
+    )]
+    pub(crate) kmers: Option<String>,
+    #[arg(long, value_name = "GB", help = "Memory limit of SPAdes")]
+    pub(crate) memory: Option<usize>,
+}
+
+#[derive(Args)]
+pub(crate) struct ReportSubcommand {
+    #[arg(
+        short,
+        long,
+        default_value = "clean_reads",
+        value_name = "CLEAN-READ DIR"
+    )]
+    pub(crate) qc_dir: PathBuf,
+    #[arg(short, long, default_value = "assemblies", value_name = "ASSEMBLY DIR")]
+    pub(crate) assembly_dir: PathBuf,
+    #[arg(short, long, default_value = report::REPORT_FILE, value_name = "OUTPUT")]
+    pub(crate) output: PathBuf,
 }
 
 #[derive(Args)]
@@ -72,10 +338,70 @@ pub(crate) struct QcSubcommand {
     pub(crate) dry_run: bool,
     #[arg(long, help = "Renames output files")]
     pub(crate) rename: bool,
+    #[arg(
+        long,
+        default_value_t = sheet::MIN_INDEX_DISTANCE,
+        value_name = "MISMATCHES",
+        help = "Reports samples whose i7 and i5 indexes are fewer mismatches apart"
+    )]
+    pub(crate) min_index_distance: usize,
+    #[arg(
+        long,
+        help = "Keeps the output dir and only processes samples without complete outputs"
+    )]
+    pub(crate) resume: bool,
     #[arg(short, long, value_name = "OUTPUT DIR")]
     pub(crate) output: Option<PathBuf>,
-    #[arg(long, value_name = "OPTIONAL PARAMS")]
+    #[arg(
+        short,
+        long,
+        value_name = "THREAD-NUM",
+        help = "Total threads shared by the fastp jobs. Default is all available threads"
+    )]
+    pub(crate) threads: Option<usize>,
+    #[arg(
+        short,
+        long,
+        value_name = "JOB-NUM",
+        help = "Number of samples cleaned at once. Default is one for every four threads"
+    )]
+    pub(crate) jobs: Option<usize>,
+    #[arg(
+        long,
+        default_value = "fastp",
+        value_parser = ["fastp", "native"],
+        help = "Trimming engine. The native engine is built in and does not need fastp"
+    )]
+    pub(crate) engine: String,
+    #[arg(long, help = "Exits with zero even if samples fail the QC thresholds")]
+    pub(crate) allow_failed: bool,
+    #[arg(
+        long,
+        help = "Splits interleaved reads into read 1 and read 2 files before trimming"
+    )]
+    pub(crate) deinterleave: bool,
+    #[arg(
+        long,
+        value_name = "OPTIONAL PARAMS",
+        allow_hyphen_values = true,
+        help = "Tool options, quoted as in a shell"
+    )]
     pub(crate) opts: Option<String>,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+    // Flattened last, so their help headings do not carry over
+    // to the options above.
+    #[command(flatten)]
+    pub(crate) fastp: FastpArgs,
+    #[command(flatten)]
+    pub(crate) native: NativeArgs,
+    #[command(flatten)]
+    pub(crate) thresholds: ThresholdArgs,
 }
 
 #[derive(Subcommand)]
@@ -86,7 +412,11 @@ pub(crate) enum AssemblySubcommand {
         long_about = None
     )]
     Auto(AutoArgs),
-    #[command(name = "conf", about = "Runs SPAdes using a config file", long_about = None)]
+    #[command(
+        name = "conf",
+        about = "Runs SPAdes using a sample sheet",
+        long_about = None
+    )]
     Conf(ConfArgs),
     #[command(name = "clean", about = "Cleans unused SPAdes files.")]
     Clean(CleanArgs),
@@ -113,29 +443,94 @@ pub(crate) struct AutoArgs {
     pub(crate) output: Option<PathBuf>,
     #[arg(long, help = "Checks if the program can find the correct files")]
     pub(crate) dry_run: bool,
+    #[arg(
+        long,
+        help = "Keeps the output dir and only processes samples without complete outputs"
+    )]
+    pub(crate) resume: bool,
+    #[arg(long, help = "Assembles samples that failed the QC thresholds")]
+    pub(crate) include_failed: bool,
+    #[arg(
+        long,
+        help = "Splits interleaved reads into read 1 and read 2 files before running SPAdes"
+    )]
+    pub(crate) deinterleave: bool,
     #[arg(short, long, value_name = "THREAD-NUM")]
     pub(crate) threads: Option<usize>,
-    #[arg(long, value_name = "OPTIONAL PARAMS")]
+    #[arg(
+        long,
+        value_name = "OPTIONAL PARAMS",
+        allow_hyphen_values = true,
+        help = "Tool options, quoted as in a shell"
+    )]
     pub(crate) opts: Option<String>,
     #[arg(
         long,
         help = "Keep all intermediate SPAdes files. Default is to keep only the contigs, scaffolds, and log files."
     )]
     pub(crate) keep_all: bool,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+    // Flattened last, so its help heading does not carry over
+    // to the options above.
+    #[command(flatten)]
+    pub(crate) spades: SpadesArgs,
 }
 
 #[derive(Args)]
 pub(crate) struct ConfArgs {
     #[arg(short, long, value_name = "INPUT")]
     pub(crate) input: PathBuf,
+    #[arg(
+        long,
+        value_name = "CLEAN-READ DIR",
+        help = "Use clean reads from the yap qc output dir instead of the reads in the input file"
+    )]
+    pub(crate) clean_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Input file has an output name column (as in yap qc --rename)"
+    )]
+    pub(crate) rename: bool,
     #[arg(long, help = "Checks if the program detect the correct files")]
     pub(crate) dry_run: bool,
+    #[arg(
+        long,
+        help = "Keeps the output dir and only processes samples without complete outputs"
+    )]
+    pub(crate) resume: bool,
+    #[arg(long, help = "Assembles samples that failed the QC thresholds")]
+    pub(crate) include_failed: bool,
+    #[arg(
+        long,
+        help = "Splits interleaved reads into read 1 and read 2 files before running SPAdes"
+    )]
+    pub(crate) deinterleave: bool,
     #[arg(short, long, value_name = "THREAD-NUM")]
     pub(crate) threads: Option<usize>,
     #[arg(short, long, value_name = "OUTPUT DIR")]
     pub(crate) output: Option<PathBuf>,
-    #[arg(long, value_name = "OPTIONAL PARAMS")]
+    #[arg(
+        long,
+        value_name = "OPTIONAL PARAMS",
+        allow_hyphen_values = true,
+        help = "Tool options, quoted as in a shell"
+    )]
     pub(crate) opts: Option<String>,
+    #[arg(
+        long,
+        default_value = "auto",
+        value_name = "SCHEME",
+        help = "Read naming scheme: auto, illumina, sra, yap, or a regex with (?P<sample>...) and (?P<read>...) groups"
+    )]
+    pub(crate) naming: String,
+    #[command(flatten)]
+    pub(crate) spades: SpadesArgs,
 }
 
 #[derive(Args)]
@@ -1,4 +1,6 @@
 use std::io::Result;
+use std::path::Path;
+use std::process;
 
 use clap::crate_version;
 use log::LevelFilter;
@@ -9,13 +11,22 @@ use log4rs::encode::pattern::PatternEncoder;
 
 use crate::assembly;
 use crate::assembly::cleaner;
+use crate::assembly::options::parse_kmers;
+use crate::assembly::{AssemblySettings, SpadesMode, SpadesOptions};
 use crate::cli::args;
-use crate::init::Init;
-use crate::qc::Qc;
+use crate::init::{DuplicateIds, IdFormat, Init};
+use crate::qc::{self, AdapterDetector, Engine, FastpOptions, NativeSettings, Qc, QcThresholds};
+use crate::report::Report;
+use crate::sheet::{NamingScheme, Validator};
 use crate::utils::checker::DependencyChecker;
 use clap::Parser;
+use colored::Colorize;
 
-use super::args::{AssemblySubcommand, AutoArgs, CleanArgs, ConfArgs, NewSubcommand, QcSubcommand};
+use super::args::{
+    AdaptersSubcommand, AssemblySubcommand, AutoArgs, CleanArgs, ConfArgs, FastpArgs, NativeArgs,
+    NewSubcommand, QcSubcommand, ReportSubcommand, SpadesArgs, SummarySubcommand, ThresholdArgs,
+    ValidateSubcommand,
+};
 
 pub const LOG_FILE: &str = "yap.log";
 
@@ -27,18 +38,124 @@ pub fn parse_cli() {
         args::MainSubcommand::Check(arg) => DependencyChecker::new(arg.auto_install).check(),
         args::MainSubcommand::New(new) => parse_new_cli(&new),
         args::MainSubcommand::Qc(qc) => QcCli::new(&qc, version).parse(),
+        args::MainSubcommand::Summary(summary) => parse_summary_cli(&summary),
+        args::MainSubcommand::Report(report) => parse_report_cli(&report),
+        args::MainSubcommand::Validate(validate) => parse_validate_cli(&validate),
+        args::MainSubcommand::Adapters(adapters) => parse_adapters_cli(&adapters),
         args::MainSubcommand::Assembly(assembly) => Spades::new(&assembly, version).parse(),
     };
 }
 
 fn parse_new_cli(command: &NewSubcommand) {
     let path = command.dir.as_path();
-    let len = command.len;
-    let sep = command.sep;
+    let id_format = IdFormat::new(
+        command.len,
+        command.sep,
+        command.id_regex.as_deref(),
+        command.id_template.as_deref(),
+    );
     let is_csv = command.csv;
-    let mut init = Init::new(path, len, sep, is_csv);
+    let scheme = NamingScheme::from_arg(&command.naming);
+    let mut init = Init::new(path, id_format, is_csv, &scheme);
+    init.duplicates = DuplicateIds::from_arg(&command.duplicates, &command.dup_suffix);
 
-    init.initialize_input_file();
+    if command.preview {
+        init.preview();
+    } else {
+        init.initialize_input_file();
+    }
+}
+
+fn parse_validate_cli(command: &ValidateSubcommand) {
+    let scheme = NamingScheme::from_arg(&command.naming);
+    let mut validator = Validator::new(&command.input, command.rename, &scheme);
+    validator.min_index_distance = command.min_index_distance;
+    if !validator.validate() {
+        process::exit(1);
+    }
+}
+
+fn parse_adapters_cli(command: &AdaptersSubcommand) {
+    let scheme = NamingScheme::from_arg(&command.naming);
+    let output = match &command.output {
+        Some(output) => output.clone(),
+        None => command.input.with_extension("adapters.csv"),
+    };
+    let mut detector = AdapterDetector::new(&command.input, &output, command.rename, &scheme);
+    detector.max_reads = command.reads;
+    detector.detect();
+}
+
+fn parse_summary_cli(command: &SummarySubcommand) {
+    let output = match &command.output {
+        Some(output) => output.clone(),
+        None => command.dir.join(qc::SUMMARY_FILE),
+    };
+    let thresholds = get_thresholds(&command.thresholds);
+    qc::write_summary(&command.dir, &output, &thresholds).expect("CAN'T WRITE QC SUMMARY");
+    log::info!("{:18}: {}", "QC summary", output.display());
+}
+
+fn get_thresholds(args: &ThresholdArgs) -> QcThresholds {
+    QcThresholds {
+        min_reads: args.min_reads,
+        min_q30: args.min_q30,
+        max_duplication: args.max_dup,
+    }
+}
+
+fn get_native_settings(args: &NativeArgs) -> NativeSettings {
+    NativeSettings {
+        window_size: args.window_size,
+        window_quality: args.window_quality,
+        min_len: args.min_len,
+        max_n: args.max_n,
+    }
+}
+
+fn get_fastp_options(args: &FastpArgs) -> FastpOptions {
+    FastpOptions {
+        qualified_quality: args.qualified_quality,
+        unqualified_limit: args.unqualified_limit,
+        length_required: args.length_required,
+        cut_right: args.cut_right,
+        cut_mean_quality: args.cut_mean_quality,
+        dedup: args.dedup,
+    }
+}
+
+fn get_spades_options(args: &SpadesArgs) -> SpadesOptions {
+    let kmers = match &args.kmers {
+        Some(kmers) => parse_kmers(kmers).unwrap_or_else(|e| panic!("{}", e)),
+        None => Vec::new(),
+    };
+    let options = SpadesOptions {
+        mode: args.mode.as_deref().and_then(SpadesMode::from_name),
+        kmers,
+        memory: args.memory,
+    };
+    options.validate().unwrap_or_else(|e| panic!("{}", e));
+    options
+}
+
+fn parse_report_cli(command: &ReportSubcommand) {
+    let get_dir = |dir: &Path| {
+        if dir.is_dir() {
+            Some(dir.to_path_buf())
+        } else {
+            log::warn!("Skipping {}. The directory does not exist.", dir.display());
+            None
+        }
+    };
+    let qc_dir = get_dir(&command.qc_dir);
+    let assembly_dir = get_dir(&command.assembly_dir);
+    if qc_dir.is_none() && assembly_dir.is_none() {
+        panic!("CANNOT FIND THE QC OR ASSEMBLY OUTPUT DIR");
+    }
+    let report = Report::new(qc_dir.as_deref(), assembly_dir.as_deref());
+    report.write(&command.output).expect("CAN'T WRITE REPORT");
+    log::info!("{:18}: {} samples", "Report", report.samples.len());
+    log::info!("{:18}: {}", "Output", command.output.display());
 }
 
 struct QcCli<'a> {
@@ -57,14 +174,42 @@ impl<'a> QcCli<'a> {
         let optional_params = self.matches.opts.as_deref();
         let output_dir = self.matches.output.as_deref();
         let is_dry_run = self.matches.dry_run;
+        let scheme = NamingScheme::from_arg(&self.matches.naming);
 
-        let runner = Qc::new(input_path, is_rename, optional_params, output_dir);
+        let mut runner = Qc::new(input_path, is_rename, optional_params, output_dir, &scheme);
+        runner.threads = self.matches.threads;
+        runner.jobs = self.matches.jobs;
+        runner.resume = self.matches.resume;
+        runner.deinterleave = self.matches.deinterleave;
+        runner.min_index_distance = self.matches.min_index_distance;
+        runner.thresholds = get_thresholds(&self.matches.thresholds);
+        runner.fastp_options = get_fastp_options(&self.matches.fastp);
+        runner.engine = Engine::from_arg(
+            &self.matches.engine,
+            get_native_settings(&self.matches.native),
+        );
 
         if is_dry_run {
             runner.dry_run();
         } else {
             log::info!("Starting YAP-qc v{}...\n", self.version);
-            runner.run();
+            let failed = runner.run();
+            self.check_failed(&failed);
+        }
+    }
+
+    fn check_failed(&self, failed: &[String]) {
+        if failed.is_empty() {
+            return;
+        }
+        log::warn!(
+            "{} {} samples failed the QC thresholds or have no fastp report: {}",
+            "[WARNING]".yellow(),
+            failed.len(),
+            failed.join(", ")
+        );
+        if !self.matches.allow_failed {
+            process::exit(1);
         }
     }
 }
@@ -89,40 +234,51 @@ impl<'a> Spades<'a> {
 
     fn run_auto(&self, matches: &AutoArgs) {
         let input_dir = matches.dir.as_path();
-        let output = matches.output.as_deref();
-        let optional_params = matches.opts.as_deref();
-        let threads = matches.threads;
         let dry_run = matches.dry_run;
+        let scheme = NamingScheme::from_arg(&matches.naming);
+        let settings = AssemblySettings {
+            threads: matches.threads,
+            output_dir: matches.output.as_deref(),
+            args: matches.opts.as_deref(),
+            spades_options: get_spades_options(&matches.spades),
+            resume: matches.resume,
+            include_failed: matches.include_failed,
+            deinterleave: matches.deinterleave,
+        };
         if dry_run {
-            assembly::auto_dry_run(input_dir, &matches.specify);
+            assembly::auto_dry_run(input_dir, &matches.specify, &scheme, &settings);
         } else {
             self.print_header();
-            assembly::auto_process_input(
-                input_dir,
-                &matches.specify,
-                threads,
-                output,
-                optional_params,
-            );
+            assembly::auto_process_input(input_dir, &matches.specify, &scheme, &settings);
         }
     }
 
     fn run(&self, matches: &ConfArgs) {
         let config = matches.input.as_path();
-        let threads = matches.threads;
-        let output = matches.output.as_deref();
+        let clean_dir = matches.clean_dir.as_deref();
+        let is_rename = matches.rename;
         let dry_run = matches.dry_run;
+        let scheme = NamingScheme::from_arg(&matches.naming);
+        let settings = AssemblySettings {
+            threads: matches.threads,
+            output_dir: matches.output.as_deref(),
+            args: matches.opts.as_deref(),
+            spades_options: get_spades_options(&matches.spades),
+            resume: matches.resume,
+            include_failed: matches.include_failed,
+            deinterleave: matches.deinterleave,
+        };
         if dry_run {
-            assembly::dry_run(config);
+            assembly::dry_run(config, clean_dir, is_rename, &scheme, &settings);
         } else {
             self.print_header();
-            assembly::process_input(config, threads, output, None);
+            assembly::process_input(config, clean_dir, is_rename, &scheme, &settings);
         }
     }
 
     fn clean_files(&self, matches: &CleanArgs) {
         let dir = matches.dir.as_path();
-        cleaner::clean_spades_files(&dir);
+        cleaner::clean_spades_files(dir);
     }
 
     fn print_header(&self) {
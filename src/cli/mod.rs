@@ -1,2 +1,3 @@
 mod args;
+#[allow(clippy::module_inception)]
 pub mod cli;
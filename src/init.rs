@@ -1,28 +1,232 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{LineWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use regex::Regex;
+use colored::Colorize;
+use regex::{Captures, Regex};
 use walkdir::WalkDir;
 
+use crate::sheet::{self, NamingScheme, ReadType};
+
+/// Read files of a library found in the input directory.
+#[derive(Default, Clone)]
+struct ReadPair {
+    read_1: Option<PathBuf>,
+    read_2: Option<PathBuf>,
+    singleton: Option<PathBuf>,
+    lane: Option<String>,
+}
+
+impl ReadPair {
+    fn is_complete(&self) -> bool {
+        self.read_1.is_some() && self.read_2.is_some()
+    }
+}
+
+#[derive(Clone)]
+enum FileIssue {
+    // Read 1 or read 2 without its mate.
+    Orphan,
+    // Fastq without a read token that does not belong to any pair.
+    Unpaired,
+    // File that matches more than one read slot.
+    Ambiguous,
+    // Pair whose file name does not give a sample id.
+    NoId,
+}
+
+impl fmt::Display for FileIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileIssue::Orphan => write!(f, "ORPHAN"),
+            FileIssue::Unpaired => write!(f, "UNPAIRED"),
+            FileIssue::Ambiguous => write!(f, "AMBIGUOUS"),
+            FileIssue::NoId => write!(f, "NO ID"),
+        }
+    }
+}
+
+// Parent directory, the name before the read token, and the name after it.
+type PairKey = (PathBuf, String, String);
+
+/// How `yap new` builds a sample id from a file name.
+pub enum IdFormat {
+    /// The first `len` words split by `sep`.
+    Words { len: usize, sep: char },
+    /// Named groups of a regex joined by `sep`, in pattern order.
+    Regex { re: Regex, sep: char },
+    /// Named groups of a regex put into a template, e.g. `{genus}_{species}`.
+    Template { re: Regex, template: String },
+}
+
+impl IdFormat {
+    pub fn new(len: usize, sep: char, regex: Option<&str>, template: Option<&str>) -> Self {
+        match (regex, template) {
+            (Some(regex), Some(template)) => {
+                let re = Self::compile(regex);
+                Self::check_template(&re, template);
+                Self::Template {
+                    re,
+                    template: template.to_string(),
+                }
+            }
+            (Some(regex), None) => Self::Regex {
+                re: Self::compile(regex),
+                sep,
+            },
+            (None, Some(template)) => Self::Template {
+                re: Self::compile(&Self::template_to_regex(template)),
+                template: template.to_string(),
+            },
+            (None, None) => Self::Words { len, sep },
+        }
+    }
+
+    /// Returns None if the file name does not fit the format.
+    pub fn get_id(&self, fname: &str) -> Option<String> {
+        match self {
+            Self::Words { len, sep } => Self::get_words(fname, *len, *sep),
+            Self::Regex { re, sep } => {
+                let caps = re.captures(fname)?;
+                let words: Vec<&str> = re
+                    .capture_names()
+                    .flatten()
+                    .filter_map(|name| caps.name(name))
+                    .map(|m| m.as_str())
+                    .collect();
+                if words.is_empty() {
+                    return None;
+                }
+                Some(words.join(&sep.to_string()))
+            }
+            Self::Template { re, template } => {
+                let caps = re.captures(fname)?;
+                Some(Self::render(template, &caps))
+            }
+        }
+    }
+
+    fn get_words(fname: &str, len: usize, sep: char) -> Option<String> {
+        let words: Vec<&str> = fname.split(sep).collect();
+        // The last word holds the read token and the extension.
+        if len == 0 || words.len() <= len {
+            return None;
+        }
+        Some(words[..len].join(&sep.to_string()))
+    }
+
+    fn compile(pattern: &str) -> Regex {
+        match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(e) => panic!("INVALID ID PATTERN {}: {}", pattern, e),
+        }
+    }
+
+    // Each field matches one word. The rest of the template
+    // must match the start of the file name as it is.
+    fn template_to_regex(template: &str) -> String {
+        let mut pattern = String::from("^");
+        let mut last = 0;
+        Self::field_regex()
+            .captures_iter(template)
+            .for_each(|caps| {
+                let field = caps.get(0).unwrap();
+                pattern.push_str(&regex::escape(&template[last..field.start()]));
+                pattern.push_str(&format!(r"(?P<{}>[^_.\-]+)", &caps[1]));
+                last = field.end();
+            });
+        pattern.push_str(&regex::escape(&template[last..]));
+        pattern
+    }
+
+    fn check_template(re: &Regex, template: &str) {
+        Self::field_regex()
+            .captures_iter(template)
+            .for_each(|caps| {
+                if !re.capture_names().any(|n| n == Some(&caps[1])) {
+                    panic!(
+                        "UNKNOWN FIELD {{{}}} IN ID TEMPLATE. \
+                    THE REGEX MUST HAVE A (?P<{}>...) GROUP.",
+                        &caps[1], &caps[1]
+                    );
+                }
+            });
+    }
+
+    fn render(template: &str, caps: &Captures) -> String {
+        Self::field_regex()
+            .replace_all(template, |field: &Captures| {
+                caps.name(&field[1]).map_or("", |m| m.as_str()).to_string()
+            })
+            .to_string()
+    }
+
+    fn field_regex() -> &'static Regex {
+        lazy_static! {
+            static ref FIELD: Regex = Regex::new(r"\{(\w+)\}").unwrap();
+        }
+        &FIELD
+    }
+}
+
+/// How `yap new` resolves two read pairs with the same sample id.
+pub enum DuplicateIds {
+    /// Give the later pairs a new id from a suffix template, e.g. `_{n}`.
+    Suffix(String),
+    /// Treat the pairs as runs of one sample and merge them before qc.
+    Merge,
+}
+
+impl DuplicateIds {
+    pub fn from_arg(mode: &str, suffix: &str) -> Self {
+        match mode {
+            "merge" => Self::Merge,
+            "suffix" => {
+                if !suffix.contains("{n}") {
+                    panic!("INVALID SUFFIX {}. THE SUFFIX MUST CONTAIN {{n}}.", suffix);
+                }
+                Self::Suffix(suffix.to_string())
+            }
+            _ => panic!("UNKNOWN DUPLICATE MODE {}. USE suffix OR merge.", mode),
+        }
+    }
+}
+
+// Original id, read 1 of the duplicate pair, and the id it is written under.
+type Duplicate = (String, PathBuf, String);
+
 pub struct Init<'a> {
     path: &'a Path,
-    len: usize,
-    sep: char,
+    pub duplicates: DuplicateIds,
+    id_format: IdFormat,
     is_csv: bool,
+    scheme: &'a NamingScheme,
     fname: String,
+    file_count: usize,
+    issues: Vec<(FileIssue, PathBuf)>,
+    resolved: Vec<Duplicate>,
 }
 
 impl<'a> Init<'a> {
-    pub fn new(path: &'a Path, len: usize, sep: char, is_csv: bool) -> Self {
+    pub fn new(
+        path: &'a Path,
+        id_format: IdFormat,
+        is_csv: bool,
+        scheme: &'a NamingScheme,
+    ) -> Self {
         Self {
             path,
-            len,
-            sep,
+            duplicates: DuplicateIds::Suffix(String::from("_{n}")),
+            id_format,
             is_csv,
+            scheme,
             fname: String::from("yap-qc_input"),
+            file_count: 0,
+            issues: Vec::new(),
+            resolved: Vec::new(),
         }
     }
 
@@ -32,39 +236,209 @@ impl<'a> Init<'a> {
         let mut line = LineWriter::new(output);
         let seqs = self.find_files();
         self.write_header(&mut line);
-        let file_count = seqs.len();
         let mut sample_count = 0;
-        seqs.iter().for_each(|(id, path)| {
-            self.write_content(&mut line, id, path);
+        seqs.iter().for_each(|(id, lanes)| {
+            self.write_content(&mut line, id, lanes);
             sample_count += 1;
         });
 
-        self.print_saved_path(file_count, sample_count);
+        self.print_duplicates();
+        self.print_issues();
+        self.print_saved_path(self.file_count, sample_count);
+    }
+
+    /// Show the sample id of every file without writing the config.
+    pub fn preview(&mut self) {
+        let seqs = self.find_files();
+        let width = seqs.keys().map(|id| id.len()).max().unwrap_or(0).max(2) + 2;
+        println!("{:width$}File", "ID", width = width);
+        seqs.iter().for_each(|(id, lanes)| {
+            lanes
+                .iter()
+                .flat_map(|reads| [&reads.read_1, &reads.read_2, &reads.singleton])
+                .flatten()
+                .for_each(|file| println!("{:width$}{}", id, file.display(), width = width));
+        });
+        println!();
+        self.print_duplicates();
+        self.print_issues();
+        println!(
+            "Found {} samples of {} files. No file is written in preview mode.",
+            seqs.len(),
+            self.file_count
+        );
     }
 
-    fn find_files(&self) -> HashMap<String, String> {
-        let mut seq = HashMap::new();
-        WalkDir::new(self.path)
+    // Pairs from different lanes of the same sample are kept together
+    // and merged before qc.
+    fn find_files(&mut self) -> BTreeMap<String, Vec<ReadPair>> {
+        let files = self.find_fastq();
+        self.file_count = files.len();
+        let pairs = self.pair_reads(&files);
+        let mut seq: BTreeMap<String, Vec<ReadPair>> = BTreeMap::new();
+        // Suffixed ids by the original id and the directory of the pair,
+        // so the other lanes in the same directory follow.
+        let mut renamed: HashMap<(String, PathBuf), String> = HashMap::new();
+        pairs.into_iter().for_each(|((dir, _, _), pair)| {
+            if !pair.is_complete() {
+                self.add_orphans(pair);
+                return;
+            }
+            let read_1 = pair.read_1.as_ref().expect("MISSING READ 1");
+            let id = match self.get_id(read_1) {
+                Some(id) => id,
+                None => return self.add_issues(FileIssue::NoId, pair),
+            };
+            let key = (id.clone(), dir);
+            let id = renamed.get(&key).cloned().unwrap_or(id);
+            match seq.get_mut(&id) {
+                None => {
+                    seq.insert(id, vec![pair]);
+                }
+                Some(lanes) if self.is_new_lane(lanes, &pair) => lanes.push(pair),
+                Some(lanes) => {
+                    let new_id = match &self.duplicates {
+                        DuplicateIds::Merge => {
+                            lanes.push(pair.clone());
+                            id.clone()
+                        }
+                        DuplicateIds::Suffix(suffix) => {
+                            let new_id = self.get_suffixed_id(&seq, &id, suffix);
+                            renamed.insert(key, new_id.clone());
+                            seq.insert(new_id.clone(), vec![pair.clone()]);
+                            new_id
+                        }
+                    };
+                    let read_1 = pair.read_1.expect("MISSING READ 1");
+                    self.resolved.push((id, read_1, new_id));
+                }
+            }
+        });
+
+        seq
+    }
+
+    // The first unused id, starting from the second copy.
+    fn get_suffixed_id(
+        &self,
+        seq: &BTreeMap<String, Vec<ReadPair>>,
+        id: &str,
+        suffix: &str,
+    ) -> String {
+        (2..)
+            .map(|n| format!("{}{}", id, suffix.replace("{n}", &n.to_string())))
+            .find(|new_id| !seq.contains_key(new_id))
+            .expect("NO UNUSED SAMPLE ID")
+    }
+
+    fn find_fastq(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = WalkDir::new(self.path)
             .into_iter()
             .filter_map(|ok| ok.ok())
             .filter(|e| e.file_type().is_file())
-            .for_each(|e| {
-                let path = e.path();
-                let fname = e.path().file_name().unwrap().to_string_lossy();
-                if self.re_matches_lazy(&fname) {
-                    let id = self.construct_id(&fname);
-                    let full_path = String::from(
-                        path.parent()
-                            .unwrap()
-                            .canonicalize()
-                            .unwrap()
-                            .to_string_lossy(),
-                    );
-                    seq.entry(id).or_insert(full_path);
+            .filter(|e| sheet::is_fastq(&e.file_name().to_string_lossy()))
+            .map(|e| e.path().canonicalize().expect("CAN'T READ FILE PATH"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn pair_reads(&mut self, files: &[PathBuf]) -> BTreeMap<PairKey, ReadPair> {
+        let mut pairs: BTreeMap<PairKey, ReadPair> = BTreeMap::new();
+        let mut others = Vec::new();
+        files.iter().for_each(|file| {
+            let fname = file.file_name().unwrap().to_string_lossy();
+            match self.scheme.parse(&fname) {
+                Some(name) => {
+                    let parent = file.parent().unwrap().to_path_buf();
+                    let pair = pairs.entry((parent, name.prefix, name.suffix)).or_default();
+                    let slot = match name.read {
+                        ReadType::Read1 => &mut pair.read_1,
+                        ReadType::Read2 => &mut pair.read_2,
+                    };
+                    if slot.is_some() {
+                        self.issues.push((FileIssue::Ambiguous, file.to_path_buf()));
+                    } else {
+                        *slot = Some(file.to_path_buf());
+                        pair.lane = name.lane;
+                    }
                 }
-            });
+                None => others.push(file.to_path_buf()),
+            }
+        });
 
-        seq
+        others
+            .iter()
+            .for_each(|file| self.match_singleton(&mut pairs, file));
+        pairs
+    }
+
+    // A singleton shares the directory and the name before
+    // the read token with exactly one pair.
+    fn match_singleton(&mut self, pairs: &mut BTreeMap<PairKey, ReadPair>, file: &Path) {
+        let parent = file.parent().unwrap();
+        let fname = file.file_name().unwrap().to_string_lossy();
+        let mut matches: Vec<&mut ReadPair> = pairs
+            .iter_mut()
+            .filter(|((dir, prefix, _), _)| dir == parent && self.is_same_library(&fname, prefix))
+            .map(|(_, pair)| pair)
+            .collect();
+        match matches.len() {
+            0 => self.issues.push((FileIssue::Unpaired, file.to_path_buf())),
+            1 if matches[0].singleton.is_none() => matches[0].singleton = Some(file.to_path_buf()),
+            _ => self.issues.push((FileIssue::Ambiguous, file.to_path_buf())),
+        }
+    }
+
+    // A user given id regex or template comes first,
+    // then the sample name if the naming scheme defines it.
+    fn get_id(&self, read_1: &Path) -> Option<String> {
+        let fname = read_1.file_name().unwrap().to_string_lossy();
+        if let IdFormat::Words { .. } = self.id_format {
+            if let Some(sample) = self.scheme.parse(&fname).and_then(|name| name.sample) {
+                return Some(sample);
+            }
+        }
+        self.id_format.get_id(&fname)
+    }
+
+    // A lane of the same library sits in the same directory and its name
+    // only differs in the lane token. Other pairs with the same id are
+    // resolved as duplicates.
+    fn is_new_lane(&self, lanes: &[ReadPair], pair: &ReadPair) -> bool {
+        let library = self.get_library(pair);
+        pair.lane.is_some()
+            && library.is_some()
+            && lanes
+                .iter()
+                .all(|p| p.lane != pair.lane && self.get_library(p) == library)
+    }
+
+    // The directory and the read 1 name without the lane and read tokens.
+    fn get_library(&self, pair: &ReadPair) -> Option<(PathBuf, String, String)> {
+        let read_1 = pair.read_1.as_ref()?;
+        let fname = read_1.file_name()?.to_string_lossy();
+        let name = self.scheme.parse(&fname)?;
+        let prefix = sheet::remove_lane(&name.prefix).to_string();
+        Some((read_1.parent()?.to_path_buf(), prefix, name.suffix))
+    }
+
+    fn is_same_library(&self, fname: &str, prefix: &str) -> bool {
+        match fname.strip_prefix(prefix) {
+            Some(rest) => rest.starts_with(['_', '-', '.']),
+            None => false,
+        }
+    }
+
+    fn add_orphans(&mut self, pair: ReadPair) {
+        self.add_issues(FileIssue::Orphan, pair);
+    }
+
+    fn add_issues(&mut self, issue: FileIssue, pair: ReadPair) {
+        [pair.read_1, pair.read_2, pair.singleton]
+            .into_iter()
+            .flatten()
+            .for_each(|file| self.issues.push((issue.clone(), file)));
     }
 
     fn get_file_names(&mut self) {
@@ -77,18 +451,75 @@ impl<'a> Init<'a> {
 
     fn write_header<W: Write>(&self, line: &mut W) {
         if self.is_csv {
-            writeln!(line, "id,new_name").unwrap();
+            writeln!(line, "id,read1,read2,singleton").unwrap();
         } else {
             writeln!(line, "[seqs]").unwrap();
         }
     }
 
-    fn write_content<W: Write>(&self, line: &mut W, id: &str, full_path: &str) {
+    fn write_content<W: Write>(&self, line: &mut W, id: &str, lanes: &[ReadPair]) {
         if self.is_csv {
-            writeln!(line, "{}", id).unwrap();
+            // One column per read type. Lanes are separated by semicolons.
+            let join = |get: fn(&ReadPair) -> &Option<PathBuf>| {
+                lanes
+                    .iter()
+                    .filter_map(|reads| get(reads).as_ref())
+                    .map(|p| p.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(";")
+            };
+            let reads = [
+                join(|r| &r.read_1),
+                join(|r| &r.read_2),
+                join(|r| &r.singleton),
+            ];
+            writeln!(line, "{},{}", id, reads.join(",")).unwrap();
         } else {
-            writeln!(line, "{}:{}/", id, full_path).unwrap();
+            let paths: Vec<String> = lanes
+                .iter()
+                .flat_map(|reads| [&reads.read_1, &reads.read_2, &reads.singleton])
+                .flatten()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            writeln!(line, "{}:{}", id, paths.join(",")).unwrap();
+        }
+    }
+
+    fn print_duplicates(&self) {
+        if self.resolved.is_empty() {
+            return;
         }
+        let width = self
+            .resolved
+            .iter()
+            .map(|(id, _, new_id)| id.len().max(new_id.len()))
+            .max()
+            .unwrap_or(0)
+            + 2;
+        println!("{}", "Duplicate sample IDs:".yellow());
+        println!("{:width$}{:width$}File", "ID", "Written as", width = width);
+        self.resolved.iter().for_each(|(id, file, new_id)| {
+            println!(
+                "{:width$}{:width$}{}",
+                id,
+                new_id.green(),
+                file.display(),
+                width = width
+            );
+        });
+        println!();
+    }
+
+    fn print_issues(&self) {
+        if self.issues.is_empty() {
+            return;
+        }
+        println!("{}", "Skipped files:".yellow());
+        println!("{:12}File", "Status");
+        self.issues.iter().for_each(|(issue, file)| {
+            println!("{:12}{}", issue.to_string().red(), file.display());
+        });
+        println!();
     }
 
     fn print_saved_path(&self, file_count: usize, sample_count: usize) {
@@ -102,70 +533,192 @@ impl<'a> Init<'a> {
             self.fname
         );
     }
+}
 
-    fn re_matches_lazy(&self, fname: &str) -> bool {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"(_|-)((?i)(read|r)1)(.fq|.fastq)(?:.*)").unwrap();
-        }
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        RE.is_match(fname)
+    fn get_words_format() -> IdFormat {
+        IdFormat::new(3, '_', None, None)
     }
 
-    fn construct_id(&self, names: &str) -> String {
-        let words: Vec<&str> = names.split(self.sep).collect();
-        assert!(words.len() > self.len, "NO. OF WORDS EXCEED THE SLICES");
-        let mut sequence_name = String::new();
-
-        words[0..(self.len - 1)].iter().for_each(|w| {
-            let comp = format!("{}{}", w, self.sep);
-            sequence_name.push_str(&comp);
-        });
+    #[test]
+    fn find_files_test() {
+        let path = Path::new("test_files/init/");
+        let mut init = Init::new(path, get_words_format(), true, &NamingScheme::Auto);
+        let seqs = init.find_files();
 
-        sequence_name.push_str(words[self.len - 1]);
-        sequence_name
+        assert_eq!(2, seqs.len());
+        assert_eq!(7, init.file_count);
+        let abc456 = &seqs.get("sample_test_ABC456").unwrap()[0];
+        assert!(abc456.is_complete());
+        assert!(abc456.singleton.is_some());
+        let orphans = init
+            .issues
+            .iter()
+            .filter(|(i, _)| matches!(i, FileIssue::Orphan))
+            .count();
+        let unpaired = init
+            .issues
+            .iter()
+            .filter(|(i, _)| matches!(i, FileIssue::Unpaired))
+            .count();
+        assert_eq!(1, orphans);
+        assert_eq!(1, unpaired);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn find_illumina_files_test() {
+        let path = Path::new("test_files/illumina/");
+        let mut init = Init::new(path, get_words_format(), false, &NamingScheme::Auto);
+        let seqs = init.find_files();
+
+        assert_eq!(2, seqs.len());
+        assert_eq!(2, seqs.get("Bunomys_andrewsi").unwrap().len());
+        assert_eq!(1, seqs.get("Rattus_exulans").unwrap().len());
+        assert_eq!(1, init.issues.len());
+    }
 
     #[test]
-    fn regex_test() {
+    fn find_files_no_id_test() {
         let path = Path::new("test_files/init/");
-        let len = 3;
-        let sep = '_';
-        let re = Init::new(path, len, sep, true);
-        let zipped_read = "sample_buno_clean_read1.fastq.gz";
-        let unzipped_read = "sample_buno_clean_read1.fastq";
+        let format = IdFormat::new(3, '_', Some(r"_(?P<voucher>ABC123)_"), None);
+        let mut init = Init::new(path, format, true, &NamingScheme::Auto);
+        let seqs = init.find_files();
+        let no_id = init
+            .issues
+            .iter()
+            .filter(|(i, _)| matches!(i, FileIssue::NoId))
+            .count();
 
-        assert_eq!(true, re.re_matches_lazy(zipped_read));
-        assert_eq!(true, re.re_matches_lazy(unzipped_read));
+        assert_eq!(1, seqs.len());
+        assert!(seqs.contains_key("ABC123"));
+        assert_eq!(3, no_id);
     }
 
     #[test]
-    fn construct_id_test() {
-        let path = Path::new("test_files/init/");
-        let len = 3;
-        let sep = '_';
-        let re = Init::new(path, len, sep, true);
+    fn duplicate_ids_suffix_test() {
+        let path = Path::new("test_files/duplicates/");
+        let mut init = Init::new(path, get_words_format(), false, &NamingScheme::Auto);
+        let seqs = init.find_files();
 
-        let file_name = "sample_buno_ABCD123_read1.fastq.gz";
+        assert_eq!(5, seqs.len());
+        assert_eq!(1, seqs.get("sample_test_ABC123_2").unwrap().len());
+        // Lanes in the second run follow the suffixed id.
+        assert_eq!(2, seqs.get("Apodemus_2").unwrap().len());
+        assert_eq!(2, init.resolved.len());
+        assert!(init
+            .resolved
+            .iter()
+            .all(|(_, file, _)| file.to_string_lossy().contains("run_2")));
+    }
 
-        let id = re.construct_id(file_name);
+    #[test]
+    fn duplicate_ids_other_library_test() {
+        let path = Path::new("test_files/libraries/");
+        let mut init = Init::new(path, get_words_format(), false, &NamingScheme::Auto);
+        let seqs = init.find_files();
 
-        assert_eq!("sample_buno_ABCD123", id);
+        assert_eq!(2, seqs.len());
+        assert_eq!(1, seqs.get("Apodemus").unwrap().len());
+        assert_eq!(1, seqs.get("Apodemus_2").unwrap().len());
+        assert_eq!(1, init.resolved.len());
     }
 
     #[test]
-    #[should_panic]
-    fn construct_id_panic_test() {
-        let path = Path::new("test_files/init/");
-        let len = 4;
-        let sep = '_';
-        let re = Init::new(path, len, sep, true);
+    fn duplicate_ids_merge_test() {
+        let path = Path::new("test_files/duplicates/");
+        let mut init = Init::new(path, get_words_format(), false, &NamingScheme::Auto);
+        init.duplicates = DuplicateIds::Merge;
+        let seqs = init.find_files();
+
+        assert_eq!(3, seqs.len());
+        assert_eq!(2, seqs.get("sample_test_ABC123").unwrap().len());
+        assert_eq!(4, seqs.get("Apodemus").unwrap().len());
+        assert_eq!(3, init.resolved.len());
+    }
+
+    #[test]
+    fn suffixed_id_test() {
+        let path = Path::new("test_files/duplicates/");
+        let init = Init::new(path, get_words_format(), false, &NamingScheme::Auto);
+        let mut seqs = BTreeMap::new();
+        seqs.insert(String::from("ABC1"), Vec::new());
+        seqs.insert(String::from("ABC1-rerun2"), Vec::new());
+
+        assert_eq!(
+            "ABC1-rerun3",
+            init.get_suffixed_id(&seqs, "ABC1", "-rerun{n}")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "THE SUFFIX MUST CONTAIN {n}")]
+    fn duplicate_ids_invalid_suffix_test() {
+        DuplicateIds::from_arg("suffix", "_dup");
+    }
+
+    #[test]
+    fn words_id_test() {
+        let format = get_words_format();
         let file_name = "sample_buno_ABCD123_read1.fastq.gz";
 
-        re.construct_id(file_name);
+        assert_eq!(
+            Some(String::from("sample_buno_ABCD123")),
+            format.get_id(file_name)
+        );
+    }
+
+    #[test]
+    fn words_id_short_name_test() {
+        let format = IdFormat::new(4, '_', None, None);
+        let file_name = "sample_buno_ABCD123_read1.fastq.gz";
+
+        assert_eq!(None, format.get_id(file_name));
+    }
+
+    #[test]
+    fn regex_id_test() {
+        let regex = r"^(?P<genus>[A-Z][a-z]+)_(?P<species>[a-z]+)_.*?(?P<voucher>[A-Z]+\d+)";
+        let format = IdFormat::new(3, '_', Some(regex), None);
+        let file_name = "Bunomys_andrewsi_well12_MZB1234_L001_R1.fastq.gz";
+
+        assert_eq!(
+            Some(String::from("Bunomys_andrewsi_MZB1234")),
+            format.get_id(file_name)
+        );
+        assert_eq!(None, format.get_id("undetermined_R1.fastq.gz"));
+    }
+
+    #[test]
+    fn template_id_test() {
+        let format = IdFormat::new(3, '_', None, Some("{genus}_{species}-{voucher}"));
+        let file_name = "Bunomys_andrewsi-MZB1234_A01_R1.fastq.gz";
+
+        assert_eq!(
+            Some(String::from("Bunomys_andrewsi-MZB1234")),
+            format.get_id(file_name)
+        );
+        assert_eq!(None, format.get_id("Bunomys_andrewsi_MZB1234_R1.fastq.gz"));
+    }
+
+    #[test]
+    fn regex_template_id_test() {
+        let regex =
+            r"^(?P<genus>[A-Z][a-z]+)_(?P<species>[a-z]+)_(?P<well>[A-H]\d{2})_(?P<voucher>\w+?)_R";
+        let format = IdFormat::new(3, '_', Some(regex), Some("{voucher}_{genus}_{species}"));
+        let file_name = "Rattus_exulans_B07_KU1234_R2.fq.gz";
+
+        assert_eq!(
+            Some(String::from("KU1234_Rattus_exulans")),
+            format.get_id(file_name)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "UNKNOWN FIELD {voucher}")]
+    fn template_unknown_field_test() {
+        IdFormat::new(3, '_', Some(r"^(?P<genus>\w+)_"), Some("{genus}_{voucher}"));
     }
 }
@@ -2,6 +2,8 @@ mod assembly;
 mod cli;
 mod init;
 mod qc;
+mod report;
+mod sheet;
 mod utils;
 
 #[macro_use]
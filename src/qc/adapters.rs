@@ -0,0 +1,123 @@
+//! Adapter detection for the samples without adapters.
+//!
+//! Writes the detected adapters into a new sample sheet,
+//! so later runs trim with the same adapters.
+use std::path::Path;
+
+use colored::Colorize;
+use rayon::prelude::*;
+
+use crate::qc::native::detect::{self, AdapterDetection, DetectedAdapter};
+use crate::sheet::{self, NamingScheme, Sample};
+
+/// Default number of reads or read pairs checked per sample.
+pub const DETECTION_READS: usize = 100_000;
+
+pub struct AdapterDetector<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub is_rename: bool,
+    pub scheme: &'a NamingScheme,
+    /// Reads or read pairs checked per sample.
+    pub max_reads: usize,
+}
+
+impl<'a> AdapterDetector<'a> {
+    pub fn new(
+        input: &'a Path,
+        output: &'a Path,
+        is_rename: bool,
+        scheme: &'a NamingScheme,
+    ) -> Self {
+        Self {
+            input,
+            output,
+            is_rename,
+            scheme,
+            max_reads: DETECTION_READS,
+        }
+    }
+
+    /// Samples that already have adapters are written as they are.
+    pub fn detect(&self) {
+        let mut samples = sheet::parse_sample_sheet(self.input, self.is_rename, self.scheme);
+        let detections: Vec<Option<AdapterDetection>> = samples
+            .par_iter()
+            .map(|sample| match sample.auto_idx {
+                true => Some(
+                    detect::detect_adapters(sample, self.max_reads)
+                        .unwrap_or_else(|e| panic!("CAN'T READ THE READS OF {}: {}", sample.id, e)),
+                ),
+                false => None,
+            })
+            .collect();
+        println!();
+        let mut undetected = 0;
+        samples
+            .iter_mut()
+            .zip(detections)
+            .for_each(|(sample, detection)| {
+                if let Some(detection) = detection {
+                    print_detection(sample, &detection);
+                    if !set_adapters(sample, detection) {
+                        undetected += 1;
+                    }
+                }
+            });
+        sheet::write_sample_sheet(self.output, &samples).expect("CAN'T WRITE THE SAMPLE SHEET");
+        if undetected > 0 {
+            log::warn!(
+                "{} No adapters detected for {} samples. fastp will auto-detect them.",
+                "[WARNING]".yellow(),
+                undetected
+            );
+        }
+        log::info!("{:18}: {}", "Sample sheet", self.output.display());
+    }
+}
+
+fn print_detection(sample: &Sample, detection: &AdapterDetection) {
+    log::info!("{:18}: {}\x1b[0m", "ID".yellow(), sample.id.yellow());
+    log::info!("{:18}: {}", "Reads checked", detection.reads);
+    print_adapter("Read 1 adapter", detection.read_1.as_ref());
+    if sample.has_read_2() {
+        print_adapter("Read 2 adapter", detection.read_2.as_ref());
+    }
+    println!();
+}
+
+fn print_adapter(label: &str, adapter: Option<&DetectedAdapter>) {
+    let adapter = match adapter {
+        Some(adapter) => adapter,
+        None => {
+            log::info!("{:18}: NOT FOUND", label);
+            return;
+        }
+    };
+    log::info!("{:18}: {}", label, adapter.seq);
+    log::info!(
+        "{:18}: {} in {} reads",
+        "Evidence",
+        adapter.evidence.name(),
+        adapter.support
+    );
+    if let Some(name) = adapter.get_known_name() {
+        log::info!("{:18}: {}", "Known adapter", name);
+    }
+}
+
+// The detected adapters are in read orientation and are written
+// to the sheet as oligos. The read 2 adapter alone can't be written,
+// as a single adapter trims both reads. Returns false if nothing
+// was detected.
+fn set_adapters(sample: &mut Sample, detection: AdapterDetection) -> bool {
+    match detection.read_1 {
+        Some(read_1) => {
+            let read_2 = detection.read_2.map(|a| a.seq);
+            sample.set_read_adapters(&read_1.seq, read_2.as_deref());
+            sample.auto_idx = false;
+            true
+        }
+        None => false,
+    }
+}
@@ -1,23 +1,51 @@
-mod parser;
+mod adapters;
+mod native;
+pub mod options;
 mod runner;
-mod tag;
+mod scheduler;
+mod summary;
+mod threshold;
 
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::utils::opts::{self, Tool};
 use crate::utils::utils;
 use colored::Colorize;
 
-use crate::qc::parser::RawSeq;
 use crate::qc::runner::Fastp;
+use crate::qc::scheduler::{self as sched, Scheduler};
+use crate::sheet::{self, NamingScheme, Sample};
+
+pub use adapters::{AdapterDetector, DETECTION_READS};
+pub use native::NativeSettings;
+pub use options::FastpOptions;
+pub use runner::Engine;
+pub use scheduler::lock_log;
+pub use summary::{find_stats, read_failed_samples, write_summary, FastpStats, SUMMARY_FILE};
+pub use threshold::QcThresholds;
 
 pub struct Qc<'a> {
     pub input: &'a Path,
     pub is_rename: bool,
+    /// Raw fastp options. They win over the typed options.
     pub params: Option<&'a str>,
+    pub fastp_options: FastpOptions,
     pub output_dir: Option<&'a Path>,
+    pub scheme: &'a NamingScheme,
+    /// Total threads for all fastp jobs. Defaults to all available threads.
+    pub threads: Option<usize>,
+    /// Number of samples cleaned at once.
+    pub jobs: Option<usize>,
+    /// Keep the output dir and only clean samples without complete outputs.
+    pub resume: bool,
+    pub thresholds: QcThresholds,
+    pub engine: Engine,
+    /// Split interleaved reads into read 1 and read 2 files before trimming.
+    pub deinterleave: bool,
+    /// Index pairs of two samples closer than this are reported by the dry run.
+    pub min_index_distance: usize,
 }
 
 impl<'a> Qc<'a> {
@@ -26,22 +54,53 @@ impl<'a> Qc<'a> {
         is_rename: bool,
         params: Option<&'a str>,
         output_dir: Option<&'a Path>,
+        scheme: &'a NamingScheme,
     ) -> Self {
         Self {
             input,
             is_rename,
             params,
+            fastp_options: FastpOptions::default(),
             output_dir,
+            scheme,
+            threads: None,
+            jobs: None,
+            resume: false,
+            thresholds: QcThresholds::default(),
+            engine: Engine::Fastp,
+            deinterleave: false,
+            min_index_distance: sheet::MIN_INDEX_DISTANCE,
         }
     }
 
     pub fn dry_run(&self) {
-        let reads: Vec<RawSeq> = parser::parse_input(self.input, self.is_rename);
+        let reads: Vec<Sample> = sheet::parse_sample_sheet(self.input, self.is_rename, self.scheme);
+        let params = self.check_opts(&reads);
         println!();
         reads.iter().for_each(|r| {
             log::info!("{:18}: {}\x1b[0m", "ID".yellow(), r.id.yellow());
-            log::info!("{:18}: {}", "Read 1", r.read_1.to_string_lossy());
-            log::info!("{:18}: {}", "Read 2", r.read_2.to_string_lossy());
+            log::info!("{:18}: {}", "Layout", r.layout.name());
+            if r.is_multi_lane() {
+                r.lanes.iter().enumerate().for_each(|(i, (r1, r2))| {
+                    log::info!(
+                        "{:18}: {}",
+                        format!("Lane {} Read 1", i + 1),
+                        r1.to_string_lossy()
+                    );
+                    if r.has_read_2() {
+                        log::info!(
+                            "{:18}: {}",
+                            format!("Lane {} Read 2", i + 1),
+                            r2.to_string_lossy()
+                        );
+                    }
+                });
+            } else {
+                log::info!("{:18}: {}", "Read 1", r.read_1.to_string_lossy());
+                if r.has_read_2() {
+                    log::info!("{:18}: {}", "Read 2", r.read_2.to_string_lossy());
+                }
+            }
 
             match r.adapter_i7.as_ref() {
                 Some(i7) => {
@@ -57,41 +116,234 @@ impl<'a> Qc<'a> {
                 }
             };
 
-            log::info!("{:18}: {}", "Target Dir", r.dir.to_string_lossy());
+            log::info!("{:18}: {}", "Target Dir", r.target_dir.to_string_lossy());
+            r.metadata.iter().for_each(|(key, value)| {
+                log::info!("{:18}: {}", key, value);
+            });
             if self.is_rename {
                 log::info!("{:18}: {}", "Target fname", r.output_name.as_ref().unwrap());
             }
+            if matches!(self.engine, Engine::Fastp) {
+                log::info!(
+                    "{:18}: {}",
+                    "Command",
+                    self.get_fastp_command(r, reads.len(), &params)
+                );
+            }
 
             println!();
         });
+        self.print_index_collisions(&reads);
+    }
+
+    // Returns the options for all samples. The native engine
+    // does not use the fastp options and rejects them.
+    fn check_opts(&self, reads: &[Sample]) -> Vec<String> {
+        if !matches!(self.engine, Engine::Fastp) {
+            self.check_native_opts(reads)
+                .unwrap_or_else(|e| panic!("{}", e));
+            return Vec::new();
+        }
+        let params = opts::get_global_opts(Tool::Fastp, self.fastp_options.to_args(), self.params)
+            .unwrap_or_else(|e| panic!("INVALID --opts FOR fastp: {}", e));
+        opts::check_sample_opts(Tool::Fastp, &params, reads);
+        params
+    }
+
+    fn check_native_opts(&self, reads: &[Sample]) -> Result<(), String> {
+        if matches!(self.params, Some(params) if !params.trim().is_empty()) {
+            return Err(String::from(
+                "--opts ONLY APPLIES TO fastp. USE THE NATIVE ENGINE OPTIONS INSTEAD",
+            ));
+        }
+        let args = self.fastp_options.to_args();
+        if !args.is_empty() {
+            return Err(format!(
+                "THE NATIVE ENGINE DOES NOT USE THE fastp OPTIONS: {}",
+                args.join(" ")
+            ));
+        }
+        let samples: Vec<&str> = reads
+            .iter()
+            .filter(|r| matches!(r.fastp_opts.as_deref(), Some(opts) if !opts.trim().is_empty()))
+            .map(|r| r.id.as_str())
+            .collect();
+        if !samples.is_empty() {
+            return Err(format!(
+                "THE NATIVE ENGINE DOES NOT USE THE fastp_opts OF: {}",
+                samples.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    // The merged or deinterleaved reads of a sample are only
+    // written when the sample runs.
+    fn get_fastp_command(&self, read: &Sample, sample_count: usize, params: &[String]) -> String {
+        let dir = self.get_output_dir();
+        let sample_dir = dir.join(&read.target_dir);
+        let input = if read.is_multi_lane() {
+            sheet::get_merged_sample(read, &sample_dir.join("merged_reads"), self.scheme)
+        } else if read.is_interleaved() && self.deinterleave {
+            sheet::get_deinterleaved_sample(read, &sample_dir.join("deinterleaved_reads"))
+        } else {
+            read.clone()
+        };
+        let scheduler = Scheduler::new(self.threads, self.jobs, sample_count);
+        let mut runner = Fastp::new(&dir, &input, params);
+        runner.dual_idx = input.is_dual_idx();
+        runner.threads = Some(scheduler.threads_per_job);
+        runner.get_command()
+    }
+
+    fn print_index_collisions(&self, reads: &[Sample]) {
+        let collisions = sheet::find_index_collisions(reads, self.min_index_distance);
+        if collisions.is_empty() {
+            return;
+        }
+        collisions.iter().for_each(|c| {
+            log::warn!("{:18}: {} {}", "Index collision", c.sample, c.message());
+        });
+        log::warn!(
+            "{} {} sample pairs have indexes fewer than {} mismatches apart.\n",
+            "[WARNING]".yellow(),
+            collisions.len(),
+            self.min_index_distance
+        );
     }
 
-    pub fn run(&self) {
-        let reads: Vec<RawSeq> = parser::parse_input(&self.input, self.is_rename);
-        self.clean_reads(&reads);
+    /// Returns the samples that failed the qc thresholds
+    /// or have no fastp report.
+    pub fn run(&self) -> Vec<String> {
+        let reads: Vec<Sample> = sheet::parse_sample_sheet(self.input, self.is_rename, self.scheme);
+        self.clean_reads(&reads)
     }
 
-    pub fn clean_reads(&self, reads: &[RawSeq]) {
+    pub fn clean_reads(&self, reads: &[Sample]) -> Vec<String> {
+        let params = self.check_opts(reads);
         let dir = self.get_output_dir();
-        utils::check_dir_exist(&dir);
+        if !self.resume {
+            utils::check_dir_exist(&dir);
+        }
         fs::create_dir_all(&dir).expect("CAN'T CREATE CLEAN READ DIR");
+        let manifest = sheet::write_manifest(&dir, reads, |s| dir.join(&s.target_dir))
+            .expect("CAN'T WRITE MANIFEST");
+        log::info!("{:18}: {}\n", "Manifest", manifest.display());
+        // Includes the samples skipped by resume.
+        let targets: Vec<PathBuf> = reads.iter().map(|s| s.target_dir.clone()).collect();
+        let pending = self.get_pending_samples(&dir, reads);
+        let reads = pending.as_slice();
         let sample_count = reads.len();
-        let mut processed = 0;
-        reads.iter().for_each(|read| {
-            let mut runner = Fastp::new(&dir, read, self.params);
-
-            if read.adapter_i7.as_ref().is_some() {
-                // Check if i7 contains sequence
-                runner.dual_idx = true;
+        let scheduler = Scheduler::new(self.threads, self.jobs, sample_count);
+        log::info!(
+            "{:18}: {} jobs, {} threads each\n",
+            self.engine.name(),
+            scheduler.jobs,
+            scheduler.threads_per_job
+        );
+        let processed = AtomicUsize::new(0);
+        scheduler.run(reads, |read| {
+            if read.is_multi_lane() {
+                // Merged reads are only needed as fastp input.
+                let merged_dir = dir.join(&read.target_dir).join("merged_reads");
+                let merged = sheet::merge_lanes(read, &merged_dir, self.scheme);
+                self.run_fastp(&dir, &merged, &scheduler, &params);
+                fs::remove_dir_all(&merged_dir).expect("CAN'T REMOVE MERGED READ DIR");
+            } else if read.is_interleaved() && self.deinterleave {
+                let split_dir = dir.join(&read.target_dir).join("deinterleaved_reads");
+                let paired = sheet::deinterleave(read, &split_dir);
+                self.run_fastp(&dir, &paired, &scheduler, &params);
+                fs::remove_dir_all(&split_dir).expect("CAN'T REMOVE DEINTERLEAVED READ DIR");
+            } else {
+                self.run_fastp(&dir, read, &scheduler, &params);
             }
-
-            runner.run();
-            processed += 1;
-            log::info!("Processed {} of {} samples", processed, sample_count);
+            let count = processed.fetch_add(1, Ordering::SeqCst) + 1;
+            let _lock = sched::lock_log();
+            log::info!("Processed {} of {} samples", count, sample_count);
             log::info!("");
         });
 
+        let summary = dir.join(SUMMARY_FILE);
+        write_summary(&dir, &summary, &self.thresholds).expect("CAN'T WRITE QC SUMMARY");
+        log::info!("{:18}: {}", "QC summary", summary.display());
         log::info!("");
+        summary::find_failed_samples(&dir, &targets, &self.thresholds)
+    }
+
+    // Skip samples with complete outputs. The outputs of the other samples
+    // are removed, so they are cleaned from the start.
+    fn get_pending_samples(&self, dir: &Path, reads: &[Sample]) -> Vec<Sample> {
+        if !self.resume {
+            return reads.to_vec();
+        }
+        let pending: Vec<Sample> = reads
+            .iter()
+            .filter(|read| {
+                let sample_dir = dir.join(&read.target_dir);
+                if self.is_complete(dir, read) {
+                    log::info!("{:18}: {}", "Skipped (complete)", read.id);
+                    // Left behind if the run stopped before the temporary reads were removed.
+                    ["merged_reads", "deinterleaved_reads"]
+                        .iter()
+                        .map(|name| sample_dir.join(name))
+                        .filter(|tmp_dir| tmp_dir.is_dir())
+                        .for_each(|tmp_dir| {
+                            fs::remove_dir_all(tmp_dir).expect("CAN'T REMOVE TEMPORARY READ DIR")
+                        });
+                    return false;
+                }
+                if sample_dir.exists() {
+                    fs::remove_dir_all(&sample_dir).expect("CAN'T REMOVE INCOMPLETE OUTPUT");
+                }
+                true
+            })
+            .cloned()
+            .collect();
+        log::info!(
+            "{:18}: {} of {} samples\n",
+            "Resuming",
+            pending.len(),
+            reads.len()
+        );
+        pending
+    }
+
+    fn is_complete(&self, dir: &Path, read: &Sample) -> bool {
+        if read.is_multi_lane() {
+            let merged_dir = dir.join(&read.target_dir).join("merged_reads");
+            let merged = sheet::get_merged_sample(read, &merged_dir, self.scheme);
+            Fastp::new(dir, &merged, &[]).is_complete()
+        } else {
+            Fastp::new(dir, read, &[]).is_complete()
+        }
+    }
+
+    fn run_fastp(&self, dir: &Path, read: &Sample, scheduler: &Scheduler, params: &[String]) {
+        let mut runner = Fastp::new(dir, read, params);
+        runner.engine = self.engine.clone();
+        runner.dual_idx = read.is_dual_idx();
+        runner.threads = Some(scheduler.threads_per_job);
+        runner.is_parallel = scheduler.is_parallel();
+        runner.run();
+        self.check_thresholds(&runner.clean_dir, read);
+    }
+
+    fn check_thresholds(&self, sample_dir: &Path, read: &Sample) {
+        let json = summary::get_report_path(sample_dir);
+        let stats = FastpStats::from_json(&read.id, &json).unwrap_or_else(|e| panic!("{}", e));
+        let failures = self.thresholds.check(&stats);
+        let _lock = sched::lock_log();
+        if failures.is_empty() {
+            log::info!("{:18}: {} {}", "QC thresholds", read.id, "passed".green());
+        } else {
+            log::warn!(
+                "{:18}: {} {} ({})",
+                "QC thresholds",
+                read.id,
+                "failed".red(),
+                failures.join("; ")
+            );
+        }
     }
 
     fn get_output_dir(&self) -> PathBuf {
@@ -0,0 +1,298 @@
+//! Adapter detection from the first reads of a sample.
+//!
+//! Read pairs that overlap past the insert run into the adapters,
+//! so the bases after the insert give the adapter directly. Reads
+//! without a mate, or pairs that rarely run through, fall back to
+//! the k-mers that are overrepresented in the 3' half of the reads,
+//! extended base by base while the reads agree.
+use std::collections::HashMap;
+use std::io::Result;
+
+use crate::qc::native::fastq::{FastqReader, FastqRecord};
+use crate::qc::native::{read_pairs, trim};
+use crate::sheet::Sample;
+
+const KMER_SIZE: usize = 10;
+// Reads that must support an adapter.
+const MIN_SUPPORT: usize = 10;
+// Consensus extension stops below these.
+const MIN_COVERAGE: usize = 5;
+const MIN_AGREEMENT: f64 = 0.6;
+const MIN_ADAPTER_LEN: usize = 12;
+
+const KNOWN_ADAPTERS: [(&str, &str); 3] = [
+    ("Illumina TruSeq", "AGATCGGAAGAGC"),
+    ("Illumina Nextera", "CTGTCTCTTATACACATCT"),
+    ("Illumina small RNA", "TGGAATTCTCGG"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Evidence {
+    /// Bases past the insert of overlapping pairs.
+    Overlap,
+    /// Overrepresented 3' k-mers.
+    Kmer,
+}
+
+impl Evidence {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Overlap => "read-through overlap",
+            Self::Kmer => "3' k-mer",
+        }
+    }
+}
+
+pub struct DetectedAdapter {
+    pub seq: String,
+    pub evidence: Evidence,
+    /// Reads that support the adapter.
+    pub support: usize,
+}
+
+impl DetectedAdapter {
+    /// The name of a common adapter that starts the same way.
+    pub fn get_known_name(&self) -> Option<&str> {
+        KNOWN_ADAPTERS
+            .iter()
+            .find(|(_, prefix)| self.seq.starts_with(prefix) || prefix.starts_with(&self.seq))
+            .map(|(name, _)| *name)
+    }
+}
+
+pub struct AdapterDetection {
+    /// Reads or read pairs checked.
+    pub reads: usize,
+    pub read_1: Option<DetectedAdapter>,
+    pub read_2: Option<DetectedAdapter>,
+}
+
+/// Detect the adapters from the first `max_reads` reads or pairs.
+/// Multi-lane samples use the first lane.
+pub fn detect_adapters(sample: &Sample, max_reads: usize) -> Result<AdapterDetection> {
+    let pairs = read_first_pairs(sample, max_reads)?;
+    let r1: Vec<&[u8]> = pairs.iter().map(|(r1, _)| r1.seq.as_slice()).collect();
+    let r2: Vec<&[u8]> = pairs
+        .iter()
+        .filter_map(|(_, r2)| r2.as_ref().map(|r| r.seq.as_slice()))
+        .collect();
+    let (tails_r1, tails_r2) = find_read_through(&pairs);
+    let detect = |tails: &[&[u8]], reads: &[&[u8]]| {
+        find_overlap_adapter(tails).or_else(|| find_kmer_adapter(reads))
+    };
+    let read_2 = match r2.is_empty() {
+        true => None,
+        false => detect(&tails_r2, &r2),
+    };
+    Ok(AdapterDetection {
+        reads: pairs.len(),
+        read_1: detect(&tails_r1, &r1),
+        read_2,
+    })
+}
+
+fn read_first_pairs(
+    sample: &Sample,
+    max_reads: usize,
+) -> Result<Vec<(FastqRecord, Option<FastqRecord>)>> {
+    let mut reader_r1 = FastqReader::open(&sample.read_1)?;
+    let mut reader_r2 = match sample.has_read_2() {
+        true => Some(FastqReader::open(&sample.read_2)?),
+        false => None,
+    };
+    let mut pairs = Vec::new();
+    while pairs.len() < max_reads {
+        let chunk = read_pairs(&mut reader_r1, reader_r2.as_mut(), sample.is_interleaved())?;
+        if chunk.is_empty() {
+            break;
+        }
+        pairs.extend(chunk);
+    }
+    pairs.truncate(max_reads);
+    Ok(pairs)
+}
+
+// The bases after the insert of each read of the overlapping pairs.
+fn find_read_through(pairs: &[(FastqRecord, Option<FastqRecord>)]) -> (Vec<&[u8]>, Vec<&[u8]>) {
+    let mut tails_r1 = Vec::new();
+    let mut tails_r2 = Vec::new();
+    pairs.iter().for_each(|(r1, r2)| {
+        let r2 = match r2 {
+            Some(r2) => r2,
+            None => return,
+        };
+        if let Some(insert) = trim::find_insert_size(&r1.seq, &r2.seq) {
+            if insert < r1.seq.len() {
+                tails_r1.push(&r1.seq[insert..]);
+            }
+            if insert < r2.seq.len() {
+                tails_r2.push(&r2.seq[insert..]);
+            }
+        }
+    });
+    (tails_r1, tails_r2)
+}
+
+fn find_overlap_adapter(tails: &[&[u8]]) -> Option<DetectedAdapter> {
+    if tails.len() < MIN_SUPPORT {
+        return None;
+    }
+    let seq = extend_consensus(tails.iter().map(|t| t.iter()));
+    to_adapter(seq, Evidence::Overlap, tails.len())
+}
+
+// The k-mer found most often in the 3' half of the reads and at least
+// twice as often as in the 5' half, extended both ways.
+fn find_kmer_adapter(reads: &[&[u8]]) -> Option<DetectedAdapter> {
+    let mut counts: HashMap<&[u8], (usize, usize)> = HashMap::new();
+    reads.iter().for_each(|read| {
+        let half = read.len() / 2;
+        read.windows(KMER_SIZE)
+            .enumerate()
+            .filter(|(_, kmer)| is_complex(kmer))
+            .for_each(|(pos, kmer)| {
+                let count = counts.entry(kmer).or_insert((0, 0));
+                match pos < half {
+                    true => count.0 += 1,
+                    false => count.1 += 1,
+                }
+            });
+    });
+    let (kmer, support) = counts
+        .into_iter()
+        .filter(|(_, (five, three))| *three >= MIN_SUPPORT && *three >= 2 * *five)
+        .map(|(kmer, (_, three))| (kmer, three))
+        .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then(b.cmp(a)))?;
+    let hits: Vec<(&[u8], usize)> = reads
+        .iter()
+        .filter_map(|read| {
+            let half = read.len() / 2;
+            read.windows(KMER_SIZE)
+                .skip(half)
+                .position(|w| w == kmer)
+                .map(|pos| (*read, pos + half))
+        })
+        .collect();
+    let right = extend_consensus(
+        hits.iter()
+            .map(|(read, pos)| read[pos + KMER_SIZE..].iter()),
+    );
+    let left = extend_consensus(hits.iter().map(|(read, pos)| read[..*pos].iter().rev()));
+    let mut seq: Vec<u8> = left.into_iter().rev().collect();
+    seq.extend_from_slice(kmer);
+    seq.extend(right);
+    to_adapter(seq, Evidence::Kmer, support)
+}
+
+// Majority base at each position while enough reads cover it
+// and agree on it.
+fn extend_consensus<'a, I>(reads: impl Iterator<Item = I>) -> Vec<u8>
+where
+    I: Iterator<Item = &'a u8>,
+{
+    let mut reads: Vec<I> = reads.collect();
+    let mut seq = Vec::new();
+    loop {
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        reads
+            .iter_mut()
+            .filter_map(|read| read.next())
+            .filter(|base| !base.eq_ignore_ascii_case(&b'N'))
+            .for_each(|base| *counts.entry(base.to_ascii_uppercase()).or_insert(0) += 1);
+        let coverage: usize = counts.values().sum();
+        let best = counts
+            .into_iter()
+            .max_by_key(|(base, count)| (*count, *base));
+        match best {
+            Some((base, count))
+                if coverage >= MIN_COVERAGE && count as f64 / coverage as f64 >= MIN_AGREEMENT =>
+            {
+                seq.push(base)
+            }
+            _ => break,
+        }
+    }
+    seq
+}
+
+// Skip low complexity k-mers, such as poly-G tails.
+fn is_complex(kmer: &[u8]) -> bool {
+    let mut bases: Vec<u8> = kmer.iter().map(|b| b.to_ascii_uppercase()).collect();
+    if bases.contains(&b'N') {
+        return false;
+    }
+    bases.sort_unstable();
+    bases.dedup();
+    bases.len() >= 3
+}
+
+fn to_adapter(seq: Vec<u8>, evidence: Evidence, support: usize) -> Option<DetectedAdapter> {
+    if seq.len() < MIN_ADAPTER_LEN {
+        return None;
+    }
+    Some(DetectedAdapter {
+        seq: String::from_utf8_lossy(&seq).to_string(),
+        evidence,
+        support,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::sheet::Layout;
+
+    const ADAPTER_R1: &str = "AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC";
+    const ADAPTER_R2: &str = "AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT";
+
+    fn get_sample() -> Sample {
+        let mut sample = Sample::new("Rattus", Path::new("test_files/detect"));
+        sample.read_1 = PathBuf::from("test_files/detect/Rattus_R1.fastq.gz");
+        sample.read_2 = PathBuf::from("test_files/detect/Rattus_R2.fastq.gz");
+        sample
+    }
+
+    #[test]
+    fn detect_paired_adapters_test() {
+        let detection = detect_adapters(&get_sample(), 1000).unwrap();
+        let read_1 = detection.read_1.unwrap();
+        let read_2 = detection.read_2.unwrap();
+
+        assert_eq!(60, detection.reads);
+        assert_eq!(Evidence::Overlap, read_1.evidence);
+        assert_eq!(30, read_1.support);
+        assert!(read_1.seq.starts_with(ADAPTER_R1));
+        assert!(read_2.seq.starts_with(ADAPTER_R2));
+        assert_eq!(Some("Illumina TruSeq"), read_1.get_known_name());
+    }
+
+    #[test]
+    fn detect_single_end_adapter_test() {
+        let mut sample = get_sample();
+        sample.layout = Layout::Single;
+        sample.read_2 = PathBuf::new();
+        let detection = detect_adapters(&sample, 1000).unwrap();
+        let read_1 = detection.read_1.unwrap();
+
+        assert!(detection.read_2.is_none());
+        assert_eq!(Evidence::Kmer, read_1.evidence);
+        assert!(read_1.seq.starts_with(ADAPTER_R1));
+    }
+
+    #[test]
+    fn max_reads_test() {
+        let detection = detect_adapters(&get_sample(), 8).unwrap();
+
+        assert_eq!(8, detection.reads);
+        assert!(detection.read_1.is_none());
+    }
+
+    #[test]
+    fn low_complexity_test() {
+        assert!(!is_complex(b"GGGGGGGGGG"));
+        assert!(!is_complex(b"ACACACACAC"));
+        assert!(is_complex(b"AGATCGGAAG"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Fastq reader and writer for the native engine.
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::sheet::is_gzip;
+
+pub struct FastqRecord {
+    // Header line without the leading `@`.
+    pub id: String,
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+}
+
+impl FastqRecord {
+    pub fn truncate(&mut self, len: usize) {
+        self.seq.truncate(len);
+        self.qual.truncate(len);
+    }
+}
+
+pub struct FastqReader {
+    reader: Box<dyn BufRead>,
+    line: String,
+}
+
+impl FastqReader {
+    /// Gzip files are detected by their magic bytes.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let reader: Box<dyn BufRead> = if is_gzip(path)? {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        Ok(Self {
+            reader,
+            line: String::new(),
+        })
+    }
+
+    /// None at the end of the file.
+    pub fn next_record(&mut self) -> Result<Option<FastqRecord>> {
+        let id = match self.read_line()? {
+            Some(header) => match header.strip_prefix('@') {
+                Some(id) => id.to_string(),
+                None => return Err(invalid_record("HEADER DOES NOT START WITH @")),
+            },
+            None => return Ok(None),
+        };
+        let seq = self.read_required_line()?.into_bytes();
+        if !self.read_required_line()?.starts_with('+') {
+            return Err(invalid_record("SEPARATOR LINE DOES NOT START WITH +"));
+        }
+        let qual = self.read_required_line()?.into_bytes();
+        if seq.len() != qual.len() {
+            return Err(invalid_record("SEQUENCE AND QUALITY LENGTHS DIFFER"));
+        }
+        Ok(Some(FastqRecord { id, seq, qual }))
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.line.trim_end().to_string()))
+    }
+
+    fn read_required_line(&mut self) -> Result<String> {
+        self.read_line()?
+            .ok_or_else(|| invalid_record("TRUNCATED RECORD AT THE END OF THE FILE"))
+    }
+}
+
+fn invalid_record(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("INVALID FASTQ: {}", msg))
+}
+
+enum Output {
+    Gzip(GzEncoder<BufWriter<File>>),
+    Plain(BufWriter<File>),
+}
+
+pub struct FastqWriter {
+    output: Output,
+}
+
+impl FastqWriter {
+    /// Gzips the reads if the file name ends with `.gz`, as fastp does.
+    /// Fastp compresses at level 4 by default.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let output = if path.to_string_lossy().ends_with(".gz") {
+            Output::Gzip(GzEncoder::new(file, Compression::new(4)))
+        } else {
+            Output::Plain(file)
+        };
+        Ok(Self { output })
+    }
+
+    pub fn write(&mut self, record: &FastqRecord) -> Result<()> {
+        let writer: &mut dyn Write = match &mut self.output {
+            Output::Gzip(writer) => writer,
+            Output::Plain(writer) => writer,
+        };
+        writeln!(writer, "@{}", record.id)?;
+        writer.write_all(&record.seq)?;
+        writer.write_all(b"\n+\n")?;
+        writer.write_all(&record.qual)?;
+        writer.write_all(b"\n")
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self.output {
+            Output::Gzip(writer) => writer.finish()?.flush(),
+            Output::Plain(mut writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_fastq_test() {
+        let path = Path::new("test_files/lanes/Apodemus_S3_L001_R1_001.fastq.gz");
+        let mut reader = FastqReader::open(path).unwrap();
+        let record = reader.next_record().unwrap().unwrap();
+
+        assert!(record.id.starts_with("lane_1/1"));
+        assert_eq!(record.seq.len(), record.qual.len());
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_fastq_test() {
+        let path = std::env::temp_dir().join("yap_truncated_fastq_test.fq");
+        std::fs::write(&path, "@a/1\nACGT\n+\nIIII\n@b/1\nACGT\n").unwrap();
+        let mut reader = FastqReader::open(&path).unwrap();
+        let first = reader.next_record();
+        let second = reader.next_record();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(first.unwrap().is_some());
+        assert!(second.is_err());
+    }
+
+    #[test]
+    fn write_fastq_test() {
+        let path = std::env::temp_dir().join("yap_write_fastq_test.fq.gz");
+        let record = FastqRecord {
+            id: String::from("a/1"),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut writer = FastqWriter::create(&path).unwrap();
+        writer.write(&record).unwrap();
+        writer.finish().unwrap();
+        let mut reader = FastqReader::open(&path).unwrap();
+        let read = reader.next_record().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("a/1", read.id);
+        assert_eq!(b"ACGT".to_vec(), read.seq);
+    }
+
+    #[test]
+    fn write_plain_fastq_test() {
+        let path = std::env::temp_dir().join("yap_write_plain_fastq_test.fq");
+        let record = FastqRecord {
+            id: String::from("a/1"),
+            seq: b"ACGT".to_vec(),
+            qual: b"IIII".to_vec(),
+        };
+        let mut writer = FastqWriter::create(&path).unwrap();
+        writer.write(&record).unwrap();
+        writer.finish().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!("@a/1\nACGT\n+\nIIII\n", content);
+    }
+}
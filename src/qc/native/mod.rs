@@ -0,0 +1,369 @@
+//! Built-in read trimmer, an alternative to fastp.
+//!
+//! Writes the trimmed reads under the same names as fastp and a json
+//! report with the fastp keys used by the qc summary, so the summary,
+//! the thresholds, resume, and the project report work the same
+//! for both engines.
+pub mod detect;
+mod fastq;
+mod trim;
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde_json::json;
+
+use crate::qc::native::fastq::{FastqReader, FastqRecord, FastqWriter};
+use crate::qc::native::trim::{PairStatus, ReadStats, TrimSettings, TrimStats};
+use crate::sheet::Sample;
+
+// Read pairs trimmed at once by the thread pool.
+const CHUNK_SIZE: usize = 10_000;
+
+/// Trimming settings that apply to all samples.
+/// The adapters come from the sample sheet.
+#[derive(Clone)]
+pub struct NativeSettings {
+    pub window_size: usize,
+    pub window_quality: u8,
+    pub min_len: usize,
+    pub max_n: usize,
+}
+
+impl Default for NativeSettings {
+    fn default() -> Self {
+        Self {
+            window_size: 4,
+            window_quality: 20,
+            min_len: 15,
+            max_n: 5,
+        }
+    }
+}
+
+impl NativeSettings {
+    // Read 2 uses the read 1 adapter for single index samples, as in fastp.
+    fn get_trim_settings(&self, reads: &Sample) -> TrimSettings {
+        let (adapter_r1, adapter_r2) = if reads.auto_idx {
+            (None, None)
+        } else {
+            let (r1, r2) = reads.get_read_adapters();
+            (r1.map(String::into_bytes), r2.map(String::into_bytes))
+        };
+        TrimSettings {
+            adapter_r1,
+            adapter_r2,
+            window_size: self.window_size,
+            window_quality: self.window_quality,
+            min_len: self.min_len,
+            max_n: self.max_n,
+        }
+    }
+}
+
+pub struct NativeTrimmer<'a> {
+    reads: &'a Sample,
+    settings: TrimSettings,
+    threads: usize,
+}
+
+impl<'a> NativeTrimmer<'a> {
+    pub fn new(reads: &'a Sample, settings: &NativeSettings, threads: Option<usize>) -> Self {
+        Self {
+            reads,
+            settings: settings.get_trim_settings(reads),
+            threads: threads.unwrap_or(1),
+        }
+    }
+
+    /// Trims the reads and writes the json report. Returns the log.
+    /// `out_r2` is None for single-end reads.
+    pub fn run(&self, out_r1: &Path, out_r2: Option<&Path>, json: &Path) -> Result<String> {
+        let stats = self.trim_reads(out_r1, out_r2)?;
+        fs::write(json, get_json_report(&stats))?;
+        Ok(get_log(&stats))
+    }
+
+    fn trim_reads(&self, out_r1: &Path, out_r2: Option<&Path>) -> Result<TrimStats> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .expect("CAN'T CREATE THE TRIMMING THREAD POOL");
+        let mut reader_r1 = FastqReader::open(&self.reads.read_1)?;
+        let mut reader_r2 = match self.reads.has_read_2() {
+            true => Some(FastqReader::open(&self.reads.read_2)?),
+            false => None,
+        };
+        // Interleaved files hold both reads of the pair.
+        let is_interleaved = self.reads.is_interleaved();
+        let mut writer_r1 = FastqWriter::create(out_r1)?;
+        let mut writer_r2 = out_r2.map(FastqWriter::create).transpose()?;
+        let mut stats = TrimStats::default();
+        loop {
+            let mut pairs = read_pairs(&mut reader_r1, reader_r2.as_mut(), is_interleaved)?;
+            if pairs.is_empty() {
+                break;
+            }
+            pairs.iter().for_each(|(r1, r2)| {
+                stats.before.add(r1);
+                r2.iter().for_each(|r2| stats.before.add(r2));
+            });
+            let results: Vec<_> = pool.install(|| {
+                pairs
+                    .par_iter_mut()
+                    .map(|(r1, r2)| match r2 {
+                        Some(r2) => trim::trim_pair(r1, r2, &self.settings),
+                        None => trim::trim_single(r1, &self.settings),
+                    })
+                    .collect()
+            });
+            for ((r1, r2), result) in pairs.iter().zip(results.iter()) {
+                stats.add_result(result);
+                if result.status != PairStatus::Passed {
+                    continue;
+                }
+                stats.after.add(r1);
+                writer_r1.write(r1)?;
+                if let (Some(r2), Some(writer)) = (r2, writer_r2.as_mut()) {
+                    stats.after.add(r2);
+                    writer.write(r2)?;
+                }
+            }
+        }
+        writer_r1.finish()?;
+        if let Some(writer) = writer_r2 {
+            writer.finish()?;
+        }
+        Ok(stats)
+    }
+}
+
+// Read 2 is None for single-end reads. For interleaved reads,
+// read 2 is the record after read 1 in the same file.
+fn read_pairs(
+    reader_r1: &mut FastqReader,
+    mut reader_r2: Option<&mut FastqReader>,
+    is_interleaved: bool,
+) -> Result<Vec<(FastqRecord, Option<FastqRecord>)>> {
+    let mut pairs = Vec::with_capacity(CHUNK_SIZE);
+    while pairs.len() < CHUNK_SIZE {
+        let r1 = reader_r1.next_record()?;
+        let r2 = match reader_r2.as_deref_mut() {
+            Some(reader) => Some(reader.next_record()?),
+            None if is_interleaved && r1.is_some() => Some(reader_r1.next_record()?),
+            None => None,
+        };
+        match (r1, r2) {
+            (Some(r1), None) => pairs.push((r1, None)),
+            (Some(r1), Some(Some(r2))) => pairs.push((r1, Some(r2))),
+            (None, None) | (None, Some(None)) => break,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "READ 1 AND READ 2 HAVE DIFFERENT NUMBERS OF READS",
+                ))
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+// Uses the fastp report layout.
+fn get_json_report(stats: &TrimStats) -> String {
+    let mut report = json!({
+        "summary": {
+            "engine": "yap native",
+            "before_filtering": get_json_stats(&stats.before),
+            "after_filtering": get_json_stats(&stats.after),
+        },
+        "filtering_result": {
+            "passed_filter_reads": stats.passed_reads,
+            "low_quality_reads": stats.low_quality_reads,
+            "too_many_N_reads": stats.too_many_n_reads,
+            "too_short_reads": stats.too_short_reads,
+        },
+        "adapter_cutting": {
+            "adapter_trimmed_reads": stats.adapter_trimmed_reads,
+            "adapter_trimmed_bases": stats.adapter_trimmed_bases,
+        },
+    });
+    if let Some(peak) = stats.insert_size_peak() {
+        report["insert_size"] = json!({
+            "peak": peak,
+            "unknown": stats.unknown_insert_size,
+        });
+    }
+    serde_json::to_string_pretty(&report).expect("CAN'T SERIALIZE THE TRIMMING REPORT")
+}
+
+fn get_json_stats(stats: &ReadStats) -> serde_json::Value {
+    json!({
+        "total_reads": stats.reads,
+        "total_bases": stats.bases,
+        "q20_bases": stats.q20_bases,
+        "q30_bases": stats.q30_bases,
+        "q20_rate": stats.q20_rate(),
+        "q30_rate": stats.q30_rate(),
+    })
+}
+
+fn get_log(stats: &TrimStats) -> String {
+    let mut log = String::from("Native trimming engine\n\n");
+    let sections = [
+        ("Before filtering", &stats.before),
+        ("After filtering", &stats.after),
+    ];
+    sections.iter().for_each(|(name, reads)| {
+        log.push_str(&format!("{}:\n", name));
+        log.push_str(&format!("total reads: {}\n", reads.reads));
+        log.push_str(&format!("total bases: {}\n", reads.bases));
+        log.push_str(&format!("Q20 rate: {:.4}\n", reads.q20_rate()));
+        log.push_str(&format!("Q30 rate: {:.4}\n\n", reads.q30_rate()));
+    });
+    log.push_str("Filtering result:\n");
+    log.push_str(&format!("reads passed filter: {}\n", stats.passed_reads));
+    log.push_str(&format!(
+        "reads with low quality: {}\n",
+        stats.low_quality_reads
+    ));
+    log.push_str(&format!(
+        "reads with too many N: {}\n",
+        stats.too_many_n_reads
+    ));
+    log.push_str(&format!("reads too short: {}\n", stats.too_short_reads));
+    log.push_str(&format!(
+        "reads with adapter trimmed: {}\n",
+        stats.adapter_trimmed_reads
+    ));
+    log.push_str(&format!(
+        "bases trimmed due to adapters: {}\n",
+        stats.adapter_trimmed_bases
+    ));
+    if let Some(peak) = stats.insert_size_peak() {
+        log.push_str(&format!("Insert size peak: {}\n", peak));
+    }
+    log
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::qc::native::trim;
+    use crate::qc::FastpStats;
+    use crate::sheet::NamingScheme;
+
+    #[test]
+    fn native_trimmer_test() {
+        let dir = std::env::temp_dir().join("yap_native_trimmer_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut sample = Sample::new("Apodemus", Path::new("test_files/native"));
+        sample.read_1 = PathBuf::from("test_files/native/Apodemus_R1.fastq.gz");
+        sample.read_2 = PathBuf::from("test_files/native/Apodemus_R2.fastq");
+        sample.auto_idx = true;
+        let (out_r1, out_r2) = (dir.join("R1.fq.gz"), dir.join("R2.fq.gz"));
+        let json = dir.join("fastp.json");
+        let trimmer = NativeTrimmer::new(&sample, &NativeSettings::default(), Some(2));
+        let log = trimmer.run(&out_r1, Some(&out_r2), &json).unwrap();
+        let stats = FastpStats::from_json("Apodemus", &json).unwrap();
+        let mut reader = FastqReader::open(&out_r1).unwrap();
+        let first = reader.next_record().unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(log.contains("reads passed filter: 4"));
+        assert_eq!(8, stats.reads_before);
+        assert_eq!(4, stats.reads_after);
+        assert_eq!(Some(40), stats.insert_size_peak);
+        assert_eq!(Some(2), stats.adapter_trimmed_reads);
+        assert_eq!(40, first.seq.len());
+    }
+
+    #[test]
+    fn native_single_end_test() {
+        let dir = std::env::temp_dir().join("yap_native_single_end_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut sample = Sample::new("Apodemus", Path::new("test_files/native"));
+        sample.layout = crate::sheet::Layout::Single;
+        sample.read_1 = PathBuf::from("test_files/native/Apodemus_R1.fastq.gz");
+        sample.auto_idx = true;
+        let (out_r1, json) = (dir.join("R1.fq.gz"), dir.join("fastp.json"));
+        let trimmer = NativeTrimmer::new(&sample, &NativeSettings::default(), None);
+        trimmer.run(&out_r1, None, &json).unwrap();
+        let stats = FastpStats::from_json("Apodemus", &json).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(4, stats.reads_before);
+        assert_eq!(2, stats.reads_after);
+        assert_eq!(None, stats.insert_size_peak);
+    }
+
+    #[test]
+    fn native_plain_fastq_test() {
+        let dir = std::env::temp_dir().join("yap_native_plain_fastq_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut sample = Sample::new("Apodemus", Path::new("test_files/native"));
+        sample.layout = crate::sheet::Layout::Single;
+        sample.read_1 = PathBuf::from("test_files/native/Apodemus_R2.fastq");
+        sample.auto_idx = true;
+        let (out_r1, json) = (dir.join("Apodemus_R2.fastq"), dir.join("fastp.json"));
+        let trimmer = NativeTrimmer::new(&sample, &NativeSettings::default(), None);
+        trimmer.run(&out_r1, None, &json).unwrap();
+        let is_gzip = crate::sheet::is_gzip(&out_r1).unwrap();
+        let content = fs::read_to_string(&out_r1).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!is_gzip);
+        assert!(content.starts_with('@'));
+    }
+
+    #[test]
+    fn native_interleaved_test() {
+        let dir = std::env::temp_dir().join("yap_native_interleaved_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut sample = Sample::new("ABC1", Path::new("test_files/interleaved"));
+        sample.layout = crate::sheet::Layout::Interleaved;
+        sample.read_1 = PathBuf::from("test_files/interleaved/ABC1.fastq.gz");
+        sample.auto_idx = true;
+        let (out_r1, out_r2) = (dir.join("R1.fq.gz"), dir.join("R2.fq.gz"));
+        let json = dir.join("fastp.json");
+        let trimmer = NativeTrimmer::new(&sample, &NativeSettings::default(), None);
+        trimmer.run(&out_r1, Some(&out_r2), &json).unwrap();
+        let stats = FastpStats::from_json("ABC1", &json).unwrap();
+        let mut reader = FastqReader::open(&out_r2).unwrap();
+        let first = reader.next_record().unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(8, stats.reads_before);
+        assert_eq!(4, stats.reads_after);
+        assert_eq!(Some(40), stats.insert_size_peak);
+        assert!(first.id.ends_with("/2"));
+    }
+
+    #[test]
+    fn kit_adapter_trim_test() {
+        let input = Path::new("test_files/kit/samples.csv");
+        let samples = crate::sheet::parse_sample_sheet(input, false, &NamingScheme::Auto);
+        let settings = NativeSettings::default().get_trim_settings(&samples[0]);
+        // TruSeq D701 read-through: the insert, then the reverse
+        // complement of the i7 adapter with the index.
+        let insert = "GCTACGTCAATGGCATTCGAACTTGACCTCAGGTGTAATC";
+        let adapter = "AGATCGGAAGAGCACACGTCTGAACTCCAGTCACATTACTCGATCTCG";
+        let seq = format!("{}{}", insert, adapter);
+        let mut read = FastqRecord {
+            id: String::from("read"),
+            seq: seq.as_bytes().to_vec(),
+            qual: vec![b'I'; seq.len()],
+        };
+        let result = trim::trim_single(&mut read, &settings);
+
+        assert_eq!(insert.as_bytes(), read.seq.as_slice());
+        assert_eq!(1, result.adapter_trimmed_reads);
+        assert!(settings
+            .adapter_r2
+            .unwrap()
+            .starts_with(b"AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT"));
+    }
+}
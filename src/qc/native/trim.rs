@@ -0,0 +1,423 @@
+//! Read trimming and filtering.
+//!
+//! Follows the fastp defaults where it makes sense: adapters are first
+//! trimmed by the overlap of the read pair, then by the given sequence
+//! if the reads do not overlap. The pair is dropped if either read
+//! fails the filters. Single-end reads are trimmed by the given sequence.
+use std::collections::HashMap;
+
+use crate::qc::native::fastq::FastqRecord;
+
+const PHRED_OFFSET: u8 = 33;
+
+// Overlap detection, as in fastp.
+const MIN_OVERLAP: usize = 30;
+const MAX_OVERLAP_DIFF: usize = 5;
+const MAX_OVERLAP_DIFF_RATE: f64 = 0.2;
+
+// Adapters at the 3' end need at least this many matching bases.
+const MIN_ADAPTER_MATCH: usize = 4;
+
+// Low quality filter, as in fastp. Reads with more than this percent
+// of bases below the qualified quality are dropped.
+const QUALIFIED_QUALITY: u8 = 15;
+const UNQUALIFIED_PERCENT_LIMIT: usize = 40;
+
+#[derive(Clone)]
+pub struct TrimSettings {
+    pub adapter_r1: Option<Vec<u8>>,
+    pub adapter_r2: Option<Vec<u8>>,
+    pub window_size: usize,
+    pub window_quality: u8,
+    pub min_len: usize,
+    pub max_n: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PairStatus {
+    Passed,
+    LowQuality,
+    TooManyN,
+    TooShort,
+}
+
+pub struct PairResult {
+    /// One for single-end reads, two for a pair.
+    pub reads: u64,
+    pub status: PairStatus,
+    pub insert_size: Option<usize>,
+    pub adapter_trimmed_reads: u64,
+    pub adapter_trimmed_bases: u64,
+}
+
+/// Trims the pair in place.
+pub fn trim_pair(
+    r1: &mut FastqRecord,
+    r2: &mut FastqRecord,
+    settings: &TrimSettings,
+) -> PairResult {
+    let mut result = PairResult {
+        reads: 2,
+        status: PairStatus::Passed,
+        insert_size: find_insert_size(&r1.seq, &r2.seq),
+        adapter_trimmed_reads: 0,
+        adapter_trimmed_bases: 0,
+    };
+    match result.insert_size {
+        Some(insert_size) => {
+            result.add_adapter_cut(r1, insert_size);
+            result.add_adapter_cut(r2, insert_size);
+        }
+        None => {
+            if let Some(adapter) = &settings.adapter_r1 {
+                result.add_adapter_cut(r1, find_adapter(&r1.seq, adapter));
+            }
+            if let Some(adapter) = &settings.adapter_r2 {
+                result.add_adapter_cut(r2, find_adapter(&r2.seq, adapter));
+            }
+        }
+    }
+    [&mut *r1, &mut *r2].into_iter().for_each(|read| {
+        let cut = find_quality_cut(&read.qual, settings.window_size, settings.window_quality);
+        read.truncate(cut);
+    });
+
+    result.status = match filter_read(r1, settings) {
+        PairStatus::Passed => filter_read(r2, settings),
+        status => status,
+    };
+    result
+}
+
+/// Trims a single-end read in place.
+pub fn trim_single(read: &mut FastqRecord, settings: &TrimSettings) -> PairResult {
+    let mut result = PairResult {
+        reads: 1,
+        status: PairStatus::Passed,
+        insert_size: None,
+        adapter_trimmed_reads: 0,
+        adapter_trimmed_bases: 0,
+    };
+    if let Some(adapter) = &settings.adapter_r1 {
+        result.add_adapter_cut(read, find_adapter(&read.seq, adapter));
+    }
+    let cut = find_quality_cut(&read.qual, settings.window_size, settings.window_quality);
+    read.truncate(cut);
+
+    result.status = filter_read(read, settings);
+    result
+}
+
+// The first filter the trimmed read fails.
+fn filter_read(read: &FastqRecord, settings: &TrimSettings) -> PairStatus {
+    if is_low_quality(&read.qual) {
+        PairStatus::LowQuality
+    } else if count_n(&read.seq) > settings.max_n {
+        PairStatus::TooManyN
+    } else if read.seq.len() < settings.min_len {
+        PairStatus::TooShort
+    } else {
+        PairStatus::Passed
+    }
+}
+
+fn is_low_quality(qual: &[u8]) -> bool {
+    let unqualified = qual
+        .iter()
+        .filter(|q| q.saturating_sub(PHRED_OFFSET) < QUALIFIED_QUALITY)
+        .count();
+    unqualified * 100 > qual.len() * UNQUALIFIED_PERCENT_LIMIT
+}
+
+impl PairResult {
+    fn add_adapter_cut(&mut self, read: &mut FastqRecord, cut: usize) {
+        if cut < read.seq.len() {
+            self.adapter_trimmed_reads += 1;
+            self.adapter_trimmed_bases += (read.seq.len() - cut) as u64;
+            read.truncate(cut);
+        }
+    }
+}
+
+/// Insert size from the overlap of read 1 and the reverse complement
+/// of read 2. Inserts shorter than the reads mean the reads run into
+/// the adapters past the insert.
+pub fn find_insert_size(r1: &[u8], r2: &[u8]) -> Option<usize> {
+    let rc2 = reverse_complement(r2);
+    let (len1, len2) = (r1.len() as isize, rc2.len() as isize);
+    let min_overlap = MIN_OVERLAP as isize;
+    // Offset of the reverse complement of read 2 from the start of read 1.
+    // Positive offsets are checked first, as in fastp.
+    let positive = 0..=(len1 - min_overlap);
+    let negative = (min_overlap - len2..0).rev();
+    positive
+        .chain(negative)
+        .find(|&offset| is_overlap(r1, &rc2, offset))
+        .map(|offset| (offset + len2) as usize)
+}
+
+fn is_overlap(r1: &[u8], rc2: &[u8], offset: isize) -> bool {
+    let start = offset.max(0) as usize;
+    let end = (offset + rc2.len() as isize).min(r1.len() as isize);
+    if end - (start as isize) < MIN_OVERLAP as isize {
+        return false;
+    }
+    let end = end as usize;
+    let limit = MAX_OVERLAP_DIFF.min(((end - start) as f64 * MAX_OVERLAP_DIFF_RATE) as usize);
+    let mut diff = 0;
+    for i in start..end {
+        if r1[i] != rc2[(i as isize - offset) as usize] {
+            diff += 1;
+            if diff > limit {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Start of the adapter in the read, or the read length if not found.
+/// One mismatch is allowed for every eight bases compared.
+pub fn find_adapter(seq: &[u8], adapter: &[u8]) -> usize {
+    (0..seq.len())
+        .find(|&start| {
+            let len = adapter.len().min(seq.len() - start);
+            if len < MIN_ADAPTER_MATCH {
+                return false;
+            }
+            let limit = len / 8;
+            let mut diff = 0;
+            seq[start..start + len]
+                .iter()
+                .zip(adapter)
+                .all(|(base, adapter_base)| {
+                    if !base.eq_ignore_ascii_case(adapter_base) {
+                        diff += 1;
+                    }
+                    diff <= limit
+                })
+        })
+        .unwrap_or(seq.len())
+}
+
+/// Cut at the first window with a mean quality below the threshold,
+/// dropping the window and the rest of the read. The same as fastp
+/// `--cut_right`.
+pub fn find_quality_cut(qual: &[u8], window_size: usize, min_quality: u8) -> usize {
+    let window_size = window_size.clamp(1, qual.len().max(1));
+    if qual.len() < window_size {
+        return qual.len();
+    }
+    let min_sum = min_quality as usize * window_size;
+    let phred = |q: u8| q.saturating_sub(PHRED_OFFSET) as usize;
+    let mut sum: usize = qual[..window_size].iter().map(|q| phred(*q)).sum();
+    for start in 0..=(qual.len() - window_size) {
+        if start > 0 {
+            sum = sum + phred(qual[start + window_size - 1]) - phred(qual[start - 1]);
+        }
+        if sum < min_sum {
+            return start;
+        }
+    }
+    qual.len()
+}
+
+fn count_n(seq: &[u8]) -> usize {
+    seq.iter().filter(|b| b.eq_ignore_ascii_case(&b'N')).count()
+}
+
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|base| match base.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'T' => b'A',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'N',
+        })
+        .collect()
+}
+
+/// Counts of one side of the filters. Both reads of a pair are counted,
+/// as in fastp.
+#[derive(Default)]
+pub struct ReadStats {
+    pub reads: u64,
+    pub bases: u64,
+    pub q20_bases: u64,
+    pub q30_bases: u64,
+}
+
+impl ReadStats {
+    pub fn add(&mut self, read: &FastqRecord) {
+        self.reads += 1;
+        self.bases += read.seq.len() as u64;
+        read.qual.iter().for_each(|q| {
+            let phred = q.saturating_sub(PHRED_OFFSET);
+            if phred >= 20 {
+                self.q20_bases += 1;
+            }
+            if phred >= 30 {
+                self.q30_bases += 1;
+            }
+        });
+    }
+
+    pub fn q20_rate(&self) -> f64 {
+        get_rate(self.q20_bases, self.bases)
+    }
+
+    pub fn q30_rate(&self) -> f64 {
+        get_rate(self.q30_bases, self.bases)
+    }
+}
+
+#[derive(Default)]
+pub struct TrimStats {
+    pub before: ReadStats,
+    pub after: ReadStats,
+    pub passed_reads: u64,
+    pub low_quality_reads: u64,
+    pub too_many_n_reads: u64,
+    pub too_short_reads: u64,
+    pub adapter_trimmed_reads: u64,
+    pub adapter_trimmed_bases: u64,
+    pub insert_sizes: HashMap<usize, u64>,
+    // Pairs without overlap.
+    pub unknown_insert_size: u64,
+}
+
+impl TrimStats {
+    pub fn add_result(&mut self, result: &PairResult) {
+        let reads = match result.status {
+            PairStatus::Passed => &mut self.passed_reads,
+            PairStatus::LowQuality => &mut self.low_quality_reads,
+            PairStatus::TooManyN => &mut self.too_many_n_reads,
+            PairStatus::TooShort => &mut self.too_short_reads,
+        };
+        *reads += result.reads;
+        self.adapter_trimmed_reads += result.adapter_trimmed_reads;
+        self.adapter_trimmed_bases += result.adapter_trimmed_bases;
+        match result.insert_size {
+            Some(size) => *self.insert_sizes.entry(size).or_insert(0) += 1,
+            None if result.reads == 2 => self.unknown_insert_size += 1,
+            None => (),
+        }
+    }
+
+    /// The most common insert size. Ties go to the shorter insert.
+    pub fn insert_size_peak(&self) -> Option<usize> {
+        self.insert_sizes
+            .iter()
+            .max_by(|(size_a, count_a), (size_b, count_b)| {
+                count_a.cmp(count_b).then(size_b.cmp(size_a))
+            })
+            .map(|(size, _)| *size)
+    }
+}
+
+fn get_rate(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INSERT: &[u8] = b"GATTACACCTGAGGTCAAGTTCGAATGCCATTGACGTAGC";
+    const ADAPTER: &[u8] = b"AGATCGGAAGAGCACACGTCTGAACTCCAGTCA";
+
+    fn record(seq: &[u8], qual: u8) -> FastqRecord {
+        FastqRecord {
+            id: String::from("read"),
+            seq: seq.to_vec(),
+            qual: vec![qual; seq.len()],
+        }
+    }
+
+    fn settings() -> TrimSettings {
+        TrimSettings {
+            adapter_r1: None,
+            adapter_r2: None,
+            window_size: 4,
+            window_quality: 20,
+            min_len: 15,
+            max_n: 5,
+        }
+    }
+
+    #[test]
+    fn insert_size_test() {
+        let r1 = [INSERT, ADAPTER].concat();
+        let r2 = [&reverse_complement(INSERT), ADAPTER].concat();
+
+        assert_eq!(Some(INSERT.len()), find_insert_size(&r1, &r2));
+        assert_eq!(None, find_insert_size(&r1, ADAPTER));
+    }
+
+    #[test]
+    fn adapter_test() {
+        let seq = [INSERT, &ADAPTER[..10]].concat();
+
+        assert_eq!(INSERT.len(), find_adapter(&seq, ADAPTER));
+        assert_eq!(INSERT.len(), find_adapter(INSERT, ADAPTER));
+    }
+
+    #[test]
+    fn quality_cut_test() {
+        let qual = b"IIIIIIII####IIII";
+
+        assert_eq!(7, find_quality_cut(qual, 4, 20));
+        assert_eq!(8, find_quality_cut(qual, 1, 20));
+        assert_eq!(4, find_quality_cut(b"IIII", 8, 20));
+    }
+
+    #[test]
+    fn trim_pair_test() {
+        let mut r1 = record(&[INSERT, ADAPTER].concat(), b'I');
+        let mut r2 = record(&[&reverse_complement(INSERT), ADAPTER].concat(), b'I');
+        let result = trim_pair(&mut r1, &mut r2, &settings());
+
+        assert_eq!(PairStatus::Passed, result.status);
+        assert_eq!(INSERT.to_vec(), r1.seq);
+        assert_eq!(INSERT.len(), r2.qual.len());
+        assert_eq!(2, result.adapter_trimmed_reads);
+    }
+
+    #[test]
+    fn trim_single_test() {
+        let mut read = record(&[INSERT, ADAPTER].concat(), b'I');
+        let mut settings = settings();
+        settings.adapter_r1 = Some(ADAPTER.to_vec());
+        let result = trim_single(&mut read, &settings);
+
+        assert_eq!(PairStatus::Passed, result.status);
+        assert_eq!(INSERT.to_vec(), read.seq);
+        assert_eq!(1, result.adapter_trimmed_reads);
+    }
+
+    #[test]
+    fn filter_pair_test() {
+        let mut r1 = record(b"NNNNNNACGTACGTACGTACGT", b'I');
+        let mut r2 = record(b"ACGTACGTACGTACGTACGTAC", b'I');
+        let too_many_n = trim_pair(&mut r1, &mut r2, &settings());
+        let mut r1 = record(b"ACGTACGTACGTACGTACGTAC", b'#');
+        let too_short = trim_pair(&mut r1, &mut r2, &settings());
+        // Half of the bases are Q10, but no window is below Q20.
+        let mut r1 = FastqRecord {
+            qual: b"I+I+I+I+I+I+I+I+I+I+I+".to_vec(),
+            ..record(b"ACGTACGTACGTACGTACGTAC", b'I')
+        };
+        let low_quality = trim_pair(&mut r1, &mut r2, &settings());
+
+        assert_eq!(PairStatus::TooManyN, too_many_n.status);
+        assert_eq!(PairStatus::TooShort, too_short.status);
+        assert_eq!(PairStatus::LowQuality, low_quality.status);
+        assert_eq!(22, r1.seq.len());
+    }
+}
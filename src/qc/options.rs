@@ -0,0 +1,239 @@
+//! Typed fastp options.
+//!
+//! The common options can be set with yap flags. The same options in
+//! `--opts` or in the `fastp_opts` column are read into the same model,
+//! so their values are checked before any sample runs.
+use crate::utils::opts::{self, OptionValues};
+
+// Short and long names of the fastp options, used to match aliases
+// and to catch typos. Options without a short name have an empty one.
+pub const FASTP_FLAGS: [(&str, &str); 83] = [
+    ("-i", "--in1"),
+    ("-o", "--out1"),
+    ("-I", "--in2"),
+    ("-O", "--out2"),
+    ("", "--unpaired1"),
+    ("", "--unpaired2"),
+    ("", "--overlapped_out"),
+    ("", "--failed_out"),
+    ("-m", "--merge"),
+    ("", "--merged_out"),
+    ("", "--include_unmerged"),
+    ("-6", "--phred64"),
+    ("-z", "--compression"),
+    ("", "--stdin"),
+    ("", "--stdout"),
+    ("", "--interleaved_in"),
+    ("", "--reads_to_process"),
+    ("", "--dont_overwrite"),
+    ("", "--fix_mgi_id"),
+    ("-V", "--verbose"),
+    ("-A", "--disable_adapter_trimming"),
+    ("-a", "--adapter_sequence"),
+    ("", "--adapter_sequence_r2"),
+    ("", "--adapter_fasta"),
+    ("", "--detect_adapter_for_pe"),
+    ("-f", "--trim_front1"),
+    ("-t", "--trim_tail1"),
+    ("-b", "--max_len1"),
+    ("-F", "--trim_front2"),
+    ("-T", "--trim_tail2"),
+    ("-B", "--max_len2"),
+    ("-D", "--dedup"),
+    ("", "--dup_calc_accuracy"),
+    ("", "--dont_eval_duplication"),
+    ("-g", "--trim_poly_g"),
+    ("", "--poly_g_min_len"),
+    ("-G", "--disable_trim_poly_g"),
+    ("-x", "--trim_poly_x"),
+    ("", "--poly_x_min_len"),
+    ("-5", "--cut_front"),
+    ("-3", "--cut_tail"),
+    ("-r", "--cut_right"),
+    ("-W", "--cut_window_size"),
+    ("-M", "--cut_mean_quality"),
+    ("", "--cut_front_window_size"),
+    ("", "--cut_front_mean_quality"),
+    ("", "--cut_tail_window_size"),
+    ("", "--cut_tail_mean_quality"),
+    ("", "--cut_right_window_size"),
+    ("", "--cut_right_mean_quality"),
+    ("-Q", "--disable_quality_filtering"),
+    ("-q", "--qualified_quality_phred"),
+    ("-u", "--unqualified_percent_limit"),
+    ("-n", "--n_base_limit"),
+    ("-e", "--average_qual"),
+    ("-L", "--disable_length_filtering"),
+    ("-l", "--length_required"),
+    ("", "--length_limit"),
+    ("-y", "--low_complexity_filter"),
+    ("-Y", "--complexity_threshold"),
+    ("", "--filter_by_index1"),
+    ("", "--filter_by_index2"),
+    ("", "--filter_by_index_threshold"),
+    ("-c", "--correction"),
+    ("", "--overlap_len_require"),
+    ("", "--overlap_diff_limit"),
+    ("", "--overlap_diff_percent_limit"),
+    ("-U", "--umi"),
+    ("", "--umi_loc"),
+    ("", "--umi_len"),
+    ("", "--umi_prefix"),
+    ("", "--umi_skip"),
+    ("", "--umi_delim"),
+    ("-p", "--overrepresentation_analysis"),
+    ("-P", "--overrepresentation_sampling"),
+    ("-j", "--json"),
+    ("-h", "--html"),
+    ("-R", "--report_title"),
+    ("-w", "--thread"),
+    ("-s", "--split"),
+    ("-S", "--split_by_lines"),
+    ("-d", "--split_prefix_digits"),
+    ("-v", "--version"),
+];
+
+// The highest Phred score of Phred+33 quality characters.
+const MAX_PHRED: u8 = 93;
+// The range fastp accepts for the sliding-window mean quality.
+const MAX_CUT_QUALITY: u8 = 36;
+
+/// The common fastp options. Unset options use the fastp defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FastpOptions {
+    /// Phred quality of a qualified base.
+    pub qualified_quality: Option<u8>,
+    /// Percent of unqualified bases allowed in a read.
+    pub unqualified_limit: Option<u8>,
+    /// Reads shorter than this are discarded.
+    pub length_required: Option<usize>,
+    /// Sliding-window quality trimming from the 5' end to the 3' end.
+    pub cut_right: bool,
+    /// Mean quality of the sliding windows.
+    pub cut_mean_quality: Option<u8>,
+    pub dedup: bool,
+}
+
+impl FastpOptions {
+    /// Read the typed options from fastp arguments.
+    /// Other options are left to fastp.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        for OptionValues { name, values } in opts::get_option_values(args) {
+            match get_long_name(name) {
+                "--qualified_quality_phred" => {
+                    options.qualified_quality = Some(opts::parse_value(name, &values)?)
+                }
+                "--unqualified_percent_limit" => {
+                    options.unqualified_limit = Some(opts::parse_value(name, &values)?)
+                }
+                "--length_required" => {
+                    options.length_required = Some(opts::parse_value(name, &values)?)
+                }
+                "--cut_right" => options.cut_right = true,
+                "--cut_mean_quality" => {
+                    options.cut_mean_quality = Some(opts::parse_value(name, &values)?)
+                }
+                "--dedup" => options.dedup = true,
+                _ => (),
+            }
+        }
+        options.validate()?;
+        Ok(options)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(quality) = self.qualified_quality.filter(|q| *q > MAX_PHRED) {
+            return Err(format!(
+                "--qualified_quality_phred {} IS ABOVE {}",
+                quality, MAX_PHRED
+            ));
+        }
+        if let Some(limit) = self.unqualified_limit.filter(|l| *l > 100) {
+            return Err(format!(
+                "--unqualified_percent_limit {} IS ABOVE 100",
+                limit
+            ));
+        }
+        if let Some(quality) = self
+            .cut_mean_quality
+            .filter(|q| !(1..=MAX_CUT_QUALITY).contains(q))
+        {
+            return Err(format!(
+                "--cut_mean_quality {} IS NOT BETWEEN 1 AND {}",
+                quality, MAX_CUT_QUALITY
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(quality) = self.qualified_quality {
+            args.extend([
+                String::from("--qualified_quality_phred"),
+                quality.to_string(),
+            ]);
+        }
+        if let Some(limit) = self.unqualified_limit {
+            args.extend([
+                String::from("--unqualified_percent_limit"),
+                limit.to_string(),
+            ]);
+        }
+        if let Some(len) = self.length_required {
+            args.extend([String::from("--length_required"), len.to_string()]);
+        }
+        if self.cut_right {
+            args.push(String::from("--cut_right"));
+        }
+        if let Some(quality) = self.cut_mean_quality {
+            args.extend([String::from("--cut_mean_quality"), quality.to_string()]);
+        }
+        if self.dedup {
+            args.push(String::from("--dedup"));
+        }
+        args
+    }
+}
+
+/// The long name of a fastp option. Unknown options are kept as they are.
+pub fn get_long_name(name: &str) -> &str {
+    FASTP_FLAGS
+        .iter()
+        .find(|(short, long)| *short == name || *long == name)
+        .map(|(_, long)| *long)
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn fastp_options_test() {
+        let options = FastpOptions::from_args(&to_args("-l 30 --cut_right -M=20 -W 4")).unwrap();
+
+        assert_eq!(Some(30), options.length_required);
+        assert_eq!(Some(20), options.cut_mean_quality);
+        assert!(options.cut_right);
+        assert_eq!(
+            to_args("--length_required 30 --cut_right --cut_mean_quality 20"),
+            options.to_args()
+        );
+    }
+
+    #[test]
+    fn invalid_fastp_options_test() {
+        assert_eq!(
+            Err(String::from("INVALID VALUE abc FOR --length_required")),
+            FastpOptions::from_args(&to_args("--length_required abc"))
+        );
+        assert!(FastpOptions::from_args(&to_args("-M 40")).is_err());
+        assert!(FastpOptions::from_args(&to_args("-q")).is_err());
+    }
+}
@@ -9,23 +9,61 @@ use std::os::unix;
 
 use colored::Colorize;
 
-use crate::qc::parser::RawSeq;
+use crate::qc::native::{NativeSettings, NativeTrimmer};
+use crate::qc::scheduler;
+use crate::sheet::{self, Sample};
+use crate::utils::opts::{self, Tool};
 use crate::utils::utils::{self, PrettyHeader};
 
+/// Read trimming engine.
+#[derive(Clone)]
+pub enum Engine {
+    Fastp,
+    Native(NativeSettings),
+}
+
+impl Engine {
+    pub fn from_arg(arg: &str, settings: NativeSettings) -> Self {
+        match arg {
+            "native" => Engine::Native(settings),
+            _ => Engine::Fastp,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Engine::Fastp => "Fastp",
+            Engine::Native(_) => "Native trimmer",
+        }
+    }
+
+    // The native engine does not write an html report.
+    fn has_html(&self) -> bool {
+        matches!(self, Engine::Fastp)
+    }
+}
+
 pub struct Fastp<'a> {
     pub clean_dir: PathBuf,
+    pub engine: Engine,
     pub dual_idx: bool,
+    pub threads: Option<usize>,
+    pub is_parallel: bool,
     pub out_r1: PathBuf,
     pub out_r2: PathBuf,
-    pub reads: &'a RawSeq,
-    pub params: Option<&'a str>,
+    pub reads: &'a Sample,
+    /// Options for all samples, merged with the sample options.
+    pub params: &'a [String],
 }
 
 impl<'a> Fastp<'a> {
-    pub fn new(dir: &Path, input: &'a RawSeq, params: Option<&'a str>) -> Self {
+    pub fn new(dir: &Path, input: &'a Sample, params: &'a [String]) -> Self {
         Self {
-            clean_dir: dir.join(&input.dir),
+            clean_dir: dir.join(&input.target_dir),
+            engine: Engine::Fastp,
             dual_idx: false,
+            threads: None,
+            is_parallel: false,
             out_r1: PathBuf::new(),
             out_r2: PathBuf::new(),
             reads: input,
@@ -34,36 +72,107 @@ impl<'a> Fastp<'a> {
     }
 
     pub fn run(&mut self) {
-        let mut header = PrettyHeader::new(&self.reads.id);
-        log::info!("{}", header.get());
         self.get_output_filename();
+        if self.is_parallel {
+            self.run_in_parallel();
+            return;
+        }
+        self.display_header();
         self.display_settings();
         let spin = utils::set_spinner();
-        spin.set_message("Fastp is processing\t");
-        let out = self.call_fastp();
-        let mut reports = FastpReports::new(&self.clean_dir);
-        reports.check_fastp_status(&out);
-        reports.write_stdout(&out);
+        spin.set_message(format!("{} is processing\t", self.engine.name()));
+        let reports = self.clean_reads();
+        spin.finish_with_message(format!(
+            "{} {} has finished",
+            "✔".green(),
+            self.engine.name().to_uppercase()
+        ));
+        reports.display_report_paths(self.engine.has_html());
+    }
+
+    // Spinners of concurrent jobs would overwrite each other.
+    // Each job logs one line when it starts, and its settings
+    // and reports in one block when it finishes.
+    fn run_in_parallel(&self) {
+        {
+            let _lock = scheduler::lock_log();
+            log::info!(
+                "{:18}: {} ({} threads)",
+                format!("Started {}", self.engine.name().to_lowercase()),
+                self.reads.id,
+                self.threads.unwrap_or_default()
+            );
+        }
+        let reports = self.clean_reads();
+        let _lock = scheduler::lock_log();
+        log::info!("");
+        self.display_header();
+        self.display_settings();
+        log::info!(
+            "{} {} has finished",
+            "✔".green(),
+            self.engine.name().to_uppercase()
+        );
+        reports.display_report_paths(self.engine.has_html());
+    }
+
+    /// Fastp writes the json report after the trimmed reads,
+    /// and yap writes the log last. A sample with all of them is complete.
+    pub fn is_complete(&mut self) -> bool {
+        self.get_output_filename();
+        let reports = FastpReports::new(&self.clean_dir);
+        is_non_empty(&self.out_r1)
+            && (self.reads.is_single_end() || is_non_empty(&self.out_r2))
+            && reports.is_complete()
+    }
+
+    fn clean_reads(&self) -> FastpReports {
+        let output_dir = self.out_r1.parent().expect("INVALID OUTPUT DIR");
+        fs::create_dir_all(output_dir).expect("CAN'T CREATE TRIMMED READ DIR");
+        let reports = FastpReports::new(&self.clean_dir);
+        reports
+            .prepare_dir()
+            .expect("CAN'T CREATE FASTP REPORT DIR");
         self.try_creating_symlink();
-        reports.reorganize_reports().unwrap();
-        spin.finish_with_message(format!("{} FASTP has finished", "✔".green()));
-        reports.display_report_paths();
+        match &self.engine {
+            Engine::Fastp => {
+                let out = self.call_fastp(&reports);
+                // Keep the log of a failed run.
+                reports.write_stdout(&out);
+                reports.check_fastp_status(&out);
+            }
+            Engine::Native(settings) => self.call_native(settings, &reports),
+        }
+        reports
+    }
+
+    fn display_header(&self) {
+        let mut header = PrettyHeader::new(&self.reads.id);
+        log::info!("{}", header.get());
     }
 
     fn get_output_filename(&mut self) {
         let output_dir = self.clean_dir.join("trimmed_reads");
-        fs::create_dir_all(&output_dir).unwrap();
-        let out1 = self.reads.read_1.file_name().unwrap();
-        let out2 = self.reads.read_2.file_name().unwrap();
+        if self.reads.is_interleaved() {
+            // The pairs are written to separate read 1 and read 2 files.
+            let read_1 = sheet::get_mate_name(&self.reads.read_1, "R1");
+            let read_2 = sheet::get_mate_name(&self.reads.read_1, "R2");
+            self.out_r1 = output_dir.join(self.get_output_name(&read_1));
+            self.out_r2 = output_dir.join(self.get_output_name(&read_2));
+            return;
+        }
+        self.out_r1 = output_dir.join(self.get_output_name(&self.reads.read_1));
+        if self.reads.has_read_2() {
+            self.out_r2 = output_dir.join(self.get_output_name(&self.reads.read_2));
+        }
+    }
 
+    fn get_output_name(&self, read: &Path) -> String {
+        let fname = read.file_name().unwrap().to_string_lossy();
         if self.is_rename() {
-            let out1 = self.rename_output(out1.to_str().unwrap());
-            let out2 = self.rename_output(out2.to_str().unwrap());
-            self.out_r1 = output_dir.join(out1);
-            self.out_r2 = output_dir.join(out2);
+            self.rename_output(&fname)
         } else {
-            self.out_r1 = output_dir.join(out1);
-            self.out_r2 = output_dir.join(out2);
+            fname.to_string()
         }
     }
 
@@ -77,6 +186,8 @@ impl<'a> Fastp<'a> {
     }
 
     fn display_settings(&self) {
+        log::info!("{:18}: {}", "Engine", self.engine.name());
+        log::info!("{:18}: {}", "Layout", self.reads.layout.name());
         log::info!("{:18}: {}", "Target dir", &self.clean_dir.to_string_lossy());
         log::info!(
             "{:18}: {}",
@@ -88,11 +199,13 @@ impl<'a> Fastp<'a> {
             "Input R1",
             &self.reads.read_1.file_name().unwrap().to_string_lossy()
         );
-        log::info!(
-            "{:18}: {}",
-            "Input R2",
-            &self.reads.read_2.file_name().unwrap().to_string_lossy()
-        );
+        if self.reads.has_read_2() {
+            log::info!(
+                "{:18}: {}",
+                "Input R2",
+                &self.reads.read_2.file_name().unwrap().to_string_lossy()
+            );
+        }
         log::info!(
             "{:18}: {}",
             "Output Dir",
@@ -103,14 +216,16 @@ impl<'a> Fastp<'a> {
             "Output R1",
             &self.out_r1.file_name().unwrap().to_string_lossy()
         );
-        log::info!(
-            "{:18}: {}",
-            "Output R2",
-            &self.out_r2.file_name().unwrap().to_string_lossy()
-        );
+        if !self.reads.is_single_end() {
+            log::info!(
+                "{:18}: {}",
+                "Output R2",
+                &self.out_r2.file_name().unwrap().to_string_lossy()
+            );
+        }
         if self.reads.auto_idx {
             log::info!("{:18}: AUTO-DETECT", "Adapters");
-        } else if !self.dual_idx {
+        } else if !self.is_dual_idx() {
             log::info!(
                 "{:18}: {}",
                 "Adapters",
@@ -128,59 +243,104 @@ impl<'a> Fastp<'a> {
                 self.reads.adapter_i7.as_ref().unwrap()
             );
         }
+        let params = self.get_opt_params();
+        if matches!(self.engine, Engine::Fastp) && !params.is_empty() {
+            log::info!("{:18}: {}", "Opt params", params.join(" "));
+        }
 
         log::info!("");
     }
 
-    fn call_fastp(&self) -> Output {
+    /// The fastp command line of the sample, for the dry run.
+    pub fn get_command(&mut self) -> String {
+        self.get_output_filename();
+        let reports = FastpReports::new(&self.clean_dir);
+        opts::format_command(&self.build_fastp_command(&reports))
+    }
+
+    fn call_fastp(&self, reports: &FastpReports) -> Output {
+        self.build_fastp_command(reports).output().unwrap()
+    }
+
+    fn build_fastp_command(&self, reports: &FastpReports) -> Command {
         let mut out = Command::new("fastp");
 
         out.arg("-i")
             .arg(self.reads.read_1.clone())
-            .arg("-I")
-            .arg(self.reads.read_2.clone())
             .arg("-o")
-            .arg(self.out_r1.clone())
-            .arg("-O")
-            .arg(self.out_r2.clone());
+            .arg(self.out_r1.clone());
+        if self.reads.has_read_2() {
+            out.arg("-I").arg(self.reads.read_2.clone());
+        }
+        if self.reads.is_interleaved() {
+            out.arg("--interleaved_in");
+        }
+        if !self.reads.is_single_end() {
+            out.arg("-O").arg(self.out_r2.clone());
+        }
+        out.arg("--html")
+            .arg(&reports.html)
+            .arg("--json")
+            .arg(&reports.json);
+        if let Some(threads) = self.threads {
+            out.arg("--thread").arg(threads.to_string());
+        }
         if !self.reads.auto_idx {
             self.set_fastp_idx(&mut out)
         }
         self.set_opt_params(&mut out);
+        out
+    }
+
+    fn call_native(&self, settings: &NativeSettings, reports: &FastpReports) {
+        let trimmer = NativeTrimmer::new(self.reads, settings, self.threads);
+        let out_r2 = (!self.reads.is_single_end()).then_some(self.out_r2.as_path());
+        match trimmer.run(&self.out_r1, out_r2, &reports.json) {
+            Ok(log) => fs::write(&reports.log, log).expect("CAN'T WRITE TRIMMING LOG"),
+            Err(e) => {
+                // Keep the log of a failed run.
+                fs::write(&reports.log, e.to_string()).expect("CAN'T WRITE TRIMMING LOG");
+                // A partial report would pass as complete on resume.
+                fs::remove_file(&reports.json).ok();
+                panic!("NATIVE TRIMMING FAILED FOR {}: {}", self.reads.id, e);
+            }
+        }
+    }
 
-        out.output().unwrap()
+    // Single-end reads only have the read 1 adapter.
+    fn is_dual_idx(&self) -> bool {
+        self.dual_idx && !self.reads.is_single_end()
     }
 
     fn set_fastp_idx(&self, out: &mut Command) {
-        if self.dual_idx {
+        if self.is_dual_idx() {
             self.set_fastp_dual_idx(out);
         } else {
             self.set_fastp_single_idx(out);
         }
     }
 
+    // fastp takes the adapters as they show up in the reads.
     fn set_fastp_single_idx(&self, out: &mut Command) {
-        out.arg("--adapter_sequence")
-            .arg(String::from(self.reads.adapter_i5.as_ref().unwrap()));
+        let (adapter_r1, _) = self.reads.get_read_adapters();
+        out.arg("--adapter_sequence").arg(adapter_r1.unwrap());
     }
 
     fn set_fastp_dual_idx(&self, out: &mut Command) {
+        let (adapter_r1, adapter_r2) = self.reads.get_read_adapters();
         out.arg("--adapter_sequence")
-            .arg(String::from(self.reads.adapter_i5.as_ref().unwrap()))
+            .arg(adapter_r1.unwrap())
             .arg("--adapter_sequence_r2")
-            .arg(String::from(self.reads.adapter_i7.as_ref().unwrap()));
+            .arg(adapter_r2.unwrap());
     }
 
     fn set_opt_params(&self, out: &mut Command) {
-        match self.params {
-            Some(param) => {
-                let params: Vec<&str> = param.split_whitespace().collect();
-                params.iter().for_each(|param| {
-                    out.arg(param);
-                });
-            }
-            None => (),
-        }
+        out.args(self.get_opt_params());
+    }
+
+    // The sample options in the sheet are merged with `--opts`.
+    fn get_opt_params(&self) -> Vec<String> {
+        opts::get_sample_opts(Tool::Fastp, self.params, self.reads)
     }
 
     fn try_creating_symlink(&self) {
@@ -198,16 +358,17 @@ impl<'a> Fastp<'a> {
         }
     }
 
+    // Link the raw reads as found. For merged lanes,
+    // these are the lane files instead of the merged reads.
     #[cfg(target_family = "unix")]
     fn create_symlink(&self) -> Result<()> {
         let symdir = self.clean_dir.join("raw_read_symlinks");
         fs::create_dir_all(&symdir)?;
-        let abs_r1 = self.reads.read_1.canonicalize().unwrap();
-        let abs_r2 = self.reads.read_2.canonicalize().unwrap();
-        let path_r1 = symdir.join(self.reads.read_1.file_name().unwrap());
-        let path_r2 = symdir.join(self.reads.read_2.file_name().unwrap());
-        unix::fs::symlink(abs_r1, path_r1)?;
-        unix::fs::symlink(abs_r2, path_r2)?;
+        for read in self.reads.get_raw_reads() {
+            let abs_path = read.canonicalize()?;
+            let path = symdir.join(read.file_name().unwrap());
+            unix::fs::symlink(abs_path, path)?;
+        }
         Ok(())
     }
 }
@@ -217,22 +378,36 @@ struct FastpReports {
     html: PathBuf,
     json: PathBuf,
     log: PathBuf,
-    html_out: PathBuf,
-    json_out: PathBuf,
-    log_out: PathBuf,
 }
 
 impl FastpReports {
+    // Fastp writes the reports straight to the sample directory,
+    // so it does not depend on the working directory and
+    // concurrent runs do not overwrite each other.
     fn new(dir: &Path) -> Self {
+        let dir = dir.join("fastp_reports");
         Self {
-            dir: dir.join("fastp_reports"),
-            html: PathBuf::from("fastp.html"),
-            json: PathBuf::from("fastp.json"),
-            log: PathBuf::from("fastp.log"),
-            html_out: PathBuf::new(),
-            json_out: PathBuf::new(),
-            log_out: PathBuf::new(),
+            html: dir.join("fastp.html"),
+            json: dir.join("fastp.json"),
+            log: dir.join("fastp.log"),
+            dir,
+        }
+    }
+
+    // Reports of an earlier run would hide a failed run.
+    fn prepare_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        for report in [&self.html, &self.json, &self.log] {
+            if report.is_file() {
+                fs::remove_file(report)?;
+            }
         }
+        Ok(())
+    }
+
+    fn is_complete(&self) -> bool {
+        let json = fs::read_to_string(&self.json).unwrap_or_default();
+        json.trim_end().ends_with('}') && self.log.is_file()
     }
 
     // Less likely this will be called
@@ -251,7 +426,7 @@ impl FastpReports {
     fn fastp_is_failed(&self, out: &Output) {
         io::stdout().write_all(&out.stdout).unwrap();
         io::stdout().write_all(&out.stderr).unwrap();
-        panic!("FASTP FAILED TO RUN");
+        panic!("FASTP FAILED TO RUN. CHECK {}", self.log.display());
     }
 
     // We remove the clutter of fastp stdout in the console.
@@ -265,25 +440,74 @@ impl FastpReports {
         buff.write_all(&out.stderr).unwrap();
     }
 
-    fn reorganize_reports(&mut self) -> Result<()> {
-        fs::create_dir_all(&self.dir)?;
-        self.html_out = self.dir.join(&self.html);
-        self.json_out = self.dir.join(&self.json);
-        self.log_out = self.dir.join(&self.log);
-        // Move json, html, and log reports
-        fs::rename(&self.html, &self.html_out)?;
-        fs::rename(&self.json, &self.json_out)?;
-        fs::rename(&self.log, &self.log_out)?;
+    fn display_report_paths(&self, has_html: bool) {
+        log::info!("");
+        log::info!("QC Reports:");
+        let mut reports = vec![&self.json, &self.log];
+        if has_html {
+            reports.insert(0, &self.html);
+        }
+        reports
+            .iter()
+            .enumerate()
+            .for_each(|(i, report)| log::info!("{}. {}", i + 1, report.to_string_lossy()));
+        log::info!("");
+    }
+}
 
-        Ok(())
+fn is_non_empty(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn complete_output_test() {
+        let dir = std::env::temp_dir().join("yap_complete_output_test");
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample.read_1 = PathBuf::from("raw/ABC1_R1.fq.gz");
+        sample.read_2 = PathBuf::from("raw/ABC1_R2.fq.gz");
+        let trimmed = dir.join("ABC1/trimmed_reads");
+        let reports = FastpReports::new(&dir.join("ABC1"));
+        fs::create_dir_all(&trimmed).unwrap();
+        fs::create_dir_all(&reports.dir).unwrap();
+        fs::write(trimmed.join("ABC1_R1.fq.gz"), "reads").unwrap();
+        fs::write(trimmed.join("ABC1_R2.fq.gz"), "reads").unwrap();
+        fs::write(&reports.log, "fastp").unwrap();
+        fs::write(&reports.json, "{\"summary\": {").unwrap();
+        let is_truncated = Fastp::new(&dir, &sample, &[]).is_complete();
+        fs::write(&reports.json, "{\"summary\": {}}\n").unwrap();
+        let is_finished = Fastp::new(&dir, &sample, &[]).is_complete();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!is_truncated);
+        assert!(is_finished);
     }
 
-    fn display_report_paths(&self) {
-        log::info!("");
-        log::info!("Fastp Reports:");
-        log::info!("1. {}", self.html_out.to_string_lossy());
-        log::info!("2. {}", self.json_out.to_string_lossy());
-        log::info!("3. {}", self.log_out.to_string_lossy());
-        log::info!("");
+    #[test]
+    fn single_end_output_test() {
+        let mut sample = Sample::new("SRR1234", Path::new("raw"));
+        sample.layout = crate::sheet::Layout::Single;
+        sample.read_1 = PathBuf::from("raw/SRR1234.fastq.gz");
+        sample.output_name = Some(String::from("Mus_SRR1234"));
+        let mut runner = Fastp::new(Path::new("clean_reads"), &sample, &[]);
+        runner.get_output_filename();
+
+        assert_eq!(
+            PathBuf::from("clean_reads/SRR1234/trimmed_reads/Mus_SRR1234.fastq.gz"),
+            runner.out_r1
+        );
+        assert!(runner.out_r2.as_os_str().is_empty());
+    }
+
+    #[test]
+    fn report_paths_test() {
+        let reports = FastpReports::new(Path::new("clean_reads/ABC1"));
+        let dir = PathBuf::from("clean_reads/ABC1/fastp_reports");
+        assert_eq!(dir.join("fastp.html"), reports.html);
+        assert_eq!(dir.join("fastp.json"), reports.json);
+        assert_eq!(dir.join("fastp.log"), reports.log);
     }
 }
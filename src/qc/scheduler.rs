@@ -0,0 +1,95 @@
+//! Run fastp on several samples at once.
+//!
+//! The total thread budget is split evenly between the jobs,
+//! and each job passes its share to fastp `--thread`.
+use std::sync::{Mutex, MutexGuard};
+
+use rayon::prelude::*;
+
+use crate::sheet::Sample;
+
+// Held while a job writes its log, so the lines of one sample stay together.
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+// Fastp does not use more than 16 worker threads.
+const MAX_FASTP_THREADS: usize = 16;
+
+// Threads per job when the number of jobs is not set.
+const DEFAULT_JOB_THREADS: usize = 4;
+
+pub struct Scheduler {
+    pub jobs: usize,
+    pub threads_per_job: usize,
+}
+
+impl Scheduler {
+    /// `threads` is the total budget. Defaults to all available threads.
+    /// `jobs` is the number of samples run at once.
+    /// Defaults to one job for every four threads. Never more jobs
+    /// than threads or samples, so each job has at least one thread.
+    pub fn new(threads: Option<usize>, jobs: Option<usize>, sample_count: usize) -> Self {
+        let threads = threads.unwrap_or_else(num_cpus::get).max(1);
+        let jobs = jobs
+            .unwrap_or(threads / DEFAULT_JOB_THREADS)
+            .min(threads)
+            .clamp(1, sample_count.max(1));
+        Self {
+            jobs,
+            threads_per_job: (threads / jobs).clamp(1, MAX_FASTP_THREADS),
+        }
+    }
+
+    pub fn is_parallel(&self) -> bool {
+        self.jobs > 1
+    }
+
+    /// Run `job` on every sample, at most `jobs` at a time.
+    pub fn run<F>(&self, samples: &[Sample], job: F)
+    where
+        F: Fn(&Sample) + Sync + Send,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()
+            .expect("CAN'T CREATE THE FASTP THREAD POOL");
+        pool.install(|| samples.par_iter().for_each(job));
+    }
+}
+
+pub fn lock_log() -> MutexGuard<'static, ()> {
+    LOG_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thread_budget_test() {
+        let scheduler = Scheduler::new(Some(64), None, 300);
+        assert_eq!(16, scheduler.jobs);
+        assert_eq!(4, scheduler.threads_per_job);
+
+        let scheduler = Scheduler::new(Some(64), Some(8), 300);
+        assert_eq!(8, scheduler.threads_per_job);
+
+        let scheduler = Scheduler::new(Some(64), None, 2);
+        assert_eq!(2, scheduler.jobs);
+        assert_eq!(MAX_FASTP_THREADS, scheduler.threads_per_job);
+    }
+
+    #[test]
+    fn small_thread_budget_test() {
+        let scheduler = Scheduler::new(Some(2), None, 10);
+        assert!(!scheduler.is_parallel());
+        assert_eq!(2, scheduler.threads_per_job);
+
+        let scheduler = Scheduler::new(Some(2), Some(4), 10);
+        assert_eq!(2, scheduler.jobs);
+        assert_eq!(1, scheduler.threads_per_job);
+
+        let scheduler = Scheduler::new(Some(8), Some(4), 3);
+        assert_eq!(3, scheduler.jobs);
+        assert_eq!(2, scheduler.threads_per_job);
+    }
+}
@@ -0,0 +1,336 @@
+//! Cross-sample qc summary from the fastp json reports.
+//!
+//! One row per sample directory with a `fastp_reports/fastp.json`,
+//! followed by the sample metadata from the run manifest.
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, LineWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::qc::threshold::QcThresholds;
+use crate::sheet;
+use crate::utils::csv;
+
+pub const SUMMARY_FILE: &str = "yap-qc_summary.csv";
+
+const FAILED: &str = "fail";
+
+const COLUMNS: [&str; 16] = [
+    "sample",
+    "reads_before",
+    "reads_after",
+    "reads_retained",
+    "bases_before",
+    "bases_after",
+    "q20_before",
+    "q20_after",
+    "q30_before",
+    "q30_after",
+    "duplication",
+    "insert_size_peak",
+    "adapter_trimmed_reads",
+    "adapter_trimmed_bases",
+    "qc_status",
+    "qc_fail_reasons",
+];
+
+// The parts of the fastp json report used in the summary.
+// Sections missing in a report, e.g. insert size for single-end reads,
+// are left empty in the summary.
+#[derive(Deserialize)]
+struct FastpJson {
+    summary: FastpSummary,
+    duplication: Option<Duplication>,
+    insert_size: Option<InsertSize>,
+    adapter_cutting: Option<AdapterCutting>,
+}
+
+#[derive(Deserialize)]
+struct FastpSummary {
+    before_filtering: ReadStats,
+    after_filtering: ReadStats,
+}
+
+#[derive(Deserialize)]
+struct ReadStats {
+    total_reads: u64,
+    total_bases: u64,
+    q20_rate: f64,
+    q30_rate: f64,
+}
+
+#[derive(Deserialize)]
+struct Duplication {
+    rate: f64,
+}
+
+#[derive(Deserialize)]
+struct InsertSize {
+    peak: u64,
+}
+
+#[derive(Deserialize)]
+struct AdapterCutting {
+    adapter_trimmed_reads: u64,
+    adapter_trimmed_bases: u64,
+}
+
+/// QC statistics of a sample. Rates are fractions, as in fastp.
+pub struct FastpStats {
+    pub sample: String,
+    pub reads_before: u64,
+    pub reads_after: u64,
+    pub bases_before: u64,
+    pub bases_after: u64,
+    pub q20_before: f64,
+    pub q20_after: f64,
+    pub q30_before: f64,
+    pub q30_after: f64,
+    pub duplication: Option<f64>,
+    pub insert_size_peak: Option<u64>,
+    pub adapter_trimmed_reads: Option<u64>,
+    pub adapter_trimmed_bases: Option<u64>,
+}
+
+impl FastpStats {
+    pub fn from_json(sample: &str, path: &Path) -> std::result::Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("CANNOT READ {}: {}", path.display(), e))?;
+        let json: FastpJson = serde_json::from_str(&content)
+            .map_err(|e| format!("INVALID FASTP REPORT {}: {}", path.display(), e))?;
+        let before = json.summary.before_filtering;
+        let after = json.summary.after_filtering;
+        Ok(Self {
+            sample: sample.to_string(),
+            reads_before: before.total_reads,
+            reads_after: after.total_reads,
+            bases_before: before.total_bases,
+            bases_after: after.total_bases,
+            q20_before: before.q20_rate,
+            q20_after: after.q20_rate,
+            q30_before: before.q30_rate,
+            q30_after: after.q30_rate,
+            duplication: json.duplication.map(|d| d.rate),
+            insert_size_peak: json.insert_size.map(|i| i.peak),
+            adapter_trimmed_reads: json
+                .adapter_cutting
+                .as_ref()
+                .map(|a| a.adapter_trimmed_reads),
+            adapter_trimmed_bases: json.adapter_cutting.map(|a| a.adapter_trimmed_bases),
+        })
+    }
+
+    /// Fraction of the reads that passed the filters.
+    pub fn reads_retained(&self) -> f64 {
+        if self.reads_before == 0 {
+            return 0.0;
+        }
+        self.reads_after as f64 / self.reads_before as f64
+    }
+
+    fn to_row(&self, thresholds: &QcThresholds) -> Vec<String> {
+        let failures = thresholds.check(self);
+        let status = if failures.is_empty() { "pass" } else { FAILED };
+        vec![
+            self.sample.clone(),
+            self.reads_before.to_string(),
+            self.reads_after.to_string(),
+            format_rate(self.reads_retained()),
+            self.bases_before.to_string(),
+            self.bases_after.to_string(),
+            format_rate(self.q20_before),
+            format_rate(self.q20_after),
+            format_rate(self.q30_before),
+            format_rate(self.q30_after),
+            self.duplication.map(format_rate).unwrap_or_default(),
+            format_option(self.insert_size_peak),
+            format_option(self.adapter_trimmed_reads),
+            format_option(self.adapter_trimmed_bases),
+            status.to_string(),
+            failures.join("; "),
+        ]
+    }
+}
+
+/// Parse the fastp reports of every sample in a yap qc output dir.
+/// Samples are sorted by their directory name.
+pub fn find_stats(dir: &Path) -> Vec<FastpStats> {
+    let mut samples: Vec<PathBuf> = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| get_report_path(e.path()).is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    samples.sort();
+    samples
+        .iter()
+        .map(|path| {
+            let sample = path.file_name().unwrap().to_string_lossy();
+            let json = get_report_path(path);
+            FastpStats::from_json(&sample, &json).unwrap_or_else(|e| panic!("{}", e))
+        })
+        .collect()
+}
+
+/// Samples in `target_dirs` that failed the thresholds or have no
+/// readable fastp report, e.g. because fastp crashed. Other sample
+/// dirs in `dir`, such as those of earlier runs, are not checked.
+pub fn find_failed_samples(
+    dir: &Path,
+    target_dirs: &[PathBuf],
+    thresholds: &QcThresholds,
+) -> Vec<String> {
+    target_dirs
+        .iter()
+        .filter(|target| {
+            let sample = target.to_string_lossy();
+            match FastpStats::from_json(&sample, &get_report_path(&dir.join(target))) {
+                Ok(stats) => !thresholds.check(&stats).is_empty(),
+                Err(_) => true,
+            }
+        })
+        .map(|target| target.to_string_lossy().to_string())
+        .collect()
+}
+
+/// The fastp json report in a sample output dir.
+pub fn get_report_path(sample_dir: &Path) -> PathBuf {
+    sample_dir.join("fastp_reports").join("fastp.json")
+}
+
+/// Write the summary of a yap qc output dir to `output`.
+/// The summary is tab separated if `output` ends with `.tsv`.
+pub fn write_summary(dir: &Path, output: &Path, thresholds: &QcThresholds) -> Result<()> {
+    let stats = find_stats(dir);
+    let metadata = sheet::read_manifest_metadata(dir);
+    // Every manifest row has the same metadata keys.
+    let keys: Vec<String> = metadata
+        .values()
+        .next()
+        .map(|values| values.iter().map(|(key, _)| key.clone()).collect())
+        .unwrap_or_default();
+    let sep = match output.extension() {
+        Some(ext) if ext == "tsv" => '\t',
+        _ => ',',
+    };
+
+    let mut writer = LineWriter::new(File::create(output)?);
+    let header: Vec<&str> = COLUMNS
+        .iter()
+        .copied()
+        .chain(keys.iter().map(|k| k.as_str()))
+        .collect();
+    writeln!(writer, "{}", csv::join_fields(&header, sep))?;
+    for sample in &stats {
+        let mut row = sample.to_row(thresholds);
+        let values = metadata.get(&sample.sample);
+        keys.iter().for_each(|key| {
+            let value = values
+                .and_then(|v| v.iter().find(|(k, _)| k == key))
+                .map(|(_, value)| value.clone());
+            row.push(value.unwrap_or_default());
+        });
+        writeln!(writer, "{}", csv::join_fields(&row, sep))?;
+    }
+    Ok(())
+}
+
+/// Samples marked as failed in the summary of a yap qc output dir.
+/// Empty if there is no summary.
+pub fn read_failed_samples(dir: &Path) -> HashSet<String> {
+    let file = match File::open(dir.join(SUMMARY_FILE)) {
+        Ok(file) => file,
+        Err(_) => return HashSet::new(),
+    };
+    let mut lines = BufReader::new(file).lines().map_while(Result::ok);
+    let status = lines
+        .next()
+        .and_then(|header| csv::split_fields(&header, ',').ok())
+        .and_then(|header| header.iter().position(|col| col == "qc_status"));
+    match status {
+        Some(idx) => lines
+            .filter_map(|line| csv::split_fields(&line, ',').ok())
+            .filter(|cols| cols.get(idx).map(String::as_str) == Some(FAILED))
+            .map(|cols| cols[0].clone())
+            .collect(),
+        None => HashSet::new(),
+    }
+}
+
+fn format_rate(rate: f64) -> String {
+    format!("{:.4}", rate)
+}
+
+fn format_option(value: Option<u64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fastp_stats_test() {
+        let path = Path::new("test_files/qc_summary/Rattus_ABC1/fastp_reports/fastp.json");
+        let stats = FastpStats::from_json("Rattus_ABC1", path).unwrap();
+
+        assert_eq!(2000000, stats.reads_before);
+        assert_eq!(1900000, stats.reads_after);
+        assert_eq!(0.95, stats.reads_retained());
+        assert_eq!(0.93, stats.q30_after);
+        assert_eq!(Some(0.0525), stats.duplication);
+        assert_eq!(Some(187), stats.insert_size_peak);
+        assert_eq!(Some(12400000), stats.adapter_trimmed_bases);
+    }
+
+    #[test]
+    fn write_summary_test() {
+        let output = std::env::temp_dir().join("yap_write_summary_test.tsv");
+
+        let thresholds = QcThresholds {
+            min_reads: Some(100000),
+            ..Default::default()
+        };
+        write_summary(Path::new("test_files/qc_summary"), &output, &thresholds).unwrap();
+        let content = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("sample\treads_before\treads_after\treads_retained"));
+        assert!(lines[0].ends_with("\tvoucher"));
+        assert!(lines[1].starts_with("Mus_ABC2\t1000000\t20000\t0.0200"));
+        assert!(lines[1]
+            .ends_with("\t0.4100\t92\t\t\tfail\treads after filtering 20000 < 100000\tKU 2"));
+        assert!(lines[2].contains("\tpass\t\tKU 1"));
+        assert!(lines[2].starts_with("Rattus_ABC1\t"));
+    }
+
+    #[test]
+    fn find_failed_samples_test() {
+        let dir = Path::new("test_files/qc_summary");
+        let thresholds = QcThresholds {
+            min_reads: Some(100000),
+            ..Default::default()
+        };
+        let targets = [PathBuf::from("Rattus_ABC1"), PathBuf::from("ABC3")];
+        let failed = find_failed_samples(dir, &targets, &thresholds);
+
+        // Mus_ABC2 fails the thresholds but is not in this run.
+        assert_eq!(vec![String::from("ABC3")], failed);
+    }
+
+    #[test]
+    fn failed_samples_test() {
+        let dir = Path::new("test_files/qc_failed");
+        let failed = read_failed_samples(dir);
+
+        assert_eq!(1, failed.len());
+        assert!(failed.contains("Mus_ABC2"));
+    }
+}
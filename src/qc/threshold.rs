@@ -0,0 +1,61 @@
+//! Pass or fail samples by their fastp statistics.
+use crate::qc::summary::FastpStats;
+
+/// Thresholds are off unless set. Rates are fractions, as in fastp.
+#[derive(Default, Clone)]
+pub struct QcThresholds {
+    pub min_reads: Option<u64>,
+    pub min_q30: Option<f64>,
+    pub max_duplication: Option<f64>,
+}
+
+impl QcThresholds {
+    /// Reasons the sample failed. Empty if it passed.
+    pub fn check(&self, stats: &FastpStats) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(min) = self.min_reads {
+            if stats.reads_after < min {
+                failures.push(format!(
+                    "reads after filtering {} < {}",
+                    stats.reads_after, min
+                ));
+            }
+        }
+        if let Some(min) = self.min_q30 {
+            if stats.q30_after < min {
+                failures.push(format!("Q30 {:.4} < {}", stats.q30_after, min));
+            }
+        }
+        // Fastp skips the duplication report with --dont_eval_duplication.
+        if let (Some(max), Some(rate)) = (self.max_duplication, stats.duplication) {
+            if rate > max {
+                failures.push(format!("duplication {:.4} > {}", rate, max));
+            }
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn thresholds_test() {
+        let path = Path::new("test_files/qc_summary/Mus_ABC2/fastp_reports/fastp.json");
+        let stats = FastpStats::from_json("Mus_ABC2", path).unwrap();
+        let mut thresholds = QcThresholds::default();
+        assert!(thresholds.check(&stats).is_empty());
+
+        thresholds.min_reads = Some(100000);
+        thresholds.min_q30 = Some(0.8);
+        thresholds.max_duplication = Some(0.3);
+        let failures = thresholds.check(&stats);
+
+        assert_eq!(2, failures.len());
+        assert!(failures[0].starts_with("reads after filtering 20000"));
+        assert!(failures[1].starts_with("duplication 0.4100"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Render the report as a single html file.
+//!
+//! The styles, the table sorting script, and the charts are inline,
+//! so the report opens offline.
+use std::fmt::Write;
+
+use crate::report::{Metric, Report, SampleReport};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+th { background: #eee; cursor: pointer; user-select: none; }
+th[data-order=asc]::after { content: ' \\25B2'; }
+th[data-order=desc]::after { content: ' \\25BC'; }
+td:first-child, th:first-child { text-align: left; }
+td.outlier { background: #f8d7da; font-weight: bold; }
+.charts { display: flex; flex-wrap: wrap; gap: 2em; }
+.chart h3 { margin: 0 0 0.5em 0; }
+";
+
+// Sort by the clicked column. Numbers are sorted by value,
+// and empty cells go last.
+const SCRIPT: &str = "
+document.querySelectorAll('th').forEach((th, col) => th.addEventListener('click', () => {
+  const body = th.closest('table').tBodies[0];
+  const asc = th.dataset.order !== 'asc';
+  th.parentNode.querySelectorAll('th').forEach(h => delete h.dataset.order);
+  th.dataset.order = asc ? 'asc' : 'desc';
+  const key = row => row.cells[col].dataset.value ?? row.cells[col].textContent;
+  Array.from(body.rows).sort((a, b) => {
+    const x = key(a), y = key(b);
+    if (x === '' || y === '') return (x === '') - (y === '');
+    const nx = Number(x), ny = Number(y);
+    const cmp = isNaN(nx) || isNaN(ny) ? x.localeCompare(y) : nx - ny;
+    return asc ? cmp : -cmp;
+  }).forEach(row => body.appendChild(row));
+}));
+";
+
+const BAR_COLOR: &str = "#4a7ab5";
+const OUTLIER_COLOR: &str = "#d9534f";
+
+pub fn render(report: &Report) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>YAP Report</title>\n");
+    writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+    html.push_str("<h1>YAP Report</h1>\n");
+    let flagged = report
+        .samples
+        .iter()
+        .filter(|s| report.outlier_count(s) > 0)
+        .count();
+    writeln!(
+        html,
+        "<p>{} samples. {} flagged as outliers (outside 1.5 IQR of the quartiles).</p>",
+        report.samples.len(),
+        flagged
+    )
+    .unwrap();
+    render_table(&mut html, report);
+    render_charts(&mut html, report);
+    writeln!(html, "<script>{}</script>\n</body>\n</html>", SCRIPT).unwrap();
+    html
+}
+
+fn render_table(html: &mut String, report: &Report) {
+    let headers = [
+        "Sample",
+        "Reads before",
+        "Reads after",
+        Metric::Retention.name(),
+        "Q30 after (%)",
+        Metric::Duplication.name(),
+        Metric::Contigs.name(),
+        "Total length (bp)",
+        Metric::N50.name(),
+        "SPAdes",
+        "Flags",
+    ];
+    html.push_str("<table>\n<thead><tr>");
+    headers
+        .iter()
+        .for_each(|h| write!(html, "<th>{}</th>", h).unwrap());
+    html.push_str("</tr></thead>\n<tbody>\n");
+    report.samples.iter().for_each(|sample| {
+        html.push_str("<tr>");
+        write!(html, "<td>{}</td>", escape(&sample.sample)).unwrap();
+        let qc = sample.qc.as_ref();
+        let assembly = sample.assembly.as_ref();
+        push_cell(html, qc.map(|q| q.reads_before as f64), 0, false);
+        push_cell(html, qc.map(|q| q.reads_after as f64), 0, false);
+        push_metric(html, report, Metric::Retention, sample, 1);
+        push_cell(html, qc.map(|q| q.q30_after * 100.0), 1, false);
+        push_metric(html, report, Metric::Duplication, sample, 1);
+        push_metric(html, report, Metric::Contigs, sample, 0);
+        push_cell(html, assembly.map(|a| a.total_len as f64), 0, false);
+        push_metric(html, report, Metric::N50, sample, 0);
+        let status = match assembly {
+            Some(a) if a.is_complete => "finished",
+            Some(_) => "incomplete",
+            None => "",
+        };
+        write!(html, "<td>{}</td>", status).unwrap();
+        write!(html, "<td>{}</td>", report.outlier_count(sample)).unwrap();
+        html.push_str("</tr>\n");
+    });
+    html.push_str("</tbody>\n</table>\n");
+}
+
+fn push_metric(
+    html: &mut String,
+    report: &Report,
+    metric: Metric,
+    sample: &SampleReport,
+    decimals: usize,
+) {
+    let is_outlier = report.is_outlier(metric, sample);
+    push_cell(html, metric.get(sample), decimals, is_outlier);
+}
+
+// The raw value is kept for sorting.
+fn push_cell(html: &mut String, value: Option<f64>, decimals: usize, is_outlier: bool) {
+    let class = if is_outlier { " class=\"outlier\"" } else { "" };
+    match value {
+        Some(value) => write!(
+            html,
+            "<td{} data-value=\"{}\">{:.*}</td>",
+            class, value, decimals, value
+        )
+        .unwrap(),
+        None => html.push_str("<td data-value=\"\"></td>"),
+    }
+}
+
+fn render_charts(html: &mut String, report: &Report) {
+    html.push_str("<div class=\"charts\">\n");
+    Metric::ALL
+        .iter()
+        .for_each(|metric| render_bar_chart(html, report, *metric));
+    html.push_str("</div>\n");
+}
+
+// Horizontal bars, one per sample with a value. Outliers are red.
+fn render_bar_chart(html: &mut String, report: &Report, metric: Metric) {
+    let bars: Vec<(&SampleReport, f64)> = report
+        .samples
+        .iter()
+        .filter_map(|s| metric.get(s).map(|v| (s, v)))
+        .collect();
+    if bars.is_empty() {
+        return;
+    }
+    let max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let (label_width, bar_width, bar_height) = (160.0, 300.0, 16.0);
+    let height = bars.len() as f64 * (bar_height + 4.0);
+    writeln!(
+        html,
+        "<div class=\"chart\"><h3>{}</h3>\n<svg xmlns=\"http://www.w3.org/2000/svg\" \
+        width=\"{}\" height=\"{}\" font-size=\"12\">",
+        metric.name(),
+        label_width + bar_width + 80.0,
+        height
+    )
+    .unwrap();
+    bars.iter().enumerate().for_each(|(i, (sample, value))| {
+        let y = i as f64 * (bar_height + 4.0);
+        let width = if max > 0.0 {
+            value / max * bar_width
+        } else {
+            0.0
+        };
+        let color = if report.is_outlier(metric, sample) {
+            OUTLIER_COLOR
+        } else {
+            BAR_COLOR
+        };
+        writeln!(
+            html,
+            "<text x=\"0\" y=\"{:.1}\">{}</text>\
+            <rect x=\"{}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/>\
+            <text x=\"{:.1}\" y=\"{:.1}\">{:.1}</text>",
+            y + 12.0,
+            escape(&sample.sample),
+            label_width,
+            y,
+            width,
+            bar_height,
+            color,
+            label_width + width + 4.0,
+            y + 12.0,
+            value
+        )
+        .unwrap();
+    });
+    html.push_str("</svg></div>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_test() {
+        assert_eq!("A&amp;B &lt;1&gt;", escape("A&B <1>"));
+    }
+}
@@ -0,0 +1,256 @@
+//! Multi-sample project report.
+//!
+//! Collects the fastp json reports from the qc output dir and the SPAdes
+//! contigs and logs from the assembly output dir into one offline html file.
+mod html;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Result};
+use std::path::Path;
+
+use crate::assembly;
+use crate::qc::{self, FastpStats};
+use crate::sheet;
+
+pub const REPORT_FILE: &str = "yap-report.html";
+
+/// Assembly statistics from the SPAdes contigs.
+pub struct ContigStats {
+    pub count: usize,
+    pub total_len: usize,
+    pub n50: usize,
+    pub is_complete: bool,
+}
+
+impl ContigStats {
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let mut lengths = read_contig_lengths(&dir.join("contigs.fasta"))?;
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(Self {
+            count: lengths.len(),
+            total_len: lengths.iter().sum(),
+            n50: get_n50(&lengths),
+            is_complete: assembly::is_complete(dir),
+        })
+    }
+}
+
+pub struct SampleReport {
+    pub sample: String,
+    pub qc: Option<FastpStats>,
+    pub assembly: Option<ContigStats>,
+}
+
+/// Metrics checked for outliers.
+#[derive(Clone, Copy)]
+pub enum Metric {
+    Retention,
+    Duplication,
+    Contigs,
+    N50,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::Retention,
+        Metric::Duplication,
+        Metric::Contigs,
+        Metric::N50,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Metric::Retention => "Read retention (%)",
+            Metric::Duplication => "Duplication (%)",
+            Metric::Contigs => "Contigs",
+            Metric::N50 => "N50 (bp)",
+        }
+    }
+
+    pub fn get(&self, sample: &SampleReport) -> Option<f64> {
+        match self {
+            Metric::Retention => sample.qc.as_ref().map(|q| q.reads_retained() * 100.0),
+            Metric::Duplication => sample
+                .qc
+                .as_ref()
+                .and_then(|q| q.duplication)
+                .map(|d| d * 100.0),
+            Metric::Contigs => sample.assembly.as_ref().map(|a| a.count as f64),
+            Metric::N50 => sample.assembly.as_ref().map(|a| a.n50 as f64),
+        }
+    }
+}
+
+pub struct Report {
+    pub samples: Vec<SampleReport>,
+    // Tukey fences of each metric.
+    fences: Vec<Option<(f64, f64)>>,
+}
+
+impl Report {
+    /// Either dir can be missing. Samples are joined by their qc
+    /// target directory, which is the sample id unless renamed.
+    pub fn new(qc_dir: Option<&Path>, assembly_dir: Option<&Path>) -> Self {
+        let mut samples: BTreeMap<String, SampleReport> = BTreeMap::new();
+        if let Some(dir) = qc_dir {
+            qc::find_stats(dir).into_iter().for_each(|stats| {
+                let report = get_entry(&mut samples, &stats.sample);
+                report.qc = Some(stats);
+            });
+        }
+        if let Some(dir) = assembly_dir {
+            let targets = sheet::read_manifest_targets(dir);
+            find_assemblies(dir).into_iter().for_each(|(id, stats)| {
+                let sample = targets.get(&id).unwrap_or(&id);
+                let report = get_entry(&mut samples, sample);
+                report.assembly = Some(stats);
+            });
+        }
+
+        let samples: Vec<SampleReport> = samples.into_values().collect();
+        let fences = Metric::ALL
+            .iter()
+            .map(|metric| {
+                let values: Vec<f64> = samples.iter().filter_map(|s| metric.get(s)).collect();
+                get_fences(&values)
+            })
+            .collect();
+        Self { samples, fences }
+    }
+
+    pub fn is_outlier(&self, metric: Metric, sample: &SampleReport) -> bool {
+        match (self.fences[metric as usize], metric.get(sample)) {
+            (Some((low, high)), Some(value)) => value < low || value > high,
+            _ => false,
+        }
+    }
+
+    pub fn outlier_count(&self, sample: &SampleReport) -> usize {
+        Metric::ALL
+            .iter()
+            .filter(|m| self.is_outlier(**m, sample))
+            .count()
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        fs::write(output, html::render(self))
+    }
+}
+
+fn get_entry<'a>(
+    samples: &'a mut BTreeMap<String, SampleReport>,
+    sample: &str,
+) -> &'a mut SampleReport {
+    samples
+        .entry(sample.to_string())
+        .or_insert_with(|| SampleReport {
+            sample: sample.to_string(),
+            qc: None,
+            assembly: None,
+        })
+}
+
+// Sample dirs with SPAdes contigs, by sample id.
+fn find_assemblies(dir: &Path) -> Vec<(String, ContigStats)> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_else(|_| Vec::new());
+    entries.sort();
+    entries
+        .iter()
+        .filter(|path| path.join("contigs.fasta").is_file())
+        .map(|path| {
+            let id = path.file_name().unwrap().to_string_lossy().to_string();
+            let stats = ContigStats::from_dir(path)
+                .unwrap_or_else(|e| panic!("CANNOT READ CONTIGS IN {}: {}", path.display(), e));
+            (id, stats)
+        })
+        .collect()
+}
+
+fn read_contig_lengths(path: &Path) -> Result<Vec<usize>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut lengths = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with('>') {
+            lengths.push(0);
+        } else if let Some(len) = lengths.last_mut() {
+            *len += line.trim_end().len();
+        }
+    }
+    Ok(lengths)
+}
+
+// Lengths must be sorted from the longest.
+fn get_n50(lengths: &[usize]) -> usize {
+    let half = lengths.iter().sum::<usize>() as f64 / 2.0;
+    let mut sum = 0;
+    lengths
+        .iter()
+        .find(|&&len| {
+            sum += len;
+            sum as f64 >= half
+        })
+        .copied()
+        .unwrap_or(0)
+}
+
+// Values outside Q1 - 1.5 IQR and Q3 + 1.5 IQR are outliers.
+// Needs at least four values.
+fn get_fences(values: &[f64]) -> Option<(f64, f64)> {
+    if values.len() < 4 {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let q1 = get_quantile(&sorted, 0.25);
+    let q3 = get_quantile(&sorted, 0.75);
+    let iqr = q3 - q1;
+    Some((q1 - 1.5 * iqr, q3 + 1.5 * iqr))
+}
+
+fn get_quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = (sorted.len() - 1) as f64 * q;
+    let low = pos.floor() as usize;
+    let high = pos.ceil() as usize;
+    sorted[low] + (sorted[high] - sorted[low]) * (pos - low as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn n50_test() {
+        assert_eq!(80, get_n50(&[100, 80, 50, 20, 10]));
+        assert_eq!(0, get_n50(&[]));
+    }
+
+    #[test]
+    fn fences_test() {
+        let values = [95.0, 94.0, 96.0, 93.0, 2.0];
+        let (low, high) = get_fences(&values).unwrap();
+
+        assert!(2.0 < low);
+        assert!(96.0 < high);
+        assert_eq!(None, get_fences(&values[..3]));
+    }
+
+    #[test]
+    fn report_test() {
+        let qc_dir = Path::new("test_files/qc_summary");
+        let assembly_dir = Path::new("test_files/report/assemblies");
+        let report = Report::new(Some(qc_dir), Some(assembly_dir));
+        let sample = &report.samples[1];
+        let assembly = sample.assembly.as_ref().unwrap();
+
+        assert_eq!(2, report.samples.len());
+        assert_eq!("Rattus_ABC1", sample.sample);
+        assert!(sample.qc.is_some());
+        assert_eq!(3, assembly.count);
+        assert_eq!(10, assembly.n50);
+        assert!(assembly.is_complete);
+    }
+}
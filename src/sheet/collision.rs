@@ -0,0 +1,187 @@
+//! Pairwise distances between the sample indexes.
+//!
+//! Two samples whose i7 and i5 indexes are identical or differ by
+//! only a few bases cannot be told apart reliably when demultiplexing.
+use crate::sheet::kit;
+use crate::sheet::tag::IndexRead;
+use crate::sheet::Sample;
+
+/// Index pairs closer than this are reported.
+pub const MIN_INDEX_DISTANCE: usize = 3;
+
+pub struct IndexCollision<'a> {
+    pub sample: &'a str,
+    pub other: &'a str,
+    /// Mismatches across the i7 and i5 indexes.
+    pub distance: usize,
+    /// Index reads whose lengths differ. Only their shared length is compared.
+    pub length_mismatches: Vec<IndexRead>,
+}
+
+impl IndexCollision<'_> {
+    pub fn message(&self) -> String {
+        let message = match self.distance {
+            0 => format!("SAME INDEXES AS {}", self.other),
+            d => format!("INDEXES ARE {} MISMATCHES FROM {}", d, self.other),
+        };
+        if self.length_mismatches.is_empty() {
+            return message;
+        }
+        let reads: Vec<&str> = self.length_mismatches.iter().map(|r| r.name()).collect();
+        format!(
+            "{}. THE {} INDEX LENGTHS DIFFER AND ONLY THE SHARED BASES ARE COMPARED",
+            message,
+            reads.join(" AND ")
+        )
+    }
+}
+
+// The index bases of a sample, from the index tags or kit, or from
+// full adapters that follow the template of a built-in kit.
+struct SampleIndex<'a> {
+    id: &'a str,
+    i7: Option<String>,
+    i5: Option<String>,
+}
+
+impl<'a> SampleIndex<'a> {
+    fn new(sample: &'a Sample) -> Self {
+        let get_index = |index: &Option<String>, adapter: &Option<String>, read| {
+            index
+                .clone()
+                .or_else(|| kit::find_builtin_index(adapter.as_ref()?, read))
+        };
+        Self {
+            id: &sample.id,
+            i7: get_index(&sample.index_i7, &sample.adapter_i7, IndexRead::I7),
+            i5: get_index(&sample.index_i5, &sample.adapter_i5, IndexRead::I5),
+        }
+    }
+}
+
+/// Sample pairs with an index distance below `min_distance`.
+/// Samples without known index bases are skipped.
+pub fn find_index_collisions(samples: &[Sample], min_distance: usize) -> Vec<IndexCollision<'_>> {
+    let indexed: Vec<SampleIndex> = samples
+        .iter()
+        .map(SampleIndex::new)
+        .filter(|s| s.i7.is_some() || s.i5.is_some())
+        .collect();
+    let mut collisions = Vec::new();
+    for (idx, sample) in indexed.iter().enumerate() {
+        for other in &indexed[idx + 1..] {
+            let distance = get_index_distance(sample, other);
+            if let Some((distance, length_mismatches)) = distance.filter(|(d, _)| *d < min_distance)
+            {
+                collisions.push(IndexCollision {
+                    sample: sample.id,
+                    other: other.id,
+                    distance,
+                    length_mismatches,
+                });
+            }
+        }
+    }
+    collisions
+}
+
+// An index only counts when both samples have it. Returns the distance
+// and the index reads with different lengths. None if the samples
+// share no index.
+fn get_index_distance(
+    sample: &SampleIndex,
+    other: &SampleIndex,
+) -> Option<(usize, Vec<IndexRead>)> {
+    let pairs: Vec<(IndexRead, &String, &String)> = [
+        (IndexRead::I7, &sample.i7, &other.i7),
+        (IndexRead::I5, &sample.i5, &other.i5),
+    ]
+    .into_iter()
+    .filter_map(|(read, a, b)| Some((read, a.as_ref()?, b.as_ref()?)))
+    .collect();
+    if pairs.is_empty() {
+        return None;
+    }
+    let distance = pairs.iter().map(|(_, a, b)| hamming_distance(a, b)).sum();
+    let length_mismatches = pairs
+        .iter()
+        .filter(|(_, a, b)| a.len() != b.len())
+        .map(|(read, _, _)| *read)
+        .collect();
+    Some((distance, length_mismatches))
+}
+
+// Compares the shared length, as demultiplexers
+// trim indexes to the shortest one.
+fn hamming_distance(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).filter(|(x, y)| x != y).count()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn get_sample(id: &str, i7: &str, i5: Option<&str>) -> Sample {
+        let mut sample = Sample::new(id, Path::new("."));
+        sample.index_i7 = Some(String::from(i7));
+        sample.index_i5 = i5.map(String::from);
+        sample
+    }
+
+    #[test]
+    fn index_collision_test() {
+        let samples = vec![
+            get_sample("A", "ATTACTCG", Some("TATAGCCT")),
+            get_sample("B", "ATTACTCG", Some("TATAGCCT")),
+            get_sample("C", "ATTACTCG", Some("TATAGCAA")),
+            get_sample("D", "TCCGGAGA", Some("ATAGAGGC")),
+            Sample::new("E", Path::new(".")),
+        ];
+        let collisions = find_index_collisions(&samples, MIN_INDEX_DISTANCE);
+        let pairs: Vec<(&str, &str, usize)> = collisions
+            .iter()
+            .map(|c| (c.sample, c.other, c.distance))
+            .collect();
+
+        assert_eq!(vec![("A", "B", 0), ("A", "C", 2), ("B", "C", 2)], pairs);
+        assert_eq!("SAME INDEXES AS B", collisions[0].message());
+    }
+
+    #[test]
+    fn single_index_distance_test() {
+        let a = get_sample("A", "ATTACTCG", None);
+        let b = get_sample("B", "ATTACTCGAA", Some("TATAGCCT"));
+        let mut c = Sample::new("C", Path::new("."));
+        c.index_i5 = Some(String::from("TATAGCCT"));
+        let (a, b, c) = (
+            SampleIndex::new(&a),
+            SampleIndex::new(&b),
+            SampleIndex::new(&c),
+        );
+
+        assert_eq!(Some((0, vec![IndexRead::I7])), get_index_distance(&a, &b));
+        assert_eq!(None, get_index_distance(&a, &c));
+    }
+
+    #[test]
+    fn full_adapter_collision_test() {
+        let i5 = "AATGATACGGCGACCACCGAGATCTACACTATAGCCTACACTCTTTCCCTACACGACGC";
+        let mut a = Sample::new("A", Path::new("."));
+        a.adapter_i5 = Some(String::from(i5));
+        a.adapter_i7 = Some(String::from("CAAGCAGAAGACGGCATACGAGATCGAGTAATGTGACTGGAG"));
+        let mut b = Sample::new("B", Path::new("."));
+        b.adapter_i5 = Some(String::from(i5));
+        b.adapter_i7 = Some(String::from("CAAGCAGAAGACGGCATACGAGATCGAGTAATAAGTGACTGGAG"));
+        let samples = vec![a, b];
+        let collisions = find_index_collisions(&samples, MIN_INDEX_DISTANCE);
+
+        assert_eq!(1, collisions.len());
+        assert_eq!(0, collisions[0].distance);
+        assert_eq!(
+            "SAME INDEXES AS B. THE i7 INDEX LENGTHS DIFFER AND ONLY THE SHARED BASES ARE COMPARED",
+            collisions[0].message()
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use glob::{self, MatchOptions};
+use regex::Regex;
+
+use crate::sheet::naming::{self, NamingScheme};
+
+/// Match fastq files, compressed or not.
+const FASTQ_REGEX: &str = r"(?i)\.(fq|fastq)(\.gz|\.gzip)?$";
+
+/// Words that mark unpaired reads, e.g. `sample_unpaired.fq.gz`.
+const SINGLETON_REGEX: &str = r"(?i)^[_.-](unpaired|singletons?|single|orphans?|se)[_.-]";
+
+/// Lazy static regex matcher
+///
+/// Matches a file name with a given pattern
+/// Returns true if the file name matches the pattern
+macro_rules! re_match {
+    ($pattern: ident, $fname: ident) => {{
+        lazy_static! {
+            static ref RE: Regex = Regex::new($pattern).unwrap();
+        }
+        RE.is_match($fname)
+    }};
+}
+
+pub fn is_fastq(fname: &str) -> bool {
+    re_match!(FASTQ_REGEX, fname)
+}
+
+/// Find the fastq files of a sample in a directory.
+pub struct ReadFinder<'a> {
+    dir: &'a Path,
+    id: &'a str,
+}
+
+impl<'a> ReadFinder<'a> {
+    pub fn new(dir: &'a Path, id: &'a str) -> Self {
+        Self { dir, id }
+    }
+
+    /// Returns the fastq files of the sample. A file belongs to the sample
+    /// when the naming scheme reads the id as its sample name, or when
+    /// the id is followed directly by the lane or read token. Files without
+    /// a read token need the id followed by the extension or an unpaired mark.
+    /// When `whole_dir` is true and none matches the id,
+    /// returns all fastq files in the directory.
+    pub fn find(&self, whole_dir: bool, scheme: &NamingScheme) -> Vec<PathBuf> {
+        let fastq = self.glob_fastq();
+        let matches = self.match_id(&fastq, scheme);
+        if matches.is_empty() && whole_dir {
+            fastq
+        } else {
+            matches
+        }
+    }
+
+    pub fn glob_fastq(&self) -> Vec<PathBuf> {
+        let pattern = self.dir.join("*");
+        let opts = MatchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let mut files: Vec<PathBuf> = glob::glob_with(&pattern.to_string_lossy(), opts)
+            .expect("INVALID GLOB PATTERN")
+            .filter_map(|ok| ok.ok())
+            .filter(|e| e.is_file())
+            .filter(|e| match e.file_name() {
+                Some(fname) => is_fastq(&fname.to_string_lossy()),
+                None => false,
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn match_id(&self, reads: &[PathBuf], scheme: &NamingScheme) -> Vec<PathBuf> {
+        reads
+            .iter()
+            .filter(|read| match read.file_name() {
+                Some(fname) => self.is_sample_read(&fname.to_string_lossy(), scheme),
+                None => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    // A prefix match alone would give `Rattus` the reads of `Rattus_exulans`.
+    fn is_sample_read(&self, fname: &str, scheme: &NamingScheme) -> bool {
+        let name = match scheme.parse(fname) {
+            Some(name) => name,
+            None => return self.is_unpaired_read(fname),
+        };
+        name.sample.as_deref() == Some(self.id) || naming::remove_lane(&name.prefix) == self.id
+    }
+
+    fn is_unpaired_read(&self, fname: &str) -> bool {
+        match fname.strip_prefix(self.id) {
+            Some(rest) => rest.starts_with('.') || re_match!(SINGLETON_REGEX, rest),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_raw_reads_test() {
+        let dir = Path::new("test_files/qc");
+        let finder = ReadFinder::new(dir, "test_1");
+        let files = finder.find(false, &NamingScheme::Auto);
+
+        assert_eq!(2, files.len());
+    }
+
+    #[test]
+    fn glob_id_prefix_test() {
+        let dir = Path::new("test_files/qc");
+        let finder = ReadFinder::new(dir, "test");
+
+        assert!(finder.find(false, &NamingScheme::Auto).is_empty());
+        let dir = Path::new("test_files/illumina");
+        let finder = ReadFinder::new(dir, "Rattus");
+
+        assert!(finder.find(false, &NamingScheme::Auto).is_empty());
+    }
+
+    #[test]
+    fn glob_lane_and_scheme_test() {
+        let dir = Path::new("test_files/illumina");
+        let scheme = NamingScheme::Auto;
+
+        assert_eq!(
+            4,
+            ReadFinder::new(dir, "Bunomys_andrewsi")
+                .find(false, &scheme)
+                .len()
+        );
+        assert_eq!(
+            4,
+            ReadFinder::new(dir, "Bunomys_andrewsi_S1")
+                .find(false, &scheme)
+                .len()
+        );
+        assert_eq!(
+            2,
+            ReadFinder::new(dir, "Rattus_exulans")
+                .find(false, &scheme)
+                .len()
+        );
+    }
+
+    #[test]
+    fn glob_unpaired_test() {
+        let scheme = NamingScheme::Auto;
+        let finder = ReadFinder::new(Path::new("test_files/init"), "sample_test_ABC456");
+
+        assert_eq!(3, finder.find(false, &scheme).len());
+        let finder = ReadFinder::new(Path::new("test_files/single"), "SRR1234");
+
+        assert_eq!(1, finder.find(false, &scheme).len());
+    }
+
+    #[test]
+    fn glob_whole_dir_test() {
+        let dir = Path::new("test_files/assembly/trimmed_test");
+        let finder = ReadFinder::new(dir, "some_animals");
+
+        assert_eq!(0, finder.find(false, &NamingScheme::Auto).len());
+        assert_eq!(2, finder.find(true, &NamingScheme::Auto).len());
+    }
+}
@@ -0,0 +1,183 @@
+//! Interleaved fastq: read 1 and read 2 of each pair one after another
+//! in a single file.
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::sheet::{is_gzip, Layout, Sample};
+
+// Pairs checked to detect an interleaved file.
+const PAIRS_TO_CHECK: usize = 2;
+
+/// True if the first records of the file come in pairs with the same
+/// read name, e.g. `@A1/1` and `@A1/2`, or `@A1 1:N:0` and `@A1 2:N:0`.
+/// False if the file cannot be read.
+pub fn is_interleaved(path: &Path) -> bool {
+    let headers = match read_headers(path, PAIRS_TO_CHECK * 2) {
+        Ok(headers) => headers,
+        Err(_) => return false,
+    };
+    if headers.len() < 2 || headers.len() % 2 != 0 {
+        return false;
+    }
+    let names: Vec<String> = headers.iter().map(|h| get_read_name(h)).collect();
+    let is_paired = names.chunks(2).all(|pair| pair[0] == pair[1]);
+    // Consecutive pairs must be different reads.
+    let is_distinct = names
+        .chunks(2)
+        .zip(names.chunks(2).skip(1))
+        .all(|(a, b)| a[0] != b[0]);
+    is_paired && is_distinct
+}
+
+/// Split the interleaved reads of a sample into read 1 and read 2 files
+/// in `dir`. Returns a copy of the sample that points to them.
+pub fn deinterleave(sample: &Sample, dir: &Path) -> Sample {
+    fs::create_dir_all(dir).expect("CAN'T CREATE DEINTERLEAVED READ DIR");
+    let paired = get_deinterleaved_sample(sample, dir);
+    split_reads(&sample.read_1, &paired.read_1, &paired.read_2)
+        .unwrap_or_else(|e| panic!("FAILED DEINTERLEAVING {}: {}", sample.read_1.display(), e));
+    log::info!("{:18}: {}", "Deinterleaved", sample.read_1.display());
+    log::info!("{:18}  -> {}", "", paired.read_1.display());
+    log::info!("{:18}  -> {}", "", paired.read_2.display());
+    log::info!("");
+    paired
+}
+
+/// The sample as returned by `deinterleave`, without splitting the file.
+pub fn get_deinterleaved_sample(sample: &Sample, dir: &Path) -> Sample {
+    let mut paired = sample.clone();
+    paired.layout = Layout::Paired;
+    paired.read_1 = dir.join(get_mate_name(&sample.read_1, "R1"));
+    paired.read_2 = dir.join(get_mate_name(&sample.read_1, "R2"));
+    paired.dir = dir.to_path_buf();
+    // The raw reads as found are still the interleaved file.
+    paired.lanes = vec![(sample.read_1.clone(), PathBuf::new())];
+    paired
+}
+
+/// Add the read token before the extensions, e.g. `ABC1.fq.gz`
+/// becomes `ABC1_R1.fq.gz`. The output is always gzipped.
+pub fn get_mate_name(read: &Path, token: &str) -> PathBuf {
+    let fname = read
+        .file_name()
+        .expect("Failed parsing filename")
+        .to_string_lossy();
+    let mut name = match fname.find('.') {
+        Some(idx) => format!("{}_{}{}", &fname[..idx], token, &fname[idx..]),
+        None => format!("{}_{}", fname, token),
+    };
+    if !name.ends_with(".gz") {
+        name.push_str(".gz");
+    }
+    PathBuf::from(name)
+}
+
+// The name without the mate number: `/1`, `/2`,
+// or the Illumina comment after the first space.
+fn get_read_name(header: &str) -> String {
+    let name = header.split_whitespace().next().unwrap_or_default();
+    name.strip_suffix("/1")
+        .or_else(|| name.strip_suffix("/2"))
+        .unwrap_or(name)
+        .to_string()
+}
+
+fn open_reader(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    if is_gzip(path)? {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+pub(super) fn read_headers(path: &Path, count: usize) -> Result<Vec<String>> {
+    let reader = open_reader(path)?;
+    let headers = reader
+        .lines()
+        .step_by(4)
+        .take(count)
+        .collect::<Result<Vec<String>>>()?;
+    Ok(headers)
+}
+
+fn split_reads(input: &Path, out_r1: &Path, out_r2: &Path) -> Result<()> {
+    let reader = open_reader(input)?;
+    let create = |path: &Path| -> Result<GzEncoder<BufWriter<File>>> {
+        Ok(GzEncoder::new(
+            BufWriter::new(File::create(path)?),
+            Compression::default(),
+        ))
+    };
+    let mut writers = [create(out_r1)?, create(out_r2)?];
+    // Records are four lines. Even records are read 1, odd records are read 2.
+    for (idx, line) in reader.lines().enumerate() {
+        let writer = &mut writers[(idx / 4) % 2];
+        writeln!(writer, "{}", line?)?;
+    }
+    for writer in writers {
+        writer.finish()?.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn is_interleaved_test() {
+        assert!(is_interleaved(Path::new(
+            "test_files/interleaved/ABC1.fastq.gz"
+        )));
+        assert!(!is_interleaved(Path::new(
+            "test_files/native/Apodemus_R2.fastq"
+        )));
+        assert!(!is_interleaved(Path::new("test_files/missing.fastq")));
+    }
+
+    #[test]
+    fn read_name_test() {
+        assert_eq!("@A1", get_read_name("@A1/2"));
+        assert_eq!(
+            "@M0:1:FC:1:1:10:20",
+            get_read_name("@M0:1:FC:1:1:10:20 1:N:0:ATCACG")
+        );
+    }
+
+    #[test]
+    fn mate_name_test() {
+        assert_eq!(
+            PathBuf::from("ABC1_R2.fq.gz"),
+            get_mate_name(Path::new("raw/ABC1.fq.gz"), "R2")
+        );
+        assert_eq!(
+            PathBuf::from("ABC1_R1.fastq.gz"),
+            get_mate_name(Path::new("raw/ABC1.fastq"), "R1")
+        );
+    }
+
+    #[test]
+    fn deinterleave_test() {
+        let dir = std::env::temp_dir().join("yap_deinterleave_test");
+        let mut sample = Sample::new("ABC1", Path::new("test_files/interleaved"));
+        sample.layout = Layout::Interleaved;
+        sample.read_1 = PathBuf::from("test_files/interleaved/ABC1.fastq.gz");
+        let paired = deinterleave(&sample, &dir);
+        let read_1 = read_headers(&paired.read_1, 10).unwrap();
+        let read_2 = read_headers(&paired.read_2, 10).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Layout::Paired, paired.layout);
+        assert_eq!(dir.join("ABC1_R1.fastq.gz"), paired.read_1);
+        assert_eq!(vec![sample.read_1.as_path()], paired.get_raw_reads());
+        assert_eq!(4, read_1.len());
+        assert_eq!("@pair_1/1", read_1[0]);
+        assert_eq!("@pair_4/2", read_2[3]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Adapter and index kits.
+//!
+//! A kit file is a tsv without a header. Lines starting with `#` are
+//! comments:
+//!
+//! | Entry        | Values                                        |
+//! | ------------ | --------------------------------------------- |
+//! | `name`       | Kit name. Defaults to the file name.          |
+//! | `i5_adapter` | i5 adapter. `*` marks where the index goes.   |
+//! | `i7_adapter` | i7 adapter. `*` marks where the index goes.   |
+//! | `i5`         | Index name and sequence.                      |
+//! | `i7`         | Index name and sequence.                      |
+//!
+//! Adapters are written 5' to 3' as oligos. Index sequences are written
+//! as read by a forward-strand sequencer: the i5 index is inserted as is,
+//! the i7 index as its reverse complement.
+use std::fs;
+use std::path::Path;
+
+use crate::sheet::tag::{self, IndexRead, Orientation};
+
+// Built-in kits.
+const KITS: [&str; 2] = [
+    include_str!("kits/truseq.tsv"),
+    include_str!("kits/nextera.tsv"),
+];
+
+// Template bases past the index needed to find where the index ends.
+const MIN_TEMPLATE_MATCH: usize = 6;
+
+pub struct Kit {
+    pub name: String,
+    i5_adapter: String,
+    i7_adapter: String,
+    i5_indexes: Vec<(String, String)>,
+    i7_indexes: Vec<(String, String)>,
+}
+
+impl Kit {
+    /// A built-in kit by name, ignoring case, or a kit file.
+    /// Relative kit paths start from the sample sheet dir.
+    pub fn load(name: &str, dir: &Path) -> Result<Self, String> {
+        let builtin = KITS
+            .iter()
+            .map(|content| Self::parse(content, "").expect("INVALID BUILT-IN KIT"))
+            .find(|kit| kit.name.eq_ignore_ascii_case(name));
+        if let Some(kit) = builtin {
+            return Ok(kit);
+        }
+        let path = dir.join(name);
+        if !path.is_file() {
+            return Err(format!(
+                "UNKNOWN KIT {}. USE {} OR A KIT FILE.",
+                name,
+                get_builtin_names().join(", ")
+            ));
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("CAN'T READ KIT FILE {}: {}", path.display(), e))?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        Self::parse(&content, &stem)
+            .map_err(|e| format!("INVALID KIT FILE {}: {}", path.display(), e))
+    }
+
+    fn parse(content: &str, default_name: &str) -> Result<Self, String> {
+        let mut kit = Self {
+            name: default_name.to_string(),
+            i5_adapter: String::new(),
+            i7_adapter: String::new(),
+            i5_indexes: Vec::new(),
+            i7_indexes: Vec::new(),
+        };
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cells: Vec<&str> = line.split('\t').map(|c| c.trim()).collect();
+            match cells.as_slice() {
+                ["name", name] => kit.name = name.to_string(),
+                ["i5_adapter", adapter] => kit.i5_adapter = adapter.to_uppercase(),
+                ["i7_adapter", adapter] => kit.i7_adapter = adapter.to_uppercase(),
+                ["i5", name, seq] => {
+                    tag::check_tag(seq)?;
+                    kit.i5_indexes.push((name.to_string(), seq.to_uppercase()))
+                }
+                ["i7", name, seq] => {
+                    tag::check_tag(seq)?;
+                    kit.i7_indexes.push((name.to_string(), seq.to_uppercase()))
+                }
+                _ => return Err(format!("INVALID LINE: {}", line)),
+            }
+        }
+        if !kit.i5_adapter.contains('*') || !kit.i7_adapter.contains('*') {
+            return Err(String::from("MISSING AN i5_adapter OR i7_adapter WITH *"));
+        }
+        Ok(kit)
+    }
+
+    /// The adapter with `*` where the index goes.
+    pub fn get_adapter(&self, read: IndexRead) -> &str {
+        match read {
+            IndexRead::I5 => &self.i5_adapter,
+            IndexRead::I7 => &self.i7_adapter,
+        }
+    }
+
+    // The bases of a full adapter in place of the `*` of the template.
+    fn find_index(&self, adapter: &str, read: IndexRead) -> Option<String> {
+        let (prefix, suffix) = self.get_adapter(read).split_once('*')?;
+        let rest = adapter.strip_prefix(prefix)?;
+        let min_match = MIN_TEMPLATE_MATCH.min(suffix.len());
+        (1..rest.len())
+            .take_while(|end| rest.len() - end >= min_match)
+            .find(|end| suffix.starts_with(&rest[*end..]))
+            .map(|end| rest[..end].to_string())
+    }
+
+    /// The adapter bases of an index name of the kit or an index
+    /// sequence. Kit indexes are read in the forward orientation.
+    pub fn get_index(
+        &self,
+        index: &str,
+        read: IndexRead,
+        orientation: Orientation,
+    ) -> Result<String, String> {
+        let (indexes, label) = match read {
+            IndexRead::I5 => (&self.i5_indexes, "i5"),
+            IndexRead::I7 => (&self.i7_indexes, "i7"),
+        };
+        let found = indexes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(index));
+        let index_seq = index.to_uppercase();
+        match found {
+            Some((_, seq)) => Ok(tag::get_adapter_index(seq, read, Orientation::Forward)),
+            None if is_index_seq(&index_seq) => {
+                Ok(tag::get_adapter_index(&index_seq, read, orientation))
+            }
+            None => Err(format!(
+                "UNKNOWN {} INDEX {} IN KIT {}",
+                label, index, self.name
+            )),
+        }
+    }
+}
+
+fn is_index_seq(seq: &str) -> bool {
+    !seq.is_empty() && seq.chars().all(|b| matches!(b, 'A' | 'C' | 'G' | 'T'))
+}
+
+/// The index bases of a full adapter that follows the template of
+/// a built-in kit. The adapter may stop a few bases past the index.
+pub fn find_builtin_index(adapter: &str, read: IndexRead) -> Option<String> {
+    let adapter = adapter.to_uppercase();
+    KITS.iter()
+        .map(|content| Kit::parse(content, "").expect("INVALID BUILT-IN KIT"))
+        .find_map(|kit| kit.find_index(&adapter, read))
+}
+
+fn get_builtin_names() -> Vec<String> {
+    KITS.iter()
+        .filter_map(|content| Kit::parse(content, "").ok())
+        .map(|kit| kit.name)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_kit_test() {
+        let kit = Kit::load("truseq", Path::new(".")).unwrap();
+        assert_eq!("TruSeq", kit.name);
+        assert_eq!(
+            "TATAGCCT",
+            kit.get_index("D501", IndexRead::I5, Orientation::Reverse)
+                .unwrap()
+        );
+        assert_eq!(
+            "CGAGTAAT",
+            kit.get_index("d701", IndexRead::I7, Orientation::Forward)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn index_sequence_test() {
+        let kit = Kit::load("Nextera", Path::new(".")).unwrap();
+        assert!(kit.get_adapter(IndexRead::I5).ends_with("*TCGTCGGCAGCGTC"));
+        assert_eq!(
+            "AACCGGTA",
+            kit.get_index("taccggtt", IndexRead::I5, Orientation::Reverse)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn builtin_index_test() {
+        let i5 = "AATGATACGGCGACCACCGAGATCTACACTATAGCCTACACTCTTTCCCTACACGACGCTCTTCCGATCT";
+        let i7 = "CAAGCAGAAGACGGCATACGAGATCGAGTAATGTCTCGTGGGCTCGG";
+
+        assert_eq!(
+            Some(String::from("TATAGCCT")),
+            find_builtin_index(i5, IndexRead::I5)
+        );
+        assert_eq!(
+            Some(String::from("CGAGTAAT")),
+            find_builtin_index(i7, IndexRead::I7)
+        );
+        assert_eq!(None, find_builtin_index("AGATCGGAAGAGC", IndexRead::I5));
+    }
+
+    #[test]
+    fn kit_file_test() {
+        let kit = Kit::load("kits/plate_1.tsv", Path::new("test_files/kit")).unwrap();
+        assert_eq!("plate_1", kit.name);
+        assert_eq!(
+            "GGTTACCA",
+            kit.get_index("i7_01", IndexRead::I7, Orientation::Forward)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_index_test() {
+        let kit = Kit::load("Nextera", Path::new(".")).unwrap();
+
+        assert_eq!(
+            Err(String::from("UNKNOWN i5 INDEX D799 IN KIT Nextera")),
+            kit.get_index("D799", IndexRead::I5, Orientation::Forward)
+        );
+    }
+
+    #[test]
+    fn unknown_kit_test() {
+        let kit = Kit::load("TruSeq3", Path::new("."));
+
+        assert!(matches!(kit, Err(e) if e.starts_with("UNKNOWN KIT")));
+    }
+}
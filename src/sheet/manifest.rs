@@ -0,0 +1,184 @@
+//! Run manifest: one row per sample with its reads, its output,
+//! and its metadata.
+//!
+//! `yap qc` and `yap assembly` write it in their output directory.
+//! `yap assembly auto` reads the qc manifest to keep the metadata.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::sheet::Sample;
+use crate::utils::csv;
+
+pub const MANIFEST_FILE: &str = "yap-manifest.csv";
+
+const COLUMNS: [&str; 5] = ["id", "target_dir", "read_1", "read_2", "output"];
+
+/// Write the manifest to `dir`. `get_output` gives the output of a sample.
+pub fn write_manifest<F>(dir: &Path, samples: &[Sample], get_output: F) -> Result<PathBuf>
+where
+    F: Fn(&Sample) -> PathBuf,
+{
+    let path = dir.join(MANIFEST_FILE);
+    let mut writer = LineWriter::new(File::create(&path)?);
+    let keys = get_metadata_keys(samples);
+    let header: Vec<&str> = COLUMNS
+        .iter()
+        .copied()
+        .chain(keys.iter().map(|k| k.as_str()))
+        .collect();
+    writeln!(writer, "{}", csv::join_fields(&header, ','))?;
+
+    for sample in samples {
+        let (read_1, read_2) = join_reads(sample);
+        let mut row = vec![
+            sample.id.clone(),
+            sample.target_dir.to_string_lossy().to_string(),
+            read_1,
+            read_2,
+            get_output(sample).to_string_lossy().to_string(),
+        ];
+        keys.iter().for_each(|key| row.push(get_value(sample, key)));
+        writeln!(writer, "{}", csv::join_fields(&row, ','))?;
+    }
+    Ok(path)
+}
+
+/// Metadata by target directory from the manifest in `dir`.
+/// Empty if there is no manifest.
+pub fn read_manifest_metadata(dir: &Path) -> HashMap<String, Vec<(String, String)>> {
+    let (header, rows) = read_manifest(dir);
+    rows.iter()
+        .map(|cols| {
+            let values = header
+                .iter()
+                .zip(cols.iter())
+                .skip(COLUMNS.len())
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            (cols[1].to_string(), values)
+        })
+        .collect()
+}
+
+/// Target directory by sample id from the manifest in `dir`.
+/// Empty if there is no manifest.
+pub fn read_manifest_targets(dir: &Path) -> HashMap<String, String> {
+    let (_, rows) = read_manifest(dir);
+    rows.iter()
+        .map(|cols| (cols[0].to_string(), cols[1].to_string()))
+        .collect()
+}
+
+// Header and rows with at least the fixed columns.
+// Skips the rows with an unclosed quote.
+fn read_manifest(dir: &Path) -> (Vec<String>, Vec<Vec<String>>) {
+    let file = match File::open(dir.join(MANIFEST_FILE)) {
+        Ok(file) => file,
+        Err(_) => return (Vec::new(), Vec::new()),
+    };
+    let mut lines = BufReader::new(file).lines().map_while(Result::ok);
+    let header: Vec<String> = match lines.next() {
+        Some(header) => csv::split_fields(&header, ',').unwrap_or_default(),
+        None => return (Vec::new(), Vec::new()),
+    };
+    let rows = lines
+        .filter_map(|line| csv::split_fields(&line, ',').ok())
+        .filter(|cols| cols.len() >= COLUMNS.len())
+        .collect();
+    (header, rows)
+}
+
+// Keys in the order they first appear.
+pub(super) fn get_metadata_keys(samples: &[Sample]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    samples
+        .iter()
+        .flat_map(|s| s.metadata.iter())
+        .for_each(|(key, _)| {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        });
+    keys
+}
+
+pub(super) fn get_value(sample: &Sample, key: &str) -> String {
+    sample
+        .metadata
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default()
+}
+
+// Lanes are listed in one column, separated by semicolons.
+// Read 2 is empty for single-end samples.
+pub(super) fn join_reads(sample: &Sample) -> (String, String) {
+    let lanes: Vec<(&Path, &Path)> = if sample.lanes.is_empty() {
+        vec![(&sample.read_1, &sample.read_2)]
+    } else {
+        sample
+            .lanes
+            .iter()
+            .map(|(r1, r2)| (r1.as_path(), r2.as_path()))
+            .collect()
+    };
+    let join = |reads: Vec<&Path>| {
+        reads
+            .iter()
+            .filter(|r| !r.as_os_str().is_empty())
+            .map(|r| r.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(";")
+    };
+    (
+        join(lanes.iter().map(|(r1, _)| *r1).collect()),
+        join(lanes.iter().map(|(_, r2)| *r2).collect()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manifest_test() {
+        let dir = std::env::temp_dir().join("yap_manifest_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample.read_1 = PathBuf::from("raw/ABC1_R1.fq.gz");
+        sample.read_2 = PathBuf::from("raw/ABC1_R2.fq.gz");
+        sample.target_dir = PathBuf::from("Rattus_ABC1");
+        sample.metadata = vec![
+            (String::from("voucher"), String::from("KU 1")),
+            (
+                String::from("locality"),
+                String::from("Sulawesi, \"Mt. Dako\""),
+            ),
+        ];
+        let other = Sample::new("ABC2", Path::new("raw"));
+
+        let path = write_manifest(&dir, &[sample, other], |s| dir.join(&s.target_dir)).unwrap();
+        let metadata = read_manifest_metadata(&dir);
+        let targets = read_manifest_targets(&dir);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(content.starts_with("id,target_dir,read_1,read_2,output,voucher,locality\n"));
+        assert!(content.contains(",KU 1,\"Sulawesi, \"\"Mt. Dako\"\"\"\n"));
+        assert_eq!(
+            vec![
+                (String::from("voucher"), String::from("KU 1")),
+                (
+                    String::from("locality"),
+                    String::from("Sulawesi, \"Mt. Dako\"")
+                ),
+            ],
+            metadata["Rattus_ABC1"]
+        );
+        assert_eq!("", metadata["ABC2"][0].1);
+        assert_eq!("Rattus_ABC1", targets["ABC1"]);
+    }
+}
@@ -0,0 +1,152 @@
+//! Merge the reads of a sample sequenced on several lanes or runs.
+//!
+//! Gzip files are copied as they are, because concatenated gzip members
+//! are still a valid gzip file. Uncompressed files are compressed on the fly.
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::qc;
+use crate::sheet::{NamingScheme, Sample};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Merge the lanes of a sample into one read 1 and one read 2 file in `dir`.
+/// Single-end samples only have read 1.
+/// Returns a copy of the sample that points to the merged reads.
+/// Every merge is recorded in the log as one block,
+/// so the merges of parallel jobs don't interleave.
+pub fn merge_lanes(sample: &Sample, dir: &Path, scheme: &NamingScheme) -> Sample {
+    fs::create_dir_all(dir).expect("CAN'T CREATE MERGED READ DIR");
+    let read_1: Vec<&Path> = sample.lanes.iter().map(|(r1, _)| r1.as_path()).collect();
+    let read_2: Vec<&Path> = sample.lanes.iter().map(|(_, r2)| r2.as_path()).collect();
+    let merged = get_merged_sample(sample, dir, scheme);
+
+    merge_files(&read_1, &merged.read_1).expect("FAILED MERGING READ 1");
+    if sample.has_read_2() {
+        merge_files(&read_2, &merged.read_2).expect("FAILED MERGING READ 2");
+    }
+    let _lock = qc::lock_log();
+    log::info!("{:18}: {} lanes", "Merging reads", sample.lanes.len());
+    log_merge("Merged R1", &read_1, &merged.read_1);
+    if sample.has_read_2() {
+        log_merge("Merged R2", &read_2, &merged.read_2);
+    }
+    log::info!("");
+
+    merged
+}
+
+/// The sample as returned by `merge_lanes`, without merging the files.
+pub fn get_merged_sample(sample: &Sample, dir: &Path, scheme: &NamingScheme) -> Sample {
+    let mut merged = sample.clone();
+    merged.read_1 = dir.join(get_merged_name(&sample.read_1, scheme));
+    if sample.has_read_2() {
+        merged.read_2 = dir.join(get_merged_name(&sample.read_2, scheme));
+    }
+    merged.dir = dir.to_path_buf();
+    merged
+}
+
+fn merge_files(inputs: &[&Path], output: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    for input in inputs {
+        let mut reader = BufReader::new(File::open(input)?);
+        if is_gzip(input)? {
+            io::copy(&mut reader, &mut writer)?;
+        } else {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+        }
+    }
+    writer.flush()
+}
+
+/// Check the magic bytes instead of trusting the extension.
+pub fn is_gzip(input: &Path) -> Result<bool> {
+    let mut magic = [0u8; 2];
+    let mut file = File::open(input)?;
+    match file.read_exact(&mut magic) {
+        Ok(_) => Ok(magic == GZIP_MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Drop the lane from the file name of the first lane,
+// so the merged file still follows the naming scheme.
+fn get_merged_name(read: &Path, scheme: &NamingScheme) -> PathBuf {
+    let fname = read
+        .file_name()
+        .expect("Failed parsing filename")
+        .to_string_lossy();
+    let mut merged = match scheme.parse(&fname).and_then(|name| name.lane) {
+        Some(lane) => fname.replacen(&format!("_{}", lane), "", 1),
+        None => fname.to_string(),
+    };
+    if !merged.ends_with(".gz") {
+        merged.push_str(".gz");
+    }
+    PathBuf::from(merged)
+}
+
+fn log_merge(label: &str, inputs: &[&Path], output: &Path) {
+    log::info!("{:18}: {}", label, output.display());
+    inputs.iter().for_each(|input| {
+        log::info!("{:18}  <- {}", "", input.display());
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+    use crate::sheet::ReadFinder;
+
+    fn read_gzip(path: &Path) -> String {
+        let mut decoder = MultiGzDecoder::new(File::open(path).unwrap());
+        let mut content = String::new();
+        decoder.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn merged_name_test() {
+        let scheme = NamingScheme::Auto;
+        let read = Path::new("lanes/Apodemus_S3_L002_R1_001.fastq.gz");
+        assert_eq!(
+            PathBuf::from("Apodemus_S3_R1_001.fastq.gz"),
+            get_merged_name(read, &scheme)
+        );
+        let read = Path::new("run_2/sample_ABC1_R2.fq");
+        assert_eq!(
+            PathBuf::from("sample_ABC1_R2.fq.gz"),
+            get_merged_name(read, &scheme)
+        );
+    }
+
+    #[test]
+    fn merge_lanes_test() {
+        let dir = Path::new("test_files/lanes");
+        let reads: Vec<PathBuf> = ReadFinder::new(dir, "Apodemus").glob_fastq();
+        let mut sample = Sample::new("Apodemus", dir);
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+        let tmp = std::env::temp_dir().join("yap_merge_lanes_test");
+
+        let merged = merge_lanes(&sample, &tmp, &NamingScheme::Auto);
+        let read_1 = read_gzip(&merged.read_1);
+        let read_2 = read_gzip(&merged.read_2);
+        fs::remove_dir_all(&tmp).unwrap();
+
+        assert_eq!(tmp.join("Apodemus_S3_R1_001.fastq.gz"), merged.read_1);
+        assert!(read_1.starts_with("@lane_1/1"));
+        assert!(read_1.contains("@lane_2/1"));
+        assert_eq!(8, read_1.lines().count());
+        assert_eq!(8, read_2.lines().count());
+    }
+}
@@ -0,0 +1,645 @@
+//! Sample sheet shared by the qc and assembly subcommands.
+//!
+//! A sample sheet describes every sample in a project: its id,
+//! where its reads are, the adapters used to trim them,
+//! the output name, and any extra metadata.
+mod collision;
+mod finder;
+mod interleave;
+mod kit;
+mod manifest;
+mod merger;
+mod naming;
+mod parser;
+mod schema;
+mod tag;
+mod validator;
+
+use std::path::{Path, PathBuf};
+
+use crate::sheet::tag::Orientation;
+
+pub use crate::sheet::collision::{find_index_collisions, MIN_INDEX_DISTANCE};
+pub use crate::sheet::finder::{is_fastq, ReadFinder};
+pub use crate::sheet::interleave::{
+    deinterleave, get_deinterleaved_sample, get_mate_name, is_interleaved,
+};
+pub use crate::sheet::manifest::{read_manifest_metadata, read_manifest_targets, write_manifest};
+pub use crate::sheet::merger::{get_merged_sample, is_gzip, merge_lanes};
+pub use crate::sheet::naming::{remove_lane, NamingScheme, ReadType};
+pub use crate::sheet::schema::write_sample_sheet;
+pub use crate::sheet::validator::Validator;
+
+/// Parse a sample sheet (.conf or .csv) into samples
+/// with their reads resolved.
+pub fn parse_sample_sheet(input: &Path, is_rename: bool, scheme: &NamingScheme) -> Vec<Sample> {
+    let samples = parser::parse_input(input, is_rename, scheme).unwrap_or_else(|e| panic!("{}", e));
+    println!("Total samples: {}", samples.len());
+    samples
+}
+
+// Parent directory, the name before the read token, and the name after it.
+type LaneKey = (PathBuf, String, String);
+
+/// Library layout of a sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    #[default]
+    Paired,
+    Single,
+    /// Read pairs one after another in a single file.
+    Interleaved,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "paired" | "pe" => Some(Self::Paired),
+            "single" | "se" => Some(Self::Single),
+            "interleaved" => Some(Self::Interleaved),
+            _ => None,
+        }
+    }
+
+    // Used in error messages.
+    fn label(&self) -> &str {
+        match self {
+            Self::Paired => "PAIRED-END",
+            Self::Single => "SINGLE-END",
+            Self::Interleaved => "INTERLEAVED",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Paired => "paired",
+            Self::Single => "single",
+            Self::Interleaved => "interleaved",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Sample {
+    pub id: String,
+    /// Directory containing the sample reads.
+    pub dir: PathBuf,
+    pub layout: Layout,
+    /// The interleaved file of interleaved samples.
+    pub read_1: PathBuf,
+    /// Empty for single-end and interleaved samples.
+    pub read_2: PathBuf,
+    /// Read pairs of a sample sequenced on several lanes or runs.
+    /// Empty when the sample has a single read pair.
+    /// Read 2 is empty for single-end and interleaved samples.
+    pub lanes: Vec<(PathBuf, PathBuf)>,
+    pub singleton: Option<PathBuf>,
+    pub adapter_i5: Option<String>,
+    pub adapter_i7: Option<String>,
+    /// How the index tags were read.
+    pub orientation: Orientation,
+    /// Index bases inserted into the adapters,
+    /// checked against the read headers.
+    pub index_i5: Option<String>,
+    pub index_i7: Option<String>,
+    pub auto_idx: bool,
+    pub output_name: Option<String>,
+    /// Options for fastp and SPAdes, merged with `--opts`.
+    pub fastp_opts: Option<String>,
+    pub spades_opts: Option<String>,
+    /// Output directory name for the sample.
+    pub target_dir: PathBuf,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Sample {
+    pub fn new(id: &str, dir: &Path) -> Self {
+        Self {
+            id: String::from(id),
+            dir: dir.to_path_buf(),
+            layout: Layout::Paired,
+            read_1: PathBuf::new(),
+            read_2: PathBuf::new(),
+            lanes: Vec::new(),
+            singleton: None,
+            adapter_i5: None,
+            adapter_i7: None,
+            orientation: Orientation::Forward,
+            index_i5: None,
+            index_i7: None,
+            auto_idx: false,
+            output_name: None,
+            fastp_opts: None,
+            spades_opts: None,
+            target_dir: PathBuf::from(id),
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Find the sample reads in the sample directory.
+    /// If `whole_dir` is true and no file matches the sample id,
+    /// all fastq files in the directory are treated as the sample reads.
+    pub fn find_reads(&mut self, whole_dir: bool, scheme: &NamingScheme) -> Result<(), String> {
+        let finder = ReadFinder::new(&self.dir, &self.id);
+        let reads = finder.find(whole_dir, scheme);
+        self.check_reads(&reads)?;
+        self.match_reads(&reads, scheme)?;
+        self.check_missing_reads()
+    }
+
+    /// Use the given read files as the sample reads.
+    pub fn set_reads(&mut self, reads: &[PathBuf], scheme: &NamingScheme) -> Result<(), String> {
+        if let Some(parent) = reads[0].parent() {
+            self.dir = parent.to_path_buf();
+        }
+        self.match_reads(reads, scheme)?;
+        self.check_missing_reads()
+    }
+
+    /// Point the sample to its clean reads produced by `yap qc`.
+    pub fn find_clean_reads(
+        &mut self,
+        clean_dir: &Path,
+        scheme: &NamingScheme,
+    ) -> Result<(), String> {
+        self.dir = clean_dir.join(&self.target_dir).join("trimmed_reads");
+        self.read_1 = PathBuf::new();
+        self.read_2 = PathBuf::new();
+        self.lanes.clear();
+        self.singleton = None;
+        self.find_reads(true, scheme)
+    }
+
+    /// Sort the reads into read 1, read 2, and a singleton.
+    /// Read pairs from several lanes or runs are kept in `lanes`
+    /// and the first pair is used as read 1 and read 2.
+    pub fn match_reads(&mut self, reads: &[PathBuf], scheme: &NamingScheme) -> Result<(), String> {
+        let mut read_1 = Vec::new();
+        let mut read_2 = Vec::new();
+        for read in reads {
+            let fname = read
+                .file_name()
+                .ok_or_else(|| format!("INVALID READ PATH {:?} FOR {}", read, self.id))?
+                .to_string_lossy();
+            match scheme.parse(&fname) {
+                Some(name) => {
+                    let parent = read.parent().unwrap_or(Path::new("")).to_path_buf();
+                    let key = (parent, name.prefix, name.suffix);
+                    match name.read {
+                        ReadType::Read1 => read_1.push((key, read.to_path_buf())),
+                        ReadType::Read2 => read_2.push((key, read.to_path_buf())),
+                    }
+                }
+                None => {
+                    if let Some(singleton) = &self.singleton {
+                        self.check_duplicate_read(singleton, read)?;
+                    }
+                    self.singleton = Some(read.to_path_buf());
+                }
+            }
+        }
+        self.pair_lanes(read_1, read_2)?;
+        if self.layout == Layout::Paired && self.is_interleaved_file() {
+            self.layout = Layout::Interleaved;
+        }
+        if !self.has_read_2() {
+            self.use_single_read()?;
+        }
+        Ok(())
+    }
+
+    /// Reads without a read 2 are single-end, e.g. the clean reads
+    /// of a single-end sample found without a sample sheet.
+    /// Interleaved files are already detected by `match_reads`.
+    pub fn infer_layout(&mut self) -> Result<(), String> {
+        let has_read = !self.read_1.as_os_str().is_empty() || self.singleton.is_some();
+        if self.read_2.as_os_str().is_empty() && has_read && self.layout == Layout::Paired {
+            self.layout = Layout::Single;
+            self.use_single_read()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_single_end(&self) -> bool {
+        self.layout == Layout::Single
+    }
+
+    pub fn is_interleaved(&self) -> bool {
+        self.layout == Layout::Interleaved
+    }
+
+    /// True if read 2 is in its own file.
+    pub fn has_read_2(&self) -> bool {
+        self.layout == Layout::Paired
+    }
+
+    // A single file without a read 2 is checked for read pairs.
+    fn is_interleaved_file(&self) -> bool {
+        if !self.read_2.as_os_str().is_empty() || !self.lanes.is_empty() {
+            return false;
+        }
+        match (self.read_1.as_os_str().is_empty(), &self.singleton) {
+            (false, None) => is_interleaved(&self.read_1),
+            (true, Some(singleton)) => is_interleaved(singleton),
+            _ => false,
+        }
+    }
+
+    /// True if the reads must be merged before processing.
+    pub fn is_multi_lane(&self) -> bool {
+        self.lanes.len() > 1
+    }
+
+    /// The input read files as found, before any lane merging.
+    pub fn get_raw_reads(&self) -> Vec<&Path> {
+        let reads: Vec<&Path> = if self.lanes.is_empty() {
+            vec![&self.read_1, &self.read_2]
+        } else {
+            self.lanes
+                .iter()
+                .flat_map(|(r1, r2)| [r1.as_path(), r2.as_path()])
+                .collect()
+        };
+        reads
+            .into_iter()
+            .filter(|r| !r.as_os_str().is_empty())
+            .collect()
+    }
+
+    pub fn is_missing_reads(&self) -> bool {
+        self.read_1.as_os_str().is_empty()
+            || (self.has_read_2() && self.read_2.as_os_str().is_empty())
+    }
+
+    pub fn is_dual_idx(&self) -> bool {
+        self.adapter_i7.is_some()
+    }
+
+    /// The read 1 and read 2 adapters as they show up in the reads.
+    /// The i5 and i7 adapters are oligos, so read 1 runs into the reverse
+    /// complement of the i7 adapter and read 2 into that of the i5 adapter.
+    /// A single adapter is used for both reads.
+    pub fn get_read_adapters(&self) -> (Option<String>, Option<String>) {
+        let i5 = self.adapter_i5.as_deref().map(tag::reverse_complement);
+        match self.adapter_i7.as_deref().map(tag::reverse_complement) {
+            Some(i7) => (Some(i7), i5),
+            None => (i5.clone(), i5),
+        }
+    }
+
+    /// Sets the adapters from the read 1 and read 2 adapters,
+    /// the reverse of `get_read_adapters`.
+    pub fn set_read_adapters(&mut self, adapter_r1: &str, adapter_r2: Option<&str>) {
+        let r1 = tag::reverse_complement(adapter_r1);
+        match adapter_r2 {
+            Some(r2) => {
+                self.adapter_i5 = Some(tag::reverse_complement(r2));
+                self.adapter_i7 = Some(r1);
+            }
+            None => {
+                self.adapter_i5 = Some(r1);
+                self.adapter_i7 = None;
+            }
+        }
+    }
+
+    fn check_reads(&self, reads: &[PathBuf]) -> Result<(), String> {
+        if reads.is_empty() {
+            return Err(format!(
+                "CANNOT FIND FILE {}. \
+                USE THE --id FLAG IF YOU USE THE FILE ID.",
+                self.id
+            ));
+        }
+        Ok(())
+    }
+
+    // A single read 1 and read 2 always make a pair.
+    // More than that, each read 1 needs a read 2 with the same name
+    // apart from the read token, and the pairs must be lanes or runs
+    // of one library.
+    fn pair_lanes(
+        &mut self,
+        mut read_1: Vec<(LaneKey, PathBuf)>,
+        mut read_2: Vec<(LaneKey, PathBuf)>,
+    ) -> Result<(), String> {
+        if read_1.len() <= 1 && read_2.len() <= 1 {
+            if let Some((_, read)) = read_1.pop() {
+                self.read_1 = read;
+            }
+            if let Some((_, read)) = read_2.pop() {
+                self.read_2 = read;
+            }
+            return Ok(());
+        }
+
+        self.check_libraries(read_1.iter().chain(read_2.iter()).map(|(key, _)| key))?;
+        if !self.has_read_2() && read_2.is_empty() {
+            read_1.sort();
+            self.lanes = read_1
+                .into_iter()
+                .map(|(_, r1)| (r1, PathBuf::new()))
+                .collect();
+            self.read_1 = self.lanes[0].0.clone();
+            return Ok(());
+        }
+
+        if read_1.len() != read_2.len() {
+            return Err(format!(
+                "REQUIRED TWO READS FOR {}. FOUND {} READ 1 AND {} READ 2 FILES",
+                self.id,
+                read_1.len(),
+                read_2.len()
+            ));
+        }
+
+        read_1.sort();
+        self.lanes = read_1
+            .into_iter()
+            .map(
+                |(key, r1)| match read_2.iter().position(|(k, _)| *k == key) {
+                    Some(idx) => Ok((r1, read_2.remove(idx).1)),
+                    None => Err(format!(
+                        "CANNOT FIND READ 2 FOR {:?} IN SAMPLE {}",
+                        r1, self.id
+                    )),
+                },
+            )
+            .collect::<Result<_, String>>()?;
+        self.read_1 = self.lanes[0].0.clone();
+        self.read_2 = self.lanes[0].1.clone();
+        Ok(())
+    }
+
+    // Reads in one directory are lanes when their names differ only
+    // in the lane token. Reads in other directories are other runs.
+    fn check_libraries<'k>(&self, keys: impl Iterator<Item = &'k LaneKey>) -> Result<(), String> {
+        let mut libraries: Vec<(&Path, &str)> = Vec::new();
+        for (dir, prefix, _) in keys {
+            let library = remove_lane(prefix);
+            match libraries.iter().find(|(other_dir, _)| other_dir == dir) {
+                Some((_, other)) if *other != library => {
+                    return Err(format!(
+                        "FOUND READS OF DIFFERENT LIBRARIES FOR {} IN {:?}: {} AND {}. \
+                        ONLY READS THAT DIFFER IN THE LANE TOKEN ARE MERGED.",
+                        self.id, dir, other, library
+                    ))
+                }
+                Some(_) => (),
+                None => libraries.push((dir, library)),
+            }
+        }
+        Ok(())
+    }
+
+    // Single-end and interleaved reads without a read token,
+    // e.g. SRA `SRR123.fastq.gz`, are found as a singleton.
+    fn use_single_read(&mut self) -> Result<(), String> {
+        if !self.read_2.as_os_str().is_empty() {
+            return Err(format!(
+                "FOUND READ 2 FOR {} SAMPLE {}: {:?}",
+                self.layout.label(),
+                self.id,
+                self.read_2
+            ));
+        }
+        if let Some(singleton) = self.singleton.take() {
+            if !self.read_1.as_os_str().is_empty() {
+                return Err(format!(
+                    "FOUND MORE THAN ONE READ FILE FOR {} SAMPLE {}: {:?} AND {:?}",
+                    self.layout.label(),
+                    self.id,
+                    self.read_1,
+                    singleton
+                ));
+            }
+            self.read_1 = singleton;
+        }
+        Ok(())
+    }
+
+    fn check_duplicate_read(&self, current: &Path, read: &Path) -> Result<(), String> {
+        if !current.as_os_str().is_empty() {
+            return Err(format!(
+                "REQUIRED TWO READS FOR {}. FOUND: {:?} AND {:?}",
+                self.id, current, read
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_missing_reads(&self) -> Result<(), String> {
+        if self.is_missing_reads() && !self.has_read_2() {
+            return Err(format!(
+                "CANNOT FIND THE READS FOR {} SAMPLE {}",
+                self.layout.label(),
+                self.id
+            ));
+        }
+        if self.is_missing_reads() {
+            return Err(format!(
+                "CANNOT FIND BOTH READS FOR {}. \
+                Read 1: {:?} \
+                Read 2: {:?}",
+                self.id, self.read_1, self.read_2
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_reads_error_test() {
+        let input = PathBuf::from("./ABC1234_reads.fastq.gz");
+        let mut sample = Sample::new("ABC1234", Path::new("."));
+        sample.match_reads(&[input], &NamingScheme::Auto).unwrap();
+        assert!(sample.check_missing_reads().is_err());
+    }
+
+    #[test]
+    fn check_reads_error_msg_test() {
+        let sample = Sample::new("ABC1234", Path::new("."));
+        assert_eq!(
+            Err(String::from(
+                "CANNOT FIND FILE ABC1234. USE THE --id FLAG IF YOU USE THE FILE ID."
+            )),
+            sample.check_reads(&[])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_multireads_panic_test() {
+        let input_1 = PathBuf::from("./some_seq_read1.fastq.gz");
+        let input_2 = PathBuf::from("./some_seq_read1_l1.fastq.gz");
+        let input_3 = PathBuf::from("./some_seq_read2.fastq.gz");
+        let mut sample = Sample::new("some_seq", Path::new("."));
+        sample
+            .match_reads(&[input_1, input_2, input_3], &NamingScheme::Auto)
+            .unwrap();
+    }
+
+    #[test]
+    fn match_reads_lanes_test() {
+        let dir = Path::new("test_files/lanes");
+        let mut sample = Sample::new("Apodemus", dir);
+        sample.find_reads(false, &NamingScheme::Auto).unwrap();
+
+        assert!(sample.is_multi_lane());
+        assert_eq!(dir.join("Apodemus_S3_L001_R1_001.fastq.gz"), sample.read_1);
+        assert_eq!(dir.join("Apodemus_S3_L002_R2_001.fastq"), sample.lanes[1].1);
+        assert_eq!(4, sample.get_raw_reads().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "CANNOT FIND READ 2")]
+    fn match_reads_lanes_panic_test() {
+        let reads = [
+            PathBuf::from("run_1/sample_ABC1_R1.fq.gz"),
+            PathBuf::from("run_1/sample_ABC1_R2.fq.gz"),
+            PathBuf::from("run_2/sample_ABC1_R1.fq.gz"),
+            PathBuf::from("run_3/sample_ABC1_R2.fq.gz"),
+        ];
+        let mut sample = Sample::new("sample_ABC1", Path::new("."));
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "FOUND READS OF DIFFERENT LIBRARIES")]
+    fn match_reads_other_library_panic_test() {
+        let reads = [
+            PathBuf::from("raw/Rattus_R1.fq.gz"),
+            PathBuf::from("raw/Rattus_R2.fq.gz"),
+            PathBuf::from("raw/Rattus_exulans_R1.fq.gz"),
+            PathBuf::from("raw/Rattus_exulans_R2.fq.gz"),
+        ];
+        let mut sample = Sample::new("Rattus", Path::new("raw"));
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+    }
+
+    #[test]
+    fn match_reads_runs_test() {
+        let reads = [
+            PathBuf::from("run_1/Apodemus_S1_L001_R1_001.fq.gz"),
+            PathBuf::from("run_1/Apodemus_S1_L001_R2_001.fq.gz"),
+            PathBuf::from("run_2/Apodemus_S4_L001_R1_001.fq.gz"),
+            PathBuf::from("run_2/Apodemus_S4_L001_R2_001.fq.gz"),
+        ];
+        let mut sample = Sample::new("Apodemus", Path::new("."));
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(2, sample.lanes.len());
+    }
+
+    #[test]
+    fn match_reads_id_with_number_test() {
+        let r1 = PathBuf::from("test_files/qc/test_1_R1.fastq");
+        let r2 = PathBuf::from("test_files/qc/test_1_R2.fastq");
+        let mut sample = Sample::new("test_1", Path::new("test_files/qc"));
+        sample
+            .match_reads(&[r1.clone(), r2.clone()], &NamingScheme::Auto)
+            .unwrap();
+
+        assert_eq!(r1, sample.read_1);
+        assert_eq!(r2, sample.read_2);
+        assert!(sample.singleton.is_none());
+    }
+
+    #[test]
+    fn match_single_end_reads_test() {
+        let dir = Path::new("test_files/single");
+        let mut sample = Sample::new("SRR1234", dir);
+        sample.layout = Layout::Single;
+        sample.find_reads(false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(dir.join("SRR1234.fastq.gz"), sample.read_1);
+        assert!(sample.singleton.is_none());
+        assert_eq!(1, sample.get_raw_reads().len());
+    }
+
+    #[test]
+    fn match_single_end_lanes_test() {
+        let reads = [
+            PathBuf::from("raw/ABC1_S1_L002_R1_001.fastq.gz"),
+            PathBuf::from("raw/ABC1_S1_L001_R1_001.fastq.gz"),
+        ];
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample.layout = Layout::Single;
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+
+        assert!(sample.is_multi_lane());
+        assert_eq!(reads[1], sample.read_1);
+        assert_eq!(2, sample.get_raw_reads().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "FOUND READ 2 FOR SINGLE-END SAMPLE")]
+    fn single_end_read_2_panic_test() {
+        let reads = [
+            PathBuf::from("raw/ABC1_R1.fastq.gz"),
+            PathBuf::from("raw/ABC1_R2.fastq.gz"),
+        ];
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample.layout = Layout::Single;
+        sample.match_reads(&reads, &NamingScheme::Auto).unwrap();
+    }
+
+    #[test]
+    fn detect_interleaved_test() {
+        let dir = Path::new("test_files/interleaved");
+        let mut sample = Sample::new("ABC1", dir);
+        sample.find_reads(false, &NamingScheme::Auto).unwrap();
+
+        assert!(sample.is_interleaved());
+        assert_eq!(dir.join("ABC1.fastq.gz"), sample.read_1);
+        assert!(sample.singleton.is_none());
+    }
+
+    #[test]
+    fn infer_layout_test() {
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample
+            .match_reads(&[PathBuf::from("raw/ABC1_R1.fq.gz")], &NamingScheme::Auto)
+            .unwrap();
+        sample.infer_layout().unwrap();
+
+        assert!(sample.is_single_end());
+        assert!(!sample.is_missing_reads());
+    }
+
+    #[test]
+    fn find_reads_whole_dir_test() {
+        let dir = Path::new("test_files/assembly/trimmed_test");
+        let mut sample = Sample::new("ABC123", dir);
+        sample.find_reads(true, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(dir.join("some_seq_ABC123_R1.fq.gz"), sample.read_1);
+        assert_eq!(dir.join("some_seq_ABC123_R2.fq.gz"), sample.read_2);
+    }
+
+    #[test]
+    fn read_adapters_test() {
+        let mut sample = Sample::new("ABC1", Path::new("raw"));
+        sample.set_read_adapters("AGATCGGAAGAGCACACGTC", Some("AGATCGGAAGAGCGTCGTGT"));
+
+        assert_eq!(Some("GACGTGTGCTCTTCCGATCT"), sample.adapter_i7.as_deref());
+        assert_eq!(Some("ACACGACGCTCTTCCGATCT"), sample.adapter_i5.as_deref());
+        assert_eq!(
+            (
+                Some(String::from("AGATCGGAAGAGCACACGTC")),
+                Some(String::from("AGATCGGAAGAGCGTCGTGT"))
+            ),
+            sample.get_read_adapters()
+        );
+
+        sample.set_read_adapters("CTGTCTCTTATACACATCT", None);
+        let (adapter_r1, adapter_r2) = sample.get_read_adapters();
+        assert_eq!(Some("CTGTCTCTTATACACATCT"), adapter_r1.as_deref());
+        assert_eq!(adapter_r1, adapter_r2);
+        assert!(sample.adapter_i7.is_none());
+    }
+}
@@ -0,0 +1,217 @@
+//! Read file naming schemes.
+//!
+//! A scheme tells which read (1 or 2) a file is and, when the scheme knows it,
+//! which sample the file belongs to. Custom schemes are regular expressions
+//! with a `read` group and an optional `sample` group.
+use regex::{Captures, Regex};
+
+/// Illumina bcl2fastq and BCL Convert: `Sample_S1_L001_R1_001.fastq.gz`.
+const ILLUMINA_REGEX: &str =
+    r"^(?P<sample>.+?)_S\d+(?:_(?P<lane>L\d{3}))?_(?P<read>R[12])_\d{3}\.(?:fastq|fq)(?:\.gz)?$";
+
+/// SRA downloads: `SRR1234567_1.fastq.gz`.
+const SRA_REGEX: &str = r"^(?P<sample>[SED]RR\d+)_(?P<read>[12])\.(?:fastq|fq)(?:\.gz)?$";
+
+/// Yap style: the last read token in the file name,
+/// e.g. `genus_species_voucher_R1.fq.gz` or `genus_species_voucher_read1.fastq`.
+const YAP_REGEX: &str =
+    r"(?i)^(?:.*)(?:_|-)(?P<read>R1|R2|1|2|read1|read2|read_1|read_2|read-1|read-2)[_.-].*$";
+
+/// Lane token at the end of the name before the read token.
+const LANE_REGEX: &str = r"[_.-]L\d{3}$";
+
+#[derive(Debug, PartialEq)]
+pub enum ReadType {
+    Read1,
+    Read2,
+}
+
+/// A read file name split around its read token.
+/// Read 1 and read 2 of the same library share the prefix and suffix.
+pub struct ReadName {
+    pub read: ReadType,
+    /// Sample name when the scheme defines it.
+    pub sample: Option<String>,
+    /// Sequencing lane when the scheme defines it.
+    pub lane: Option<String>,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+#[derive(Default)]
+pub enum NamingScheme {
+    /// Try Illumina, SRA, and then yap style.
+    #[default]
+    Auto,
+    Illumina,
+    Sra,
+    Yap,
+    Custom(Regex),
+}
+
+impl NamingScheme {
+    pub fn from_arg(arg: &str) -> Self {
+        match arg.to_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "illumina" => Self::Illumina,
+            "sra" => Self::Sra,
+            "yap" => Self::Yap,
+            _ => Self::from_regex(arg),
+        }
+    }
+
+    fn from_regex(pattern: &str) -> Self {
+        let re = match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(e) => panic!("INVALID NAMING SCHEME {}: {}", pattern, e),
+        };
+        if !re.capture_names().any(|n| n == Some("read")) {
+            panic!(
+                "INVALID NAMING SCHEME {}. \
+                THE PATTERN MUST HAVE A (?P<read>...) GROUP.",
+                pattern
+            );
+        }
+        Self::Custom(re)
+    }
+
+    pub fn parse(&self, fname: &str) -> Option<ReadName> {
+        lazy_static! {
+            static ref ILLUMINA: Regex = Regex::new(ILLUMINA_REGEX).unwrap();
+            static ref SRA: Regex = Regex::new(SRA_REGEX).unwrap();
+            static ref YAP: Regex = Regex::new(YAP_REGEX).unwrap();
+        }
+        match self {
+            Self::Auto => self
+                .capture(&ILLUMINA, fname, true)
+                .or_else(|| self.capture(&SRA, fname, true))
+                .or_else(|| self.capture(&YAP, fname, false)),
+            Self::Illumina => self.capture(&ILLUMINA, fname, true),
+            Self::Sra => self.capture(&SRA, fname, true),
+            Self::Yap => self.capture(&YAP, fname, false),
+            Self::Custom(re) => self.capture(re, fname, true),
+        }
+    }
+
+    // Yap style file names do not mark where the sample name ends.
+    fn capture(&self, re: &Regex, fname: &str, has_sample: bool) -> Option<ReadName> {
+        let caps = re.captures(fname)?;
+        let token = caps.name("read")?;
+        let read = match token.as_str().chars().last() {
+            Some('1') => ReadType::Read1,
+            Some('2') => ReadType::Read2,
+            _ => return None,
+        };
+        let prefix = fname[..token.start()].trim_end_matches(['_', '-', '.']);
+        Some(ReadName {
+            read,
+            sample: self.get_group(&caps, "sample").filter(|_| has_sample),
+            lane: self.get_group(&caps, "lane"),
+            prefix: prefix.to_string(),
+            suffix: fname[token.end()..].to_string(),
+        })
+    }
+
+    fn get_group(&self, caps: &Captures, name: &str) -> Option<String> {
+        caps.name(name).map(|m| m.as_str().to_string())
+    }
+}
+
+/// The name before the read token without its lane token,
+/// e.g. `Sample_S1` for `Sample_S1_L001`.
+pub fn remove_lane(prefix: &str) -> &str {
+    lazy_static! {
+        static ref LANE: Regex = Regex::new(LANE_REGEX).unwrap();
+    }
+    match LANE.find(prefix) {
+        Some(lane) => &prefix[..lane.start()],
+        None => prefix,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_read(scheme: &NamingScheme, fname: &str) -> Option<ReadType> {
+        scheme.parse(fname).map(|name| name.read)
+    }
+
+    #[test]
+    fn illumina_test() {
+        let scheme = NamingScheme::Auto;
+        let name = scheme.parse("Sample_S12_L001_R2_001.fastq.gz").unwrap();
+
+        assert_eq!(ReadType::Read2, name.read);
+        assert_eq!(Some(String::from("Sample")), name.sample);
+        assert_eq!(Some(String::from("L001")), name.lane);
+        assert_eq!("Sample_S12_L001", name.prefix);
+        assert_eq!("_001.fastq.gz", name.suffix);
+    }
+
+    #[test]
+    fn illumina_no_lane_test() {
+        let scheme = NamingScheme::Illumina;
+        let name = scheme
+            .parse("some_animals_XYZ1_S3_R1_001.fastq.gz")
+            .unwrap();
+
+        assert_eq!(ReadType::Read1, name.read);
+        assert_eq!(Some(String::from("some_animals_XYZ1")), name.sample);
+        assert!(name.lane.is_none());
+        assert_eq!("some_animals_XYZ1_S3", name.prefix);
+    }
+
+    #[test]
+    fn sra_test() {
+        let scheme = NamingScheme::Auto;
+        let name = scheme.parse("SRR1234567_2.fastq.gz").unwrap();
+
+        assert_eq!(ReadType::Read2, name.read);
+        assert_eq!(Some(String::from("SRR1234567")), name.sample);
+        assert_eq!("SRR1234567", name.prefix);
+    }
+
+    #[test]
+    fn yap_test() {
+        let scheme = NamingScheme::Auto;
+        assert_eq!(Some(ReadType::Read1), get_read(&scheme, "_R1.fastq.gz"));
+        assert_eq!(
+            Some(ReadType::Read1),
+            get_read(&scheme, "sample_buno_clean_read1.fastq.gz")
+        );
+        assert_eq!(Some(ReadType::Read2), get_read(&scheme, "_R2_001.fastq.gz"));
+        assert_eq!(Some(ReadType::Read2), get_read(&scheme, "_R2.fastq.gz"));
+        assert_eq!(
+            Some(ReadType::Read1),
+            get_read(&scheme, "_L002_R1.fastq.gz")
+        );
+        assert_eq!(Some(ReadType::Read2), get_read(&scheme, "test_1_R2.fastq"));
+        assert!(scheme.parse("sample_singleton.fastq.gz").is_none());
+        let name = scheme.parse("genus_species_ABC1_R1.fq.gz").unwrap();
+        assert!(name.sample.is_none());
+        assert_eq!("genus_species_ABC1", name.prefix);
+    }
+
+    #[test]
+    fn scheme_only_test() {
+        let scheme = NamingScheme::Sra;
+        assert!(scheme.parse("genus_species_ABC1_R1.fq.gz").is_none());
+    }
+
+    #[test]
+    fn custom_test() {
+        let scheme = NamingScheme::from_arg(r"^(?P<sample>\w+)\.(?P<read>[12])\.fq\.gz$");
+        let name = scheme.parse("ABC123.2.fq.gz").unwrap();
+
+        assert_eq!(ReadType::Read2, name.read);
+        assert_eq!(Some(String::from("ABC123")), name.sample);
+        assert_eq!("ABC123", name.prefix);
+    }
+
+    #[test]
+    #[should_panic]
+    fn custom_missing_read_group_test() {
+        NamingScheme::from_arg(r"^(?P<sample>\w+)_[12]\.fq$");
+    }
+}
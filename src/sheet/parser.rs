@@ -0,0 +1,577 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::sheet::schema::{Schema, METADATA_PREFIX};
+use crate::sheet::tag::{self, IndexRead, Orientation};
+use crate::sheet::{NamingScheme, Sample};
+
+pub enum SheetFormat {
+    Ini,
+    /// Positional csv with its metadata column indices and names.
+    Csv(Vec<(usize, String)>),
+    /// Csv or tsv with the columns named in the header.
+    Table(Schema),
+}
+
+pub fn parse_input(
+    input: &Path,
+    is_rename: bool,
+    scheme: &NamingScheme,
+) -> Result<Vec<Sample>, String> {
+    let (header, lines) = read_sheet(input);
+    let lines: Vec<String> = lines.into_iter().map(|(_, line)| line).collect();
+    let format = get_format(input, &header, &lines)?;
+    lines
+        .iter()
+        .map(|line| parse_line(line, &format, input, is_rename, scheme))
+        .collect()
+}
+
+/// The header and the sample lines of a sheet with their line numbers.
+/// Skips blank lines.
+pub fn read_sheet(input: &Path) -> (String, Vec<(usize, String)>) {
+    let file = File::open(input).expect("CAN'T OPEN INPUT FILE.");
+    let buff = BufReader::new(file);
+    let mut lines = buff.lines().map_while(Result::ok).enumerate();
+    let header = match lines.next() {
+        Some((_, header)) => header.trim_start_matches('\u{feff}').to_string(),
+        None => String::new(),
+    };
+    let lines = lines
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| (idx + 1, line))
+        .collect();
+    (header, lines)
+}
+
+// Use the file extension when it is known.
+// Otherwise, infer the format from the sample lines.
+pub fn get_format(input: &Path, header: &str, lines: &[String]) -> Result<SheetFormat, String> {
+    let ext = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "conf" | "ini" => Ok(SheetFormat::Ini),
+        "csv" => get_csv_format(header),
+        "tsv" => Ok(SheetFormat::Table(Schema::from_tsv_header(header)?)),
+        _ if header.contains('\t') => Ok(SheetFormat::Table(Schema::from_tsv_header(header)?)),
+        _ if lines.iter().all(|l| l.contains(',')) => get_csv_format(header),
+        _ if lines.iter().all(|l| l.contains(':')) => Ok(SheetFormat::Ini),
+        _ => Err(format!(
+            "{:?} IS INVALID INPUT FILES. \
+            LOOKING FOR .conf, .csv, OR .tsv FORMAT.",
+            input
+        )),
+    }
+}
+
+// Read the columns by name if the header follows the schema.
+// Otherwise, read them by position.
+fn get_csv_format(header: &str) -> Result<SheetFormat, String> {
+    match Schema::from_csv_header(header)? {
+        Some(schema) => Ok(SheetFormat::Table(schema)),
+        None => Ok(SheetFormat::Csv(get_metadata_columns(header))),
+    }
+}
+
+fn get_metadata_columns(header: &str) -> Vec<(usize, String)> {
+    split_line(header, ',')
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, col)| {
+            col.strip_prefix(METADATA_PREFIX)
+                .map(|name| (idx, name.trim().to_string()))
+        })
+        .collect()
+}
+
+pub fn parse_line(
+    line: &str,
+    format: &SheetFormat,
+    input: &Path,
+    is_rename: bool,
+    scheme: &NamingScheme,
+) -> Result<Sample, String> {
+    match format {
+        SheetFormat::Ini => parse_ini_line(line, scheme),
+        SheetFormat::Csv(metadata) => parse_csv_line(line, metadata, input, is_rename, scheme),
+        SheetFormat::Table(schema) => schema.parse_line(line, input, scheme),
+    }
+}
+
+/// Parse an `id:dir` line. The directory contains the reads of the sample.
+/// The reads can also be listed explicitly as `id:read_1,read_2[,singleton]`.
+fn parse_ini_line(line: &str, scheme: &NamingScheme) -> Result<Sample, String> {
+    let cols = split_line(line, ':');
+    check_ini_line(&cols)?;
+    let paths = split_line(&cols[1], ',');
+    let mut sample = Sample::new(&cols[0], Path::new(&paths[0]));
+    if paths.len() > 1 {
+        sample.set_reads(&to_paths(&paths), scheme)?;
+    } else {
+        sample.find_reads(true, scheme)?;
+    }
+    sample.auto_idx = true;
+    Ok(sample)
+}
+
+/// Parse a positional (legacy) csv line:
+/// `id[,output_name][,dir|reads...][,i5[,i7[,insert_i5[,insert_i7]]]]`.
+/// Without the dir or read columns, the reads are in the same directory
+/// as the input file.
+fn parse_csv_line(
+    line: &str,
+    metadata: &[(usize, String)],
+    input: &Path,
+    is_rename: bool,
+    scheme: &NamingScheme,
+) -> Result<Sample, String> {
+    let parent = input.parent().expect("INVALID INPUT PATH");
+    let mut cols = split_line(line, ',');
+    let values = take_metadata(&mut cols, metadata);
+    let mut cols = cols.as_slice();
+    let mut sample = Sample::new(&cols[0], parent);
+    sample.metadata = values;
+    cols = &cols[1..];
+    if is_rename {
+        if cols.is_empty() {
+            return Err(String::from("MISSING AN OUTPUT NAME COLUMN"));
+        }
+        sample.output_name = Some(cols[0].to_string());
+        cols = &cols[1..];
+    }
+
+    let path_count = cols.iter().take_while(|c| is_path_column(c)).count();
+    let has_dir = path_count > 0;
+    let paths = to_paths(&cols[..path_count]);
+    cols = &cols[path_count..];
+    match paths.len() {
+        0 => sample.find_reads(false, scheme)?,
+        1 if paths[0].is_dir() || !paths[0].exists() => {
+            sample.dir = paths[0].clone();
+            sample.find_reads(true, scheme)?;
+        }
+        _ => sample.set_reads(&paths, scheme)?,
+    }
+
+    get_adapters(&mut sample, cols)?;
+    get_target_dir(&mut sample, has_dir, scheme);
+    Ok(sample)
+}
+
+// Remove the metadata columns, so the rest can be read by position.
+// A row shorter than the header has empty metadata values.
+fn take_metadata(cols: &mut Vec<String>, metadata: &[(usize, String)]) -> Vec<(String, String)> {
+    let values: Vec<(String, String)> = metadata
+        .iter()
+        .map(|(idx, name)| (name.clone(), cols.get(*idx).cloned().unwrap_or_default()))
+        .collect();
+    metadata.iter().rev().for_each(|(idx, _)| {
+        if *idx < cols.len() {
+            cols.remove(*idx);
+        }
+    });
+    values
+}
+
+fn check_ini_line(cols: &[String]) -> Result<(), String> {
+    if cols.len() != 2 {
+        return Err(format!(
+            "INVALID INPUT. EXPECTING ID AND DIRECTORY PATH, \
+            FOUND: {:?}",
+            cols
+        ));
+    }
+    Ok(())
+}
+
+// Adapter sequences never contain a path separator.
+fn is_path_column(col: &str) -> bool {
+    col.contains('/') || Path::new(col).exists()
+}
+
+fn to_paths(cols: &[String]) -> Vec<PathBuf> {
+    cols.iter().map(PathBuf::from).collect()
+}
+
+fn get_target_dir(sample: &mut Sample, has_dir: bool, scheme: &NamingScheme) {
+    if let Some(name) = &sample.output_name {
+        sample.target_dir = PathBuf::from(name);
+    } else if !has_dir {
+        sample.target_dir = create_dir_from_r1(&sample.read_1, scheme);
+    }
+}
+
+// Use the sample name from the naming scheme if available.
+// Otherwise, use the first three words of the file name.
+fn create_dir_from_r1(read_1: &Path, scheme: &NamingScheme) -> PathBuf {
+    let fname = read_1.file_name().expect("MISSING FILES").to_string_lossy();
+    if let Some(sample) = scheme.parse(&fname).and_then(|name| name.sample) {
+        return PathBuf::from(sample);
+    }
+    let ids: Vec<&str> = fname.split('_').map(|e| e.trim()).collect();
+    PathBuf::from(ids.iter().take(3).copied().collect::<Vec<&str>>().join("_"))
+}
+
+// Positional sheets complement the tags, as in older versions of yap.
+fn get_adapters(sample: &mut Sample, adapters: &[String]) -> Result<(), String> {
+    sample.orientation = Orientation::Complement;
+    match adapters.len() {
+        0 => {
+            sample.auto_idx = true;
+            Ok(())
+        }
+        1 => get_adapter_single(sample, &adapters[0]),
+        2 => get_adapter_dual(sample, &adapters[0], &adapters[1]),
+        3 => get_insert_single(sample, &adapters[0], &adapters[1], &adapters[2]),
+        4 => get_insert_dual(
+            sample,
+            &adapters[0],
+            &adapters[1],
+            &adapters[2],
+            &adapters[3],
+        ),
+        _ => Err(format!(
+            "Unexpected cvs columns. It should be \
+            1 adapter column for single index and 2 adapter columns for \
+            dual index. The app received {} adapter columns",
+            adapters.len()
+        )),
+    }
+}
+
+fn get_adapter_single(sample: &mut Sample, adapter: &str) -> Result<(), String> {
+    let i5 = adapter.to_uppercase();
+    if is_insert_missing(&i5) {
+        return Err(String::from("INSERT MISSING!"));
+    }
+    set_adapter_single(sample, &i5);
+    Ok(())
+}
+
+fn get_adapter_dual(sample: &mut Sample, i5: &str, i7: &str) -> Result<(), String> {
+    let adapter_i5 = i5.to_uppercase();
+    if is_insert_missing(&adapter_i5) {
+        let adapter_i5 = insert_tag(sample, i5, i7, IndexRead::I5)?;
+        set_adapter_single(sample, &adapter_i5);
+    } else {
+        let adapter_i7 = i7.to_uppercase();
+        set_adapter_dual(sample, &adapter_i5, &adapter_i7);
+    }
+    Ok(())
+}
+
+fn get_insert_single(sample: &mut Sample, i5: &str, i7: &str, insert: &str) -> Result<(), String> {
+    let adapter_i7 = i7.to_uppercase();
+    if !is_insert_missing(i5) {
+        return Err(format!("INVALID COLUMNS FOR {}!", sample.id));
+    }
+    let adapter_i5 = insert_tag(sample, i5, insert, IndexRead::I5)?;
+    set_adapter_dual(sample, &adapter_i5, &adapter_i7);
+    Ok(())
+}
+
+fn get_insert_dual(
+    sample: &mut Sample,
+    i5: &str,
+    i7: &str,
+    insert_i5: &str,
+    insert_i7: &str,
+) -> Result<(), String> {
+    let i5 = insert_tag(sample, i5, insert_i5, IndexRead::I5)?;
+    let i7 = insert_tag(sample, i7, insert_i7, IndexRead::I7)?;
+    set_adapter_dual(sample, &i5, &i7);
+    Ok(())
+}
+
+fn insert_tag(
+    sample: &mut Sample,
+    adapter: &str,
+    insert: &str,
+    read: IndexRead,
+) -> Result<String, String> {
+    tag::check_tag(insert)?;
+    let bases = tag::get_adapter_index(insert, read, sample.orientation);
+    match read {
+        IndexRead::I5 => sample.index_i5 = Some(bases),
+        IndexRead::I7 => sample.index_i7 = Some(bases),
+    }
+    Ok(tag::insert_tag(adapter, insert, read, sample.orientation))
+}
+
+fn set_adapter_single(sample: &mut Sample, adapter: &str) {
+    sample.adapter_i5 = Some(String::from(adapter));
+}
+
+fn set_adapter_dual(sample: &mut Sample, adapter_i5: &str, adapter_i7: &str) {
+    let i5 = adapter_i5.trim();
+    let i7 = adapter_i7.trim();
+    match (i5.is_empty(), i7.is_empty()) {
+        (false, false) => {
+            sample.adapter_i5 = Some(String::from(i5));
+            sample.adapter_i7 = Some(String::from(i7));
+        }
+        (true, true) => sample.auto_idx = true,
+        _ => sample.adapter_i5 = Some(String::from(i5)),
+    }
+}
+
+fn is_insert_missing(adapter: &str) -> bool {
+    adapter.contains('*')
+}
+
+fn split_line(line: &str, sep: char) -> Vec<String> {
+    line.split(sep).map(|e| e.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_line_test() {
+        let line = "some_species;/mnt/d/test/";
+        let cols = split_line(line, ':');
+        assert!(check_ini_line(&cols).is_err());
+    }
+
+    #[test]
+    fn valid_ini_line_test() {
+        let line = "some_species:/mnt/d/test/";
+        let seq = split_line(line, ':');
+        assert_eq!(2, seq.len());
+    }
+
+    #[test]
+    fn valid_csv_line_test() {
+        let line = "some_species,other_species";
+        let seq = split_line(line, ',');
+        assert_eq!(2, seq.len());
+    }
+
+    #[test]
+    fn split_whitespaces_test() {
+        let line = " some_animals,folder/target/ ";
+        let cols = split_line(line, ',');
+        assert_eq!("some_animals", cols[0]);
+        assert_eq!("folder/target/", cols[1]);
+    }
+
+    #[test]
+    fn split_ini_error_test() {
+        let line = "some_animals:folder/target/:random";
+        let cols = split_line(line, ':');
+        assert!(check_ini_line(&cols).is_err());
+    }
+
+    #[test]
+    fn parse_ini_test() {
+        let input = PathBuf::from("test_files/qc/yap-qc_input.conf");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(2, samples.len());
+        let dir = Path::new("test_files/qc/");
+        assert_eq!(
+            dir.join("some_animals_XYZ12345_R1.fastq.gz"),
+            samples[0].read_1
+        );
+        assert_eq!(
+            dir.join("some_animals_XYZ12345_R2.fastq.gz"),
+            samples[0].read_2
+        );
+        assert_eq!(PathBuf::from("test_1"), samples[1].target_dir);
+        assert!(samples[1].auto_idx);
+    }
+
+    #[test]
+    fn parse_unknown_ext_test() {
+        let input = PathBuf::from("samples.txt");
+        let lines = vec![String::from("some_animals:/test/some_animals/")];
+
+        assert!(matches!(
+            get_format(&input, "[seqs]", &lines),
+            Ok(SheetFormat::Ini)
+        ));
+    }
+
+    #[test]
+    fn read_sheet_test() {
+        let input = PathBuf::from("test_files/qc/yap-qc_input.conf");
+        let (header, lines) = read_sheet(&input);
+
+        assert_eq!("[seqs]", header);
+        assert_eq!(2, lines.len());
+        assert_eq!(2, lines[0].0);
+        assert!(lines[1].1.starts_with("test_1:"));
+    }
+
+    #[test]
+    fn parse_tsv_test() {
+        let input = PathBuf::from("test_files/sheet/samples.tsv");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(2, samples.len());
+        assert!(samples[0].is_multi_lane());
+        assert!(samples[0].is_dual_idx());
+        assert_eq!(PathBuf::from("Rattus_rattus"), samples[1].target_dir);
+        assert_eq!("Sulawesi", samples[0].metadata[0].1);
+    }
+
+    #[test]
+    fn parse_csv_metadata_test() {
+        let input = PathBuf::from("test_files/qc/metadata_test.csv");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(2, samples.len());
+        let metadata = vec![
+            (String::from("voucher"), String::from("MZB 1234")),
+            (String::from("locality"), String::from("Sulawesi")),
+        ];
+        assert_eq!(metadata, samples[0].metadata);
+        assert_eq!("AGTCT", samples[0].adapter_i5.as_ref().unwrap());
+        assert_eq!("", samples[1].metadata[1].1);
+    }
+
+    #[test]
+    fn parse_csv_test() {
+        let input = PathBuf::from("test_files/qc/parse_csv_test.csv");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(1, samples.len());
+        let dir = input.parent().unwrap();
+        assert_eq!(dir.join("test_1_R1.fastq"), samples[0].read_1);
+        assert_eq!(dir.join("test_1_R2.fastq"), samples[0].read_2);
+        assert_eq!("AGTCT", samples[0].adapter_i5.as_ref().unwrap());
+    }
+
+    #[test]
+    fn parse_csv_rename_test() {
+        let input = PathBuf::from("test_files/qc/test_rename.csv");
+        let samples = parse_input(&input, true, &NamingScheme::Auto).unwrap();
+
+        samples.iter().for_each(|s| {
+            assert_eq!("some_animals_XYZ12345", s.id);
+            assert_eq!(PathBuf::from("Rattus_rattus_XYZ12345"), s.target_dir);
+            assert!(s.auto_idx);
+        });
+    }
+
+    #[test]
+    fn parse_csv_dir_column_test() {
+        let input = PathBuf::from("test_files/assembly/spade_runner.csv");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(1, samples.len());
+        let dir = Path::new("test_files/assembly/trimmed_test");
+        assert_eq!(dir, samples[0].dir);
+        assert_eq!(dir.join("some_seq_ABC123_R1.fq.gz"), samples[0].read_1);
+        assert_eq!(PathBuf::from("some_seq"), samples[0].target_dir);
+    }
+
+    #[test]
+    fn parse_csv_read_columns_test() {
+        let input = PathBuf::from("test_files/qc/read_columns_test.csv");
+        let samples = parse_input(&input, false, &NamingScheme::Auto).unwrap();
+
+        let dir = Path::new("test_files/qc/nested");
+        assert_eq!(
+            dir.join("nested_animals_XYZ12345_R1.fastq.gz"),
+            samples[0].read_1
+        );
+        assert_eq!(
+            dir.join("nested_animals_XYZ12345_R2.fastq.gz"),
+            samples[0].read_2
+        );
+        assert_eq!(dir, samples[0].dir);
+        assert_eq!("ATGTCTCTCT", samples[0].adapter_i5.as_ref().unwrap());
+    }
+
+    #[test]
+    fn parse_ini_read_columns_test() {
+        let line = "nested:test_files/qc/nested/nested_animals_XYZ12345_R2.fastq.gz,\
+            test_files/qc/nested/nested_animals_XYZ12345_R1.fastq.gz";
+        let sample = parse_ini_line(line, &NamingScheme::Auto).unwrap();
+
+        let dir = Path::new("test_files/qc/nested");
+        assert_eq!(
+            dir.join("nested_animals_XYZ12345_R1.fastq.gz"),
+            sample.read_1
+        );
+        assert_eq!(
+            dir.join("nested_animals_XYZ12345_R2.fastq.gz"),
+            sample.read_2
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn parse_csv_panic_test() {
+        let input = PathBuf::from("test_files/invalid.csv");
+
+        parse_input(&input, true, &NamingScheme::Auto).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn parse_csv_multicols_panic_test() {
+        let input = PathBuf::from("test_files/invalid_multicols.csv");
+
+        parse_input(&input, false, &NamingScheme::Auto).unwrap();
+    }
+
+    #[test]
+    fn get_adapter_test() {
+        let mut sample = Sample::new("MNCT", Path::new("."));
+        let i5 = String::from("ATGTGTGTGATatc");
+        let i7 = String::from("ATTTGTGTTTCCC");
+
+        get_adapters(&mut sample, &[i5, i7]).unwrap();
+
+        assert_eq!("ATGTGTGTGATATC", sample.adapter_i5.as_ref().unwrap());
+    }
+
+    #[test]
+    fn get_adapter_insert_test() {
+        let mut sample = Sample::new("MNCT", Path::new("."));
+        let i5 = String::from("ATGTGTGTGA*Tatc");
+        let i7 = String::from("ATTTGTGTTT*CCC");
+
+        let tag_i5 = String::from("ATT");
+        let tag_i7 = String::from("GCC");
+
+        get_adapters(&mut sample, &[i5, i7, tag_i5, tag_i7]).unwrap();
+
+        assert_eq!("ATGTGTGTGATAATATC", sample.adapter_i5.as_ref().unwrap());
+        assert_eq!("ATTTGTGTTTCGGCCC", sample.adapter_i7.as_ref().unwrap());
+    }
+
+    #[test]
+    fn is_insert_test() {
+        let seq = "ATATTAT*T";
+
+        assert!(is_insert_missing(seq));
+    }
+
+    #[test]
+    fn target_dir_from_r1_test() {
+        let read = Path::new("raw/some_animals_XYZ12345_R1.fastq.gz");
+
+        assert_eq!(
+            PathBuf::from("some_animals_XYZ12345"),
+            create_dir_from_r1(read, &NamingScheme::Auto)
+        );
+    }
+
+    #[test]
+    fn target_dir_from_illumina_r1_test() {
+        let read = Path::new("raw/some_animals_XYZ12345_S1_L001_R1_001.fastq.gz");
+
+        assert_eq!(
+            PathBuf::from("some_animals_XYZ12345"),
+            create_dir_from_r1(read, &NamingScheme::Auto)
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! Header-driven sample sheets.
+//!
+//! The header row names the columns, so they can be in any order
+//! and any of them can be left out, except `id`:
+//!
+//! | Column        | Content                                              |
+//! | ------------- | ---------------------------------------------------- |
+//! | `id`          | Sample id. Required.                                 |
+//! | `dir`         | Directory of the sample reads.                       |
+//! | `layout`      | `paired` (default), `single`, or `interleaved`.      |
+//! | `read1`       | Read 1 files. Lanes or runs are separated by `;`.    |
+//! | `read2`       | Read 2 files, in the same order as `read1`. Paired-end only. |
+//! | `singleton`   | Unpaired reads of the sample.                        |
+//! | `kit`         | Adapter kit. Built-in kit name or kit file.          |
+//! | `i5`          | i5 adapter. `*` marks the index inserted from `i5_tag`. With a kit, the i5 index name or sequence. |
+//! | `i7`          | i7 adapter. `*` marks the index inserted from `i7_tag`. With a kit, the i7 index name or sequence. |
+//! | `i5_tag`      | Index sequence for the i5 adapter.                   |
+//! | `i7_tag`      | Index sequence for the i7 adapter.                   |
+//! | `index_orientation` | How the tags were read: `forward` (default), `reverse`, or `complement`. |
+//! | `output_name` | Output name. Renames the output files.               |
+//! | `fastp_opts`  | fastp options, merged with `--opts`. Start with `replace:` to use them instead. |
+//! | `spades_opts` | SPAdes options, merged with `--opts`. Start with `replace:` to use them instead. |
+//! | `meta:<name>` | Sample metadata.                                     |
+//!
+//! Cells that contain the separator are quoted as in RFC 4180, e.g.
+//! `"--adapter_sequence AATG,ACTG"`. Quotes inside them are doubled.
+//! Empty cells are the same as a missing column. Without `dir` and the read
+//! columns, the reads are found in the directory of the sample sheet.
+//! Without adapters, fastp auto-detects them. `yap adapters` writes
+//! the adapters it detects into a new sheet. Adapters are the 5' to 3'
+//! oligos, not the adapter sequence in the reads.
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::sheet::kit::Kit;
+use crate::sheet::manifest;
+use crate::sheet::tag::{self, IndexRead, Orientation};
+use crate::sheet::{Layout, NamingScheme, Sample};
+use crate::utils::csv;
+
+/// Header prefix of the metadata columns, e.g. `meta:voucher`.
+pub const METADATA_PREFIX: &str = "meta:";
+
+#[derive(PartialEq)]
+enum Column {
+    Id,
+    Dir,
+    Layout,
+    Read1,
+    Read2,
+    Singleton,
+    Kit,
+    I5,
+    I7,
+    I5Tag,
+    I7Tag,
+    IndexOrientation,
+    OutputName,
+    FastpOpts,
+    SpadesOpts,
+    Meta(String),
+}
+
+impl Column {
+    // Accepts the column names used by older sheets and `yap new`.
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(key) = name.strip_prefix(METADATA_PREFIX) {
+            return Some(Self::Meta(key.trim().to_string()));
+        }
+        match name.to_lowercase().as_str() {
+            "id" => Some(Self::Id),
+            "dir" => Some(Self::Dir),
+            "layout" => Some(Self::Layout),
+            "read1" | "read_1" | "r1" => Some(Self::Read1),
+            "read2" | "read_2" | "r2" => Some(Self::Read2),
+            "singleton" => Some(Self::Singleton),
+            "kit" => Some(Self::Kit),
+            "i5" | "adapter" => Some(Self::I5),
+            "i7" => Some(Self::I7),
+            "i5_tag" => Some(Self::I5Tag),
+            "i7_tag" => Some(Self::I7Tag),
+            "index_orientation" => Some(Self::IndexOrientation),
+            "output_name" => Some(Self::OutputName),
+            "fastp_opts" => Some(Self::FastpOpts),
+            "spades_opts" => Some(Self::SpadesOpts),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> String {
+        let name = match self {
+            Self::Id => "id",
+            Self::Dir => "dir",
+            Self::Layout => "layout",
+            Self::Read1 => "read1",
+            Self::Read2 => "read2",
+            Self::Singleton => "singleton",
+            Self::Kit => "kit",
+            Self::I5 => "i5",
+            Self::I7 => "i7",
+            Self::I5Tag => "i5_tag",
+            Self::I7Tag => "i7_tag",
+            Self::IndexOrientation => "index_orientation",
+            Self::OutputName => "output_name",
+            Self::FastpOpts => "fastp_opts",
+            Self::SpadesOpts => "spades_opts",
+            Self::Meta(key) => return format!("{}{}", METADATA_PREFIX, key),
+        };
+        name.to_string()
+    }
+}
+
+pub struct Schema {
+    sep: char,
+    columns: Vec<Column>,
+}
+
+impl Schema {
+    /// A tsv header must follow the schema.
+    pub fn from_tsv_header(header: &str) -> Result<Self, String> {
+        match Self::get_columns(header, '\t') {
+            Ok(columns) => Self::new('\t', columns),
+            Err(unknown) => Err(get_unknown_columns_error(&unknown)),
+        }
+    }
+
+    /// A csv header that starts with `id` must follow the schema.
+    /// Returns None for the positional (legacy) csv format.
+    pub fn from_csv_header(header: &str) -> Result<Option<Self>, String> {
+        let first = header.split(',').next().unwrap_or_default();
+        if !first.trim().eq_ignore_ascii_case("id") {
+            return Ok(None);
+        }
+        match Self::get_columns(header, ',') {
+            Ok(columns) => Self::new(',', columns).map(Some),
+            Err(unknown) => Err(get_unknown_columns_error(&unknown)),
+        }
+    }
+
+    // Returns the unknown column names as the error.
+    fn get_columns(header: &str, sep: char) -> Result<Vec<Column>, Vec<String>> {
+        let names = csv::split_fields(header, sep).unwrap_or_else(|_| vec![header.to_string()]);
+        let names: Vec<&str> = names.iter().map(|n| n.trim()).collect();
+        let unknown: Vec<String> = names
+            .iter()
+            .filter(|n| Column::from_name(n).is_none())
+            .map(|n| n.to_string())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(unknown);
+        }
+        Ok(names.iter().filter_map(|n| Column::from_name(n)).collect())
+    }
+
+    fn new(sep: char, columns: Vec<Column>) -> Result<Self, String> {
+        let schema = Self { sep, columns };
+        schema.check_columns()?;
+        Ok(schema)
+    }
+
+    fn check_columns(&self) -> Result<(), String> {
+        if !self.has(&Column::Id) {
+            return Err(String::from(
+                "MISSING THE id COLUMN IN THE SAMPLE SHEET HEADER",
+            ));
+        }
+        for (idx, col) in self.columns.iter().enumerate() {
+            if self.columns[..idx].contains(col) {
+                return Err(format!(
+                    "DUPLICATE COLUMN {} IN THE SAMPLE SHEET HEADER",
+                    col.name()
+                ));
+            }
+        }
+        // Single-end sheets only have read 1.
+        if self.has(&Column::Read2) && !self.has(&Column::Read1) {
+            return Err(String::from(
+                "THE SAMPLE SHEET HAS A read2 COLUMN WITHOUT A read1 COLUMN",
+            ));
+        }
+        if self.has(&Column::I7) && !self.has(&Column::I5) {
+            return Err(String::from(
+                "THE SAMPLE SHEET HAS AN i7 COLUMN WITHOUT AN i5 COLUMN",
+            ));
+        }
+        Ok(())
+    }
+
+    fn has(&self, col: &Column) -> bool {
+        self.columns.contains(col)
+    }
+
+    /// Parse a sample line. The reads in the same directory as the sheet
+    /// are used when the line has neither a dir nor reads.
+    pub fn parse_line(
+        &self,
+        line: &str,
+        input: &Path,
+        scheme: &NamingScheme,
+    ) -> Result<Sample, String> {
+        let fields = csv::split_fields(line, self.sep)?;
+        let cells: Vec<&str> = fields.iter().map(|c| c.trim()).collect();
+        if cells.len() > self.columns.len() {
+            return Err(format!(
+                "THE LINE HAS {} COLUMNS BUT THE HEADER HAS {}: {}",
+                cells.len(),
+                self.columns.len(),
+                line
+            ));
+        }
+        let row = Row {
+            schema: self,
+            cells,
+        };
+        let id = row
+            .get(&Column::Id)
+            .ok_or_else(|| format!("MISSING SAMPLE ID: {}", line))?;
+        let parent = input.parent().expect("INVALID INPUT PATH");
+        let mut sample = Sample::new(id, parent);
+        if let Some(layout) = row.get(&Column::Layout) {
+            sample.layout = Layout::from_name(layout).ok_or_else(|| {
+                format!(
+                    "INVALID LAYOUT {} OF {}. USE paired, single, OR interleaved.",
+                    layout, id
+                )
+            })?;
+        }
+        if let Some(orientation) = row.get(&Column::IndexOrientation) {
+            sample.orientation = Orientation::from_name(orientation).ok_or_else(|| {
+                format!(
+                    "INVALID INDEX ORIENTATION {} OF {}. USE forward, reverse, OR complement.",
+                    orientation, id
+                )
+            })?;
+        }
+
+        row.get_reads(&mut sample, scheme)?;
+        row.get_adapters(&mut sample, parent)?;
+        if let Some(name) = row.get(&Column::OutputName) {
+            sample.output_name = Some(name.to_string());
+            sample.target_dir = PathBuf::from(name);
+        }
+        sample.fastp_opts = row.get(&Column::FastpOpts).map(String::from);
+        sample.spades_opts = row.get(&Column::SpadesOpts).map(String::from);
+        sample.metadata = row.get_metadata();
+        Ok(sample)
+    }
+}
+
+fn get_unknown_columns_error(unknown: &[String]) -> String {
+    format!(
+        "UNKNOWN COLUMNS {:?} IN THE SAMPLE SHEET HEADER. \
+        USE meta:<name> FOR METADATA COLUMNS.",
+        unknown
+    )
+}
+
+struct Row<'a> {
+    schema: &'a Schema,
+    cells: Vec<&'a str>,
+}
+
+impl Row<'_> {
+    fn get(&self, col: &Column) -> Option<&str> {
+        let idx = self.schema.columns.iter().position(|c| c == col)?;
+        self.cells.get(idx).copied().filter(|c| !c.is_empty())
+    }
+
+    fn get_paths(&self, col: &Column) -> Vec<PathBuf> {
+        match self.get(col) {
+            Some(cell) => cell
+                .split(';')
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_reads(&self, sample: &mut Sample, scheme: &NamingScheme) -> Result<(), String> {
+        let reads: Vec<PathBuf> = [Column::Read1, Column::Read2, Column::Singleton]
+            .iter()
+            .flat_map(|col| self.get_paths(col))
+            .collect();
+        if !reads.is_empty() {
+            sample.set_reads(&reads, scheme)
+        } else if let Some(dir) = self.get(&Column::Dir) {
+            sample.dir = PathBuf::from(dir);
+            sample.find_reads(true, scheme)
+        } else {
+            sample.find_reads(false, scheme)
+        }
+    }
+
+    fn get_adapters(&self, sample: &mut Sample, dir: &Path) -> Result<(), String> {
+        let kit = match self.get(&Column::Kit) {
+            Some(name) => Some(self.load_kit(name, dir, &sample.id)?),
+            None => None,
+        };
+        let i5 = self.get_adapter(sample, kit.as_ref(), IndexRead::I5)?;
+        let i7 = self.get_adapter(sample, kit.as_ref(), IndexRead::I7)?;
+        match (i5, i7) {
+            (None, None) => sample.auto_idx = true,
+            (Some(i5), i7) => {
+                sample.adapter_i5 = Some(i5);
+                sample.adapter_i7 = i7;
+            }
+            (None, Some(_)) => return Err(format!("MISSING THE i5 ADAPTER OF {}", sample.id)),
+        }
+        Ok(())
+    }
+
+    fn load_kit(&self, name: &str, dir: &Path, id: &str) -> Result<Kit, String> {
+        if self.get(&Column::I5Tag).is_some() || self.get(&Column::I7Tag).is_some() {
+            return Err(format!("USE EITHER A kit OR i5_tag AND i7_tag FOR {}", id));
+        }
+        Kit::load(name, dir)
+    }
+
+    // With a kit, the i5 and i7 cells hold index names or sequences.
+    // Records the index bases inserted into the adapter.
+    fn get_adapter(
+        &self,
+        sample: &mut Sample,
+        kit: Option<&Kit>,
+        read: IndexRead,
+    ) -> Result<Option<String>, String> {
+        let (col, tag_col) = match read {
+            IndexRead::I5 => (Column::I5, Column::I5Tag),
+            IndexRead::I7 => (Column::I7, Column::I7Tag),
+        };
+        let cell = match self.get(&col) {
+            Some(cell) => cell,
+            None => return Ok(None),
+        };
+        let (adapter, bases) = match (kit, self.get(&tag_col)) {
+            (Some(kit), _) => (
+                kit.get_adapter(read),
+                kit.get_index(cell, read, sample.orientation)?,
+            ),
+            (None, Some(tag)) if cell.contains('*') => {
+                tag::check_tag(tag)?;
+                (cell, tag::get_adapter_index(tag, read, sample.orientation))
+            }
+            (None, None) if cell.contains('*') => {
+                return Err(format!("MISSING THE {} OF {}", tag_col.name(), sample.id))
+            }
+            (None, _) => return Ok(Some(cell.to_uppercase())),
+        };
+        let adapter = adapter.replace('*', &bases).to_uppercase();
+        match read {
+            IndexRead::I5 => sample.index_i5 = Some(bases),
+            IndexRead::I7 => sample.index_i7 = Some(bases),
+        }
+        Ok(Some(adapter))
+    }
+
+    fn get_metadata(&self) -> Vec<(String, String)> {
+        self.schema
+            .columns
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, col)| match col {
+                Column::Meta(key) => {
+                    let value = self.cells.get(idx).copied().unwrap_or_default();
+                    Some((key.clone(), value.to_string()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Write the samples as a csv sheet with the resolved reads and
+/// adapters. Adapters are written in full, so the tag columns are
+/// left out. Samples without adapters keep empty adapter cells.
+pub fn write_sample_sheet(path: &Path, samples: &[Sample]) -> io::Result<()> {
+    let mut writer = LineWriter::new(File::create(path)?);
+    let keys = manifest::get_metadata_keys(samples);
+    let columns: Vec<Column> = [
+        Column::Id,
+        Column::Layout,
+        Column::Read1,
+        Column::Read2,
+        Column::Singleton,
+        Column::I5,
+        Column::I7,
+        Column::OutputName,
+        Column::FastpOpts,
+        Column::SpadesOpts,
+    ]
+    .into_iter()
+    .chain(keys.iter().map(|key| Column::Meta(key.clone())))
+    .collect();
+    let header: Vec<String> = columns.iter().map(|col| col.name()).collect();
+    writeln!(writer, "{}", csv::join_fields(&header, ','))?;
+
+    for sample in samples {
+        let (read_1, read_2) = manifest::join_reads(sample);
+        let row: Vec<String> = columns
+            .iter()
+            .map(|col| match col {
+                Column::Id => sample.id.clone(),
+                Column::Layout => sample.layout.name().to_string(),
+                Column::Read1 => read_1.clone(),
+                Column::Read2 => read_2.clone(),
+                Column::Singleton => sample
+                    .singleton
+                    .as_ref()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                Column::I5 => sample.adapter_i5.clone().unwrap_or_default(),
+                Column::I7 => sample.adapter_i7.clone().unwrap_or_default(),
+                Column::OutputName => sample.output_name.clone().unwrap_or_default(),
+                Column::FastpOpts => sample.fastp_opts.clone().unwrap_or_default(),
+                Column::SpadesOpts => sample.spades_opts.clone().unwrap_or_default(),
+                Column::Meta(key) => manifest::get_value(sample, key),
+                _ => String::new(),
+            })
+            .collect();
+        writeln!(writer, "{}", csv::join_fields(&row, ','))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_header_test() {
+        assert!(matches!(
+            Schema::from_csv_header("id,read_1,read_2"),
+            Ok(Some(_))
+        ));
+        assert!(matches!(
+            Schema::from_csv_header("ID,dir,adapter,meta:voucher"),
+            Ok(Some(_))
+        ));
+        assert!(matches!(
+            Schema::from_csv_header("id,read1,read2,i5_tg"),
+            Err(e) if e.starts_with("UNKNOWN COLUMNS [\"i5_tg\"]")
+        ));
+        assert!(matches!(Schema::from_csv_header("path,i5,i7"), Ok(None)));
+        assert!(Schema::from_csv_header("id,dir,dir").is_err());
+    }
+
+    #[test]
+    fn tsv_unknown_column_test() {
+        let res = Schema::from_tsv_header("id\tread1\tread2\tvoucher");
+
+        assert!(matches!(res, Err(e) if e.starts_with("UNKNOWN COLUMNS")));
+    }
+
+    #[test]
+    fn missing_read1_column_test() {
+        let res = Schema::from_tsv_header("id\tread2");
+
+        assert!(matches!(res, Err(e) if e.contains("read2 COLUMN WITHOUT A read1 COLUMN")));
+    }
+
+    #[test]
+    fn parse_single_end_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tlayout\tread1").unwrap();
+        let line = "SRR1234\tsingle\ttest_files/single/SRR1234.fastq.gz";
+        let sample = schema.parse_line(line, input, &NamingScheme::Auto).unwrap();
+
+        assert!(sample.is_single_end());
+        assert_eq!(
+            PathBuf::from("test_files/single/SRR1234.fastq.gz"),
+            sample.read_1
+        );
+    }
+
+    #[test]
+    fn parse_kit_test() {
+        let input = Path::new("test_files/kit/samples.csv");
+        let schema = Schema::from_csv_header("id,dir,kit,i5,i7")
+            .unwrap()
+            .unwrap();
+        let line = "Apodemus,test_files/lanes,kits/plate_1.tsv,i5_01,i7_01";
+        let sample = schema.parse_line(line, input, &NamingScheme::Auto).unwrap();
+
+        assert!(sample.is_dual_idx());
+        assert_eq!(
+            Some(String::from(
+                "AATGATACGGCGACCACCGAGATCTACACAACCGGTTACACTCTTTCCCTACACGACGC"
+            )),
+            sample.adapter_i5
+        );
+        assert_eq!(
+            Some(String::from(
+                "CAAGCAGAAGACGGCATACGAGATGGTTACCAGTGACTGGAGTTCAGACGTGT"
+            )),
+            sample.adapter_i7
+        );
+    }
+
+    #[test]
+    fn parse_tsv_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let header = "id\toutput_name\tread1\tread2\ti5\ti7\ti7_tag\tmeta:voucher";
+        let schema = Schema::from_tsv_header(header).unwrap();
+        let line = "Apodemus\tApodemus_sp\t\
+            test_files/lanes/Apodemus_S3_L001_R1_001.fastq.gz;test_files/lanes/Apodemus_S3_L002_R1_001.fastq\t\
+            test_files/lanes/Apodemus_S3_L001_R2_001.fastq.gz;test_files/lanes/Apodemus_S3_L002_R2_001.fastq\t\
+            aatgatacgg\tCAAGCAG*ATCT\tATG\tKU 1";
+        let sample = schema.parse_line(line, input, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(2, sample.lanes.len());
+        assert_eq!(PathBuf::from("Apodemus_sp"), sample.target_dir);
+        assert_eq!("AATGATACGG", sample.adapter_i5.as_ref().unwrap());
+        assert_eq!("CAAGCAGCATATCT", sample.adapter_i7.as_ref().unwrap());
+        assert_eq!(Some(String::from("CAT")), sample.index_i7);
+        assert_eq!(
+            vec![(String::from("voucher"), String::from("KU 1"))],
+            sample.metadata
+        );
+    }
+
+    #[test]
+    fn parse_empty_cells_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tdir\ti5\tmeta:voucher").unwrap();
+        let sample = schema
+            .parse_line("Apodemus\ttest_files/lanes/\t", input, &NamingScheme::Auto)
+            .unwrap();
+
+        assert!(sample.auto_idx);
+        assert_eq!(PathBuf::from("Apodemus"), sample.target_dir);
+        assert_eq!("", sample.metadata[0].1);
+    }
+
+    #[test]
+    fn parse_tool_opts_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_csv_header("id,dir,fastp_opts,spades_opts")
+            .unwrap()
+            .unwrap();
+        let line = "Apodemus,test_files/lanes/,--length_required 30 --cut_right,";
+        let sample = schema.parse_line(line, input, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(
+            Some("--length_required 30 --cut_right"),
+            sample.fastp_opts.as_deref()
+        );
+        assert!(sample.spades_opts.is_none());
+    }
+
+    #[test]
+    fn parse_quoted_cells_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_csv_header("id,dir,fastp_opts,meta:locality")
+            .unwrap()
+            .unwrap();
+        let line = "Apodemus,test_files/lanes/,\"--adapter_sequence AATG,ACTG\",\
+            \"Sulawesi, \"\"Mt. Dako\"\"\"";
+        let sample = schema.parse_line(line, input, &NamingScheme::Auto).unwrap();
+
+        assert_eq!(
+            Some("--adapter_sequence AATG,ACTG"),
+            sample.fastp_opts.as_deref()
+        );
+        assert_eq!("Sulawesi, \"Mt. Dako\"", sample.metadata[0].1);
+        let res = schema.parse_line("Apodemus,\"test_files", input, &NamingScheme::Auto);
+        assert!(matches!(res, Err(e) if e.starts_with("MISSING A CLOSING QUOTE")));
+    }
+
+    #[test]
+    fn missing_tag_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tdir\ti5").unwrap();
+        let res = schema.parse_line(
+            "Apodemus\ttest_files/lanes/\tAAT*GG",
+            input,
+            &NamingScheme::Auto,
+        );
+
+        assert!(matches!(res, Err(e) if e == "MISSING THE i5_tag OF Apodemus"));
+    }
+
+    #[test]
+    fn invalid_tag_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tdir\ti5\ti5_tag").unwrap();
+        let res = schema.parse_line(
+            "Apodemus\ttest_files/lanes/\tAAT*GG\tATXG",
+            input,
+            &NamingScheme::Auto,
+        );
+
+        assert!(matches!(res, Err(e) if e.starts_with("INVALID TAG DNA SEQUENCES")));
+    }
+
+    #[test]
+    fn extra_cells_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tdir").unwrap();
+        let res = schema.parse_line(
+            "Apodemus\ttest_files/lanes/\tATG",
+            input,
+            &NamingScheme::Auto,
+        );
+
+        assert!(
+            matches!(res, Err(e) if e.starts_with("THE LINE HAS 3 COLUMNS BUT THE HEADER HAS 2"))
+        );
+    }
+
+    #[test]
+    fn write_sample_sheet_test() {
+        let input = Path::new("test_files/sheet/samples.tsv");
+        let schema = Schema::from_tsv_header("id\tdir\ti5\tmeta:voucher").unwrap();
+        let mut sample = schema
+            .parse_line(
+                "Apodemus\ttest_files/lanes/\t\tKU 1",
+                input,
+                &NamingScheme::Auto,
+            )
+            .unwrap();
+        sample.adapter_i5 = Some(String::from("AGATCGGAAGAGCACACGTC"));
+        sample.spades_opts = Some(String::from("-k 21,33,55"));
+        let path = std::env::temp_dir().join("yap_write_sample_sheet_test.csv");
+        write_sample_sheet(&path, &[sample]).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut lines = content.lines();
+        let schema = Schema::from_csv_header(lines.next().unwrap())
+            .unwrap()
+            .unwrap();
+        let sample = schema
+            .parse_line(lines.next().unwrap(), &path, &NamingScheme::Auto)
+            .unwrap();
+
+        assert_eq!(2, sample.lanes.len());
+        assert_eq!("AGATCGGAAGAGCACACGTC", sample.adapter_i5.unwrap());
+        assert!(sample.adapter_i7.is_none());
+        assert_eq!(Some("-k 21,33,55"), sample.spades_opts.as_deref());
+        assert_eq!("KU 1", sample.metadata[0].1);
+    }
+}
@@ -0,0 +1,359 @@
+//! Index tags inserted into the adapters.
+//!
+//! Tags are the index sequences as read by the sequencer. The i7 index
+//! read is the reverse complement of the i7 adapter bases. The i5 index
+//! read depends on the sequencer: forward-strand workflows (MiSeq,
+//! NovaSeq v1.0 chemistry) read the i5 adapter bases as is, while
+//! reverse-complement workflows (NovaSeq v1.5 chemistry, NextSeq)
+//! read their reverse complement.
+
+/// How the index tags of a sample were read.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Orientation {
+    /// Forward-strand i5 workflow.
+    #[default]
+    Forward,
+    /// Reverse-complement i5 workflow.
+    Reverse,
+    /// Complement each base without reversing, as in older versions
+    /// of yap. Used by positional csv sheets.
+    Complement,
+}
+
+impl Orientation {
+    pub const ALL: [Self; 3] = [Self::Forward, Self::Reverse, Self::Complement];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "forward" => Some(Self::Forward),
+            "reverse" | "reverse-complement" => Some(Self::Reverse),
+            "complement" => Some(Self::Complement),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Forward => "forward",
+            Self::Reverse => "reverse",
+            Self::Complement => "complement",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexRead {
+    I5,
+    I7,
+}
+
+impl IndexRead {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::I5 => "i5",
+            Self::I7 => "i7",
+        }
+    }
+}
+
+/// Replace the `*` of the adapter with the bases of the tag.
+pub fn insert_tag(seq: &str, ins: &str, read: IndexRead, orientation: Orientation) -> String {
+    seq.replace('*', &get_adapter_index(ins, read, orientation))
+        .to_uppercase()
+}
+
+/// The adapter bases of an index tag.
+/// The tag must be checked with `check_tag` first.
+pub fn get_adapter_index(ins: &str, read: IndexRead, orientation: Orientation) -> String {
+    let insert = ins.to_uppercase();
+    check_tag(&insert).unwrap_or_else(|e| panic!("{}", e));
+    match (orientation, read) {
+        (Orientation::Complement, _) => translate_dna(&insert),
+        (Orientation::Forward, IndexRead::I5) => insert,
+        _ => reverse_complement(&insert),
+    }
+}
+
+/// The tag that `get_adapter_index` turns into the adapter bases.
+pub fn get_tag(bases: &str, read: IndexRead, orientation: Orientation) -> String {
+    match (orientation, read) {
+        (Orientation::Complement, _) => translate_dna(bases),
+        (Orientation::Forward, IndexRead::I5) => bases.to_string(),
+        _ => reverse_complement(bases),
+    }
+}
+
+/// True if the sequencer could have read the adapter bases as
+/// the index in a read header. `workflow` is how the sequencer reads
+/// the i5 index. Without it, the i5 index may be read in either
+/// orientation. Allows one mismatch.
+pub fn matches_header_index(
+    bases: &str,
+    header: &str,
+    read: IndexRead,
+    workflow: Option<Orientation>,
+) -> bool {
+    let rev_comp = reverse_complement(bases);
+    match (read, workflow) {
+        (IndexRead::I7, _) | (IndexRead::I5, Some(Orientation::Reverse)) => {
+            is_similar(&rev_comp, header)
+        }
+        (IndexRead::I5, Some(_)) => is_similar(bases, header),
+        (IndexRead::I5, None) => is_similar(&rev_comp, header) || is_similar(bases, header),
+    }
+}
+
+/// The i5 workflow of a sequencer from the instrument ID in the read
+/// headers, e.g. `M01234` for a MiSeq. None for unknown instruments
+/// and for the NovaSeq 6000, whose workflow depends on the chemistry.
+pub fn get_i5_workflow(instrument: &str) -> Option<Orientation> {
+    let prefix: String = instrument
+        .chars()
+        .take_while(|c| !c.is_ascii_digit())
+        .collect();
+    match prefix.as_str() {
+        // MiSeq and HiSeq 2000/2500.
+        "M" | "HWI-M" | "D" | "HWI-D" | "SN" | "HWI-ST" => Some(Orientation::Forward),
+        // MiniSeq, iSeq, NextSeq, HiSeq 3000/4000/X, and NovaSeq X.
+        "MN" | "FS" | "NB" | "NS" | "VH" | "VL" | "K" | "J" | "E" | "LH" => {
+            Some(Orientation::Reverse)
+        }
+        _ => None,
+    }
+}
+
+// N in the header is an uncalled base.
+fn is_similar(expected: &str, header: &str) -> bool {
+    if expected.len() != header.len() {
+        return false;
+    }
+    let mismatches = expected
+        .chars()
+        .zip(header.chars())
+        .filter(|(e, h)| *h != 'N' && !base_matches(*e, *h))
+        .count();
+    mismatches <= 1
+}
+
+// An IUPAC code in the tag matches any of its bases.
+fn base_matches(code: char, base: char) -> bool {
+    let bases = match code {
+        'R' => "AG",
+        'Y' => "CT",
+        'S' => "GC",
+        'W' => "AT",
+        'K' => "GT",
+        'M' => "AC",
+        'B' => "CGT",
+        'D' => "AGT",
+        'H' => "ACT",
+        'V' => "ACG",
+        'N' => "ACGT",
+        _ => return code == base,
+    };
+    bases.contains(base)
+}
+
+/// Reverse complement of an index sequence.
+pub fn reverse_complement(seq: &str) -> String {
+    let seq = seq.to_uppercase();
+    check_tag(&seq).unwrap_or_else(|e| panic!("{}", e));
+    translate_dna(&seq).chars().rev().collect()
+}
+
+/// Tags may only contain DNA bases and IUPAC codes.
+pub fn check_tag(insert: &str) -> Result<(), String> {
+    if !insert.to_uppercase().chars().all(is_iupac_base) {
+        return Err(format!("INVALID TAG DNA SEQUENCES: {}", insert));
+    }
+    Ok(())
+}
+
+/// Adapters may contain N or other IUPAC codes
+/// for the unknown bases of the index.
+pub fn is_valid_adapter(adapter: &str) -> bool {
+    !adapter.is_empty() && adapter.chars().all(is_iupac_base)
+}
+
+fn is_iupac_base(base: char) -> bool {
+    complement(base).is_some()
+}
+
+fn translate_dna(insert: &str) -> String {
+    insert
+        .to_uppercase()
+        .chars()
+        .map(|b| complement(b).expect("INVALID TAG DNA SEQUENCES"))
+        .collect()
+}
+
+fn complement(base: char) -> Option<char> {
+    let comp = match base {
+        'A' => 'T',
+        'T' => 'A',
+        'G' => 'C',
+        'C' => 'G',
+        'R' => 'Y',
+        'Y' => 'R',
+        'S' => 'S',
+        'W' => 'W',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        'N' => 'N',
+        _ => return None,
+    };
+    Some(comp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_tag_test() {
+        let tag = "ATGTTAXCG";
+
+        assert!(check_tag(tag).is_err());
+        assert!(check_tag("atgNN").is_ok());
+    }
+
+    #[test]
+    fn valid_adapter_test() {
+        assert!(is_valid_adapter("AATGATACGGCGACCACCGAGATCTACACNNNNNNNN"));
+        assert!(!is_valid_adapter("AATGAT-ACGG"));
+        assert!(!is_valid_adapter("ATG*C"));
+    }
+
+    #[test]
+    fn tag_insertion_test() {
+        let tag = "ATG";
+        let seq = "ATTTGT*C";
+        let res = String::from("ATTTGTTACC");
+
+        assert_eq!(
+            res,
+            insert_tag(seq, tag, IndexRead::I5, Orientation::Complement)
+        );
+    }
+
+    #[test]
+    fn tag_insertion_lowercase_test() {
+        let tag = "atG";
+        let seq = "ATTTGT*C";
+        let res = String::from("ATTTGTTACC");
+
+        assert_eq!(
+            res,
+            insert_tag(seq, tag, IndexRead::I5, Orientation::Complement)
+        );
+    }
+
+    #[test]
+    fn tag_orientation_test() {
+        let seq = "AAT*C";
+        let tag = "ACCG";
+
+        assert_eq!(
+            "AATACCGC",
+            insert_tag(seq, tag, IndexRead::I5, Orientation::Forward)
+        );
+        assert_eq!(
+            "AATCGGTC",
+            insert_tag(seq, tag, IndexRead::I5, Orientation::Reverse)
+        );
+        assert_eq!(
+            "AATCGGTC",
+            insert_tag(seq, tag, IndexRead::I7, Orientation::Forward)
+        );
+        for orientation in Orientation::ALL {
+            let bases = get_adapter_index(tag, IndexRead::I7, orientation);
+            assert_eq!(tag, get_tag(&bases, IndexRead::I7, orientation));
+        }
+    }
+
+    #[test]
+    fn reverse_complement_test() {
+        assert_eq!("CGAGTAAT", reverse_complement("attactcg"));
+        assert_eq!("NBAYT", reverse_complement("ARTVN"));
+    }
+
+    #[test]
+    fn header_index_test() {
+        let forward = Some(Orientation::Forward);
+        let reverse = Some(Orientation::Reverse);
+        assert!(matches_header_index(
+            "CGAGTAAT",
+            "ATTACTCG",
+            IndexRead::I7,
+            None
+        ));
+        assert!(matches_header_index(
+            "CGAGTAAT",
+            "ATTACTCN",
+            IndexRead::I7,
+            forward
+        ));
+        assert!(!matches_header_index(
+            "CGAGTAAT",
+            "CGAGTAAT",
+            IndexRead::I7,
+            None
+        ));
+        assert!(matches_header_index(
+            "TATAGCCT",
+            "TATAGCCT",
+            IndexRead::I5,
+            None
+        ));
+        assert!(matches_header_index(
+            "TATAGCCT",
+            "AGGCTATA",
+            IndexRead::I5,
+            None
+        ));
+        assert!(matches_header_index(
+            "TATNGCCT",
+            "TATAGCCT",
+            IndexRead::I5,
+            forward
+        ));
+        assert!(!matches_header_index(
+            "TATAGCCT",
+            "AGGCTATA",
+            IndexRead::I5,
+            forward
+        ));
+        assert!(matches_header_index(
+            "TATAGCCT",
+            "AGGCTATA",
+            IndexRead::I5,
+            reverse
+        ));
+        assert!(!matches_header_index(
+            "TATAGCCT",
+            "TATAGCCT",
+            IndexRead::I5,
+            reverse
+        ));
+    }
+
+    #[test]
+    fn i5_workflow_test() {
+        assert_eq!(Some(Orientation::Forward), get_i5_workflow("M01234"));
+        assert_eq!(Some(Orientation::Reverse), get_i5_workflow("MN00123"));
+        assert_eq!(Some(Orientation::Reverse), get_i5_workflow("NB501234"));
+        assert_eq!(None, get_i5_workflow("A00123"));
+    }
+
+    #[test]
+    fn translate_dna_test() {
+        let dna = "ATGC";
+        let res = String::from("TACG");
+
+        assert_eq!(res, translate_dna(dna));
+    }
+}
@@ -0,0 +1,463 @@
+//! Check a sample sheet and its reads before a run.
+//!
+//! The parser stops at the first bad line. The validator parses each line
+//! on its own and keeps going, so a single run lists every problem.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use colored::Colorize;
+use flate2::read::MultiGzDecoder;
+use rayon::prelude::*;
+
+use crate::sheet::collision::{self, MIN_INDEX_DISTANCE};
+use crate::sheet::merger::is_gzip;
+use crate::sheet::tag::{self, IndexRead, Orientation};
+use crate::sheet::{interleave, parser, NamingScheme, Sample};
+use crate::utils::opts::{self, Tool};
+
+// Reads checked for the index in the read headers.
+const HEADERS_TO_CHECK: usize = 1000;
+
+struct Problem {
+    // Sheet line or sample id.
+    location: String,
+    message: String,
+}
+
+impl Problem {
+    fn new(location: &str, message: &str) -> Self {
+        Self {
+            location: location.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+pub struct Validator<'a> {
+    input: &'a Path,
+    is_rename: bool,
+    scheme: &'a NamingScheme,
+    /// Index pairs of two samples closer than this are reported.
+    pub min_index_distance: usize,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(input: &'a Path, is_rename: bool, scheme: &'a NamingScheme) -> Self {
+        Self {
+            input,
+            is_rename,
+            scheme,
+            min_index_distance: MIN_INDEX_DISTANCE,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Print a report of every problem found.
+    /// Returns true if the sheet and its reads are valid.
+    pub fn validate(&mut self) -> bool {
+        let samples = self.check_sheet();
+
+        self.check_duplicate_ids(&samples);
+        self.check_adapters(&samples);
+        self.check_tool_opts(&samples);
+        self.check_index_collisions(&samples);
+        self.check_reads(&samples);
+        self.check_indexes(&samples);
+        self.print_report(samples.len());
+        self.problems.is_empty()
+    }
+
+    fn check_sheet(&mut self) -> Vec<Sample> {
+        if !self.input.is_file() {
+            self.add_problem("Sheet", "CANNOT FIND THE SAMPLE SHEET");
+            return Vec::new();
+        }
+        let (header, lines) = parser::read_sheet(self.input);
+        let text: Vec<String> = lines.iter().map(|(_, line)| line.clone()).collect();
+        let format = match parser::get_format(self.input, &header, &text) {
+            Ok(format) => format,
+            Err(msg) => {
+                self.add_problem("Sheet", &msg);
+                return Vec::new();
+            }
+        };
+
+        let mut samples = Vec::new();
+        lines.iter().for_each(|(idx, line)| {
+            match parser::parse_line(line, &format, self.input, self.is_rename, self.scheme) {
+                Ok(sample) => samples.push(sample),
+                Err(msg) => self.add_problem(&format!("Line {}", idx), &msg),
+            }
+        });
+        samples
+    }
+
+    fn check_duplicate_ids(&mut self, samples: &[Sample]) {
+        let mut ids: BTreeMap<&str, usize> = BTreeMap::new();
+        samples
+            .iter()
+            .for_each(|s| *ids.entry(&s.id).or_insert(0) += 1);
+        ids.iter()
+            .filter(|(_, count)| **count > 1)
+            .for_each(|(id, count)| {
+                self.add_problem(id, &format!("DUPLICATE ID IN {} LINES", count));
+            });
+    }
+
+    fn check_adapters(&mut self, samples: &[Sample]) {
+        samples.iter().for_each(|s| {
+            [("i5", &s.adapter_i5), ("i7", &s.adapter_i7)]
+                .iter()
+                .filter_map(|(name, adapter)| adapter.as_ref().map(|a| (name, a)))
+                .filter(|(_, adapter)| !tag::is_valid_adapter(adapter))
+                .for_each(|(name, adapter)| {
+                    let msg = format!("INVALID {} ADAPTER CHARACTERS: {}", name, adapter);
+                    self.add_problem(&s.id, &msg);
+                });
+        });
+    }
+
+    // Without `--opts`, only the sample options can be checked.
+    fn check_tool_opts(&mut self, samples: &[Sample]) {
+        samples.iter().for_each(|s| {
+            [Tool::Fastp, Tool::Spades].iter().for_each(|tool| {
+                if let Err(e) = opts::check_opts(*tool, &[], s) {
+                    self.add_problem(&s.id, &format!("INVALID {}: {}", tool.column(), e));
+                }
+            });
+        });
+    }
+
+    fn check_index_collisions(&mut self, samples: &[Sample]) {
+        collision::find_index_collisions(samples, self.min_index_distance)
+            .iter()
+            .for_each(|c| self.add_problem(c.sample, &c.message()));
+    }
+
+    // Only the first reads are checked, so this runs after the reads are counted.
+    fn check_indexes(&mut self, samples: &[Sample]) {
+        samples.iter().for_each(|s| {
+            if let Some(msg) = check_index(s) {
+                self.add_problem(&s.id, &msg);
+            }
+        });
+    }
+
+    // Reading every file is the slow part, so samples run in parallel.
+    fn check_reads(&mut self, samples: &[Sample]) {
+        let problems: Vec<Problem> = samples.par_iter().flat_map(check_sample_reads).collect();
+        self.problems.extend(problems);
+    }
+
+    fn add_problem(&mut self, location: &str, message: &str) {
+        self.problems.push(Problem::new(location, message));
+    }
+
+    fn print_report(&self, sample_count: usize) {
+        println!();
+        if self.problems.is_empty() {
+            println!(
+                "{} {} is valid: {} samples",
+                "✔".green(),
+                self.input.display(),
+                sample_count
+            );
+            return;
+        }
+
+        println!(
+            "{}",
+            format!(
+                "Found {} problems in {}:",
+                self.problems.len(),
+                self.input.display()
+            )
+            .red()
+        );
+        let width = self
+            .problems
+            .iter()
+            .map(|p| p.location.len())
+            .max()
+            .unwrap_or(0)
+            .max(8)
+            + 2;
+        println!("{:width$}Problem", "Location", width = width);
+        self.problems.iter().for_each(|p| {
+            println!("{:width$}{}", p.location, p.message, width = width);
+        });
+        println!();
+    }
+}
+
+fn check_sample_reads(sample: &Sample) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let pairs = if sample.lanes.is_empty() {
+        vec![(sample.read_1.as_path(), sample.read_2.as_path())]
+    } else {
+        sample
+            .lanes
+            .iter()
+            .map(|(r1, r2)| (r1.as_path(), r2.as_path()))
+            .collect()
+    };
+    if sample.is_single_end() {
+        pairs.iter().for_each(|(r1, _)| {
+            if let Err(msg) = count_reads(r1) {
+                problems.push(Problem::new(&sample.id, &msg));
+            }
+        });
+        return problems;
+    }
+    if sample.is_interleaved() {
+        match count_reads(&sample.read_1) {
+            Ok(count) if count % 2 != 0 => problems.push(Problem::new(
+                &sample.id,
+                &format!(
+                    "ODD READ COUNT IN INTERLEAVED FILE: {} READS IN {}",
+                    count,
+                    sample.read_1.display()
+                ),
+            )),
+            Ok(_) => (),
+            Err(msg) => problems.push(Problem::new(&sample.id, &msg)),
+        }
+        return problems;
+    }
+    pairs.iter().for_each(|(r1, r2)| {
+        let counts = (count_reads(r1), count_reads(r2));
+        match counts {
+            (Ok(c1), Ok(c2)) if c1 != c2 => problems.push(Problem::new(
+                &sample.id,
+                &format!(
+                    "READ COUNT MISMATCH: {} READS IN {}, {} READS IN {}",
+                    c1,
+                    r1.display(),
+                    c2,
+                    r2.display()
+                ),
+            )),
+            (c1, c2) => [c1, c2]
+                .into_iter()
+                .filter_map(|c| c.err())
+                .for_each(|msg| problems.push(Problem::new(&sample.id, &msg))),
+        }
+    });
+    if let Some(singleton) = &sample.singleton {
+        if let Err(msg) = count_reads(singleton) {
+            problems.push(Problem::new(&sample.id, &msg));
+        }
+    }
+    problems
+}
+
+// Compare the index bases in the adapters with the most common
+// index in the read headers. Suggests the orientations that match.
+// The i5 workflow comes from the instrument in the headers. For other
+// instruments, the sample orientation stands for the workflow.
+fn check_index(sample: &Sample) -> Option<String> {
+    let header = read_header_index(&sample.read_1)?;
+    let instrument_workflow = tag::get_i5_workflow(&header.instrument);
+    let indexes = [
+        (IndexRead::I7, sample.index_i7.as_ref(), Some(&header.i7)),
+        (IndexRead::I5, sample.index_i5.as_ref(), header.i5.as_ref()),
+    ];
+    let checks: Vec<(IndexRead, &String, &String)> = indexes
+        .into_iter()
+        .filter_map(|(read, bases, header)| Some((read, bases?, header?)))
+        .collect();
+    let matches = |orientation: Orientation| {
+        let workflow = instrument_workflow.or(match orientation {
+            Orientation::Complement => None,
+            orientation => Some(orientation),
+        });
+        checks.iter().all(|(read, bases, header)| {
+            let tag = tag::get_tag(bases, *read, sample.orientation);
+            let bases = tag::get_adapter_index(&tag, *read, orientation);
+            tag::matches_header_index(&bases, header, *read, workflow)
+        })
+    };
+    if checks.is_empty() || matches(sample.orientation) {
+        return None;
+    }
+    let found: Vec<String> = checks
+        .iter()
+        .map(|(read, bases, header)| {
+            format!(
+                "{} {} IN HEADERS, {} IN ADAPTER",
+                read.name(),
+                header,
+                bases
+            )
+        })
+        .collect();
+    let suggested: Vec<&str> = Orientation::ALL
+        .iter()
+        .filter(|o| matches(**o))
+        .map(|o| o.name())
+        .collect();
+    let hint = match suggested.is_empty() {
+        true => String::from("CHECK THE INDEX TAGS"),
+        false => format!("TRY index_orientation {}", suggested.join(" OR ")),
+    };
+    Some(format!(
+        "INDEX MISMATCH WITH {} ORIENTATION: {}. {}",
+        sample.orientation.name().to_uppercase(),
+        found.join(", "),
+        hint
+    ))
+}
+
+struct HeaderIndex {
+    instrument: String,
+    i7: String,
+    i5: Option<String>,
+}
+
+// Illumina headers start with the instrument and end with the index
+// reads, e.g. `@M01234:1:FC:1:1:1:1 1:N:0:ATCACG+GTACGT`. Returns the
+// instrument of the first read and the most common index of the first reads.
+fn read_header_index(path: &Path) -> Option<HeaderIndex> {
+    let headers = interleave::read_headers(path, HEADERS_TO_CHECK).ok()?;
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    headers
+        .iter()
+        .filter_map(|h| h.split_whitespace().nth(1)?.rsplit(':').next())
+        .filter(|index| {
+            !index.is_empty()
+                && index
+                    .chars()
+                    .all(|c| matches!(c, 'A' | 'C' | 'G' | 'T' | 'N' | '+'))
+        })
+        .for_each(|index| *counts.entry(index).or_insert(0) += 1);
+    let (index, _) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+    let mut parts = index.split('+');
+    let instrument = headers[0].trim_start_matches('@').split(':').next()?;
+    Some(HeaderIndex {
+        instrument: instrument.to_string(),
+        i7: parts.next()?.to_string(),
+        i5: parts.next().map(String::from),
+    })
+}
+
+// Read the whole file to catch a truncated gzip stream
+// or a fastq record cut short.
+fn count_reads(path: &Path) -> Result<usize, String> {
+    let file = File::open(path).map_err(|e| format!("CANNOT OPEN {}: {}", path.display(), e))?;
+    let is_gz = is_gzip(path).map_err(|e| format!("CANNOT READ {}: {}", path.display(), e))?;
+    let mut reader: Box<dyn Read> = if is_gz {
+        Box::new(MultiGzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut lines = 0;
+    let mut last = b'\n';
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| format!("CORRUPTED OR TRUNCATED FILE {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        lines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+        last = buf[n - 1];
+    }
+    if last != b'\n' {
+        lines += 1;
+    }
+
+    match lines {
+        0 => Err(format!("NO READS IN {}", path.display())),
+        n if n % 4 != 0 => Err(format!(
+            "TRUNCATED FASTQ {}: {} LINES IS NOT A MULTIPLE OF 4",
+            path.display(),
+            n
+        )),
+        n => Ok(n / 4),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate_lanes_test() {
+        let input = Path::new("test_files/validate/valid.csv");
+        let scheme = NamingScheme::Auto;
+        let mut validator = Validator::new(input, false, &scheme);
+
+        assert!(validator.validate());
+    }
+
+    #[test]
+    fn validate_problems_test() {
+        let input = Path::new("test_files/validate/invalid.conf");
+        let scheme = NamingScheme::Auto;
+        let mut validator = Validator::new(input, false, &scheme);
+
+        assert!(!validator.validate());
+        let locations: Vec<&str> = validator
+            .problems
+            .iter()
+            .map(|p| p.location.as_str())
+            .collect();
+        assert_eq!(
+            vec!["Line 4", "Line 5", "Apodemus", "mismatch", "truncated"],
+            locations
+        );
+    }
+
+    #[test]
+    fn validate_adapter_test() {
+        let input = Path::new("test_files/validate/adapters.csv");
+        let scheme = NamingScheme::Auto;
+        let mut validator = Validator::new(input, false, &scheme);
+
+        assert!(!validator.validate());
+        assert_eq!(1, validator.problems.len());
+        assert!(validator.problems[0].message.contains("ACGT-X"));
+    }
+
+    #[test]
+    fn validate_index_test() {
+        let input = Path::new("test_files/index/samples.csv");
+        let scheme = NamingScheme::Auto;
+        let mut validator = Validator::new(input, false, &scheme);
+
+        assert!(!validator.validate());
+        assert_eq!(1, validator.problems.len());
+        assert_eq!("ABC2", validator.problems[0].location);
+        assert!(validator.problems[0]
+            .message
+            .ends_with("TRY index_orientation forward"));
+    }
+
+    #[test]
+    fn validate_i5_workflow_test() {
+        // NextSeq headers read the i5 index as the reverse complement
+        // of the adapter bases, so the tag needs the reverse orientation.
+        let input = Path::new("test_files/index/reverse.csv");
+        let scheme = NamingScheme::Auto;
+        let mut validator = Validator::new(input, false, &scheme);
+
+        assert!(!validator.validate());
+        assert_eq!(1, validator.problems.len());
+        assert!(validator.problems[0]
+            .message
+            .ends_with("TRY index_orientation reverse"));
+    }
+
+    #[test]
+    fn count_reads_test() {
+        let read = Path::new("test_files/lanes/Apodemus_S3_L001_R1_001.fastq.gz");
+        assert_eq!(Ok(1), count_reads(read));
+        let read = Path::new("test_files/validate/truncated_R1.fastq.gz");
+        assert!(count_reads(read).is_err());
+    }
+}
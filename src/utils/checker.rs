@@ -135,7 +135,7 @@ impl<'a> DependencyInstaller<'a> {
     fn add_bioconda_channel(&self) {
         log::info!("Adding bioconda channel...");
         let out = Command::new("conda")
-            .args(&["config", "--add", "channels", "bioconda"])
+            .args(["config", "--add", "channels", "bioconda"])
             .output();
         match out {
             Ok(out) => self.check_command_status("bioconda", &out),
@@ -146,7 +146,7 @@ impl<'a> DependencyInstaller<'a> {
     fn add_conda_forge_channel(&self) {
         log::info!("Adding conda-forge channel...");
         let out = Command::new("conda")
-            .args(&["config", "--add", "channels", "conda-forge"])
+            .args(["config", "--add", "channels", "conda-forge"])
             .output();
         match out {
             Ok(out) => self.check_command_status("conda-forge", &out),
@@ -157,7 +157,7 @@ impl<'a> DependencyInstaller<'a> {
     fn install_deps(&self, app: &str) {
         log::info!("Installing {}...", app.to_uppercase());
         let out = Command::new("conda")
-            .args(&["install", "-c", "bioconda", app])
+            .args(["install", "-c", "bioconda", app])
             .output();
         match out {
             Ok(out) => self.check_command_status(app, &out),
@@ -0,0 +1,82 @@
+//! Fields of the csv and tsv files that yap writes and reads.
+//!
+//! A field that contains the separator or a double quote is quoted
+//! as in RFC 4180. Quotes inside a quoted field are doubled.
+
+/// Join the fields into a line, quoting the fields that need it.
+pub fn join_fields<S: AsRef<str>>(fields: &[S], sep: char) -> String {
+    fields
+        .iter()
+        .map(|field| quote_field(field.as_ref(), sep))
+        .collect::<Vec<_>>()
+        .join(&sep.to_string())
+}
+
+fn quote_field(field: &str, sep: char) -> String {
+    if field.contains([sep, '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Split a line into fields and remove the quotes.
+/// A quote only opens a field at its start, after any spaces.
+pub fn split_fields(line: &str, sep: char) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut is_quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if is_quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    is_quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                is_quoted = true;
+            }
+            c if c == sep && !is_quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if is_quoted {
+        return Err(format!("MISSING A CLOSING QUOTE IN: {}", line));
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn join_fields_test() {
+        let fields = ["ABC1", "Rattus, sp.", "KU \"1\"", ""];
+
+        assert_eq!(
+            "ABC1,\"Rattus, sp.\",\"KU \"\"1\"\"\",",
+            join_fields(&fields, ',')
+        );
+        assert_eq!("ABC1\tRattus, sp.", join_fields(&fields[..2], '\t'));
+    }
+
+    #[test]
+    fn split_fields_test() {
+        let line = "ABC1,\"Rattus, sp.\",\"KU \"\"1\"\"\",";
+        let fields = split_fields(line, ',').unwrap();
+
+        assert_eq!(vec!["ABC1", "Rattus, sp.", "KU \"1\"", ""], fields);
+        assert_eq!(
+            vec!["a b", "5\" long"],
+            split_fields("a b,5\" long", ',').unwrap()
+        );
+        assert!(split_fields("ABC1,\"Rattus, sp.", ',').is_err());
+    }
+}
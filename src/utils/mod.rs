@@ -1,2 +1,5 @@
 pub mod checker;
+pub mod csv;
+pub mod opts;
+#[allow(clippy::module_inception)]
 pub mod utils;
@@ -0,0 +1,531 @@
+//! Tool options from `--opts` and the sample sheet.
+//!
+//! Options are split into words as in a POSIX shell, so values with
+//! spaces can be quoted. Sample options are merged with the global
+//! options option by option. An option set by both keeps the sample
+//! value. Sample options that start with `replace:` are used instead
+//! of the global options. Options that yap sets itself, such as the
+//! input and output files, are rejected.
+use std::ffi::OsStr;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::assembly::options::{self, SpadesOptions};
+use crate::qc::options::{self as fastp, FastpOptions, FASTP_FLAGS};
+use crate::sheet::Sample;
+
+/// Marks sample options that replace the global options.
+pub const REPLACE_PREFIX: &str = "replace:";
+
+// Set by yap from the sample sheet and the command line.
+const FASTP_MANAGED: [&str; 18] = [
+    "-i",
+    "--in1",
+    "-I",
+    "--in2",
+    "-o",
+    "--out1",
+    "-O",
+    "--out2",
+    "--interleaved_in",
+    "-h",
+    "--html",
+    "-j",
+    "--json",
+    "-w",
+    "--thread",
+    "-a",
+    "--adapter_sequence",
+    "--adapter_sequence_r2",
+];
+
+const SPADES_MANAGED: [&str; 13] = [
+    "-o",
+    "-1",
+    "-2",
+    "-s",
+    "--12",
+    "--pe1-1",
+    "--pe1-2",
+    "--pe1-12",
+    "--pe1-s",
+    "-t",
+    "--threads",
+    "--continue",
+    "--restart-from",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    Fastp,
+    Spades,
+}
+
+impl Tool {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Fastp => "fastp",
+            Self::Spades => "SPAdes",
+        }
+    }
+
+    fn get_managed_opts(&self) -> &[&str] {
+        match self {
+            Self::Fastp => &FASTP_MANAGED,
+            Self::Spades => &SPADES_MANAGED,
+        }
+    }
+
+    /// The sample sheet column of the sample options.
+    pub fn column(&self) -> &str {
+        match self {
+            Self::Fastp => "fastp_opts",
+            Self::Spades => "spades_opts",
+        }
+    }
+
+    fn get_sample_column<'a>(&self, sample: &'a Sample) -> Option<&'a str> {
+        match self {
+            Self::Fastp => sample.fastp_opts.as_deref(),
+            Self::Spades => sample.spades_opts.as_deref(),
+        }
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        match self {
+            Self::Fastp => FASTP_FLAGS.iter().any(|(s, l)| *s == name || *l == name),
+            Self::Spades => options::is_spades_flag(name),
+        }
+    }
+
+    fn get_long_flags(&self) -> Vec<&str> {
+        match self {
+            Self::Fastp => FASTP_FLAGS.iter().map(|(_, long)| *long).collect(),
+            Self::Spades => options::get_spades_flags()
+                .iter()
+                .copied()
+                .filter(|f| f.starts_with("--"))
+                .collect(),
+        }
+    }
+
+    // Aliases and options that replace each other share a key.
+    fn get_key<'a>(&self, name: &'a str) -> &'a str {
+        match self {
+            Self::Fastp => fastp::get_long_name(name),
+            Self::Spades => options::get_spades_key(name),
+        }
+    }
+
+    // The values of the typed options.
+    fn check_values(&self, args: &[String]) -> Result<(), String> {
+        match self {
+            Self::Fastp => FastpOptions::from_args(args).map(|_| ()),
+            Self::Spades => SpadesOptions::from_args(args).map(|_| ()),
+        }
+    }
+}
+
+/// Options for all samples: the typed options, then `--opts`.
+/// An option set by both keeps the `--opts` value.
+pub fn get_global_opts(
+    tool: Tool,
+    typed: Vec<String>,
+    raw: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let raw = get_opts(tool, raw.unwrap_or_default())?;
+    let merged = merge_words(tool, typed, raw);
+    tool.check_values(&merged)?;
+    Ok(merged)
+}
+
+/// Check the options of every sample merged with the global options,
+/// so invalid options stop the run before any job starts.
+pub fn check_sample_opts(tool: Tool, global: &[String], samples: &[Sample]) {
+    let errors: Vec<String> = samples
+        .iter()
+        .filter_map(|s| {
+            let e = check_opts(tool, global, s).err()?;
+            Some(format!("{} OF {}: {}", tool.column(), s.id, e))
+        })
+        .collect();
+    if !errors.is_empty() {
+        panic!("INVALID {} OPTIONS\n{}", tool.name(), errors.join("\n"));
+    }
+}
+
+/// Check the options of a sample merged with the global options.
+pub fn check_opts(tool: Tool, global: &[String], sample: &Sample) -> Result<(), String> {
+    merge_opts(tool, global, tool.get_sample_column(sample)).map(|_| ())
+}
+
+/// The options of a sample. Invalid options panic,
+/// as `check_sample_opts` reports them before the run.
+pub fn get_sample_opts(tool: Tool, global: &[String], sample: &Sample) -> Vec<String> {
+    merge_opts(tool, global, tool.get_sample_column(sample))
+        .unwrap_or_else(|e| panic!("INVALID {} OPTIONS OF {}: {}", tool.name(), sample.id, e))
+}
+
+// The sample options merged with the global options.
+fn merge_opts(tool: Tool, global: &[String], sample: Option<&str>) -> Result<Vec<String>, String> {
+    let sample = match sample.map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        Some(sample) => sample,
+        None => return Ok(global.to_vec()),
+    };
+    let merged = match sample.strip_prefix(REPLACE_PREFIX) {
+        Some(replacement) => get_opts(tool, replacement)?,
+        None => merge_words(tool, global.to_vec(), get_opts(tool, sample)?),
+    };
+    tool.check_values(&merged)?;
+    Ok(merged)
+}
+
+// Options in `extra` replace the same options in `base`.
+fn merge_words(tool: Tool, base: Vec<String>, extra: Vec<String>) -> Vec<String> {
+    let extra = group_opts(extra);
+    let keys: Vec<String> = extra.iter().filter_map(|g| get_key(tool, g)).collect();
+    group_opts(base)
+        .into_iter()
+        .filter(|group| !matches!(get_key(tool, group), Some(key) if keys.contains(&key)))
+        .chain(extra)
+        .flatten()
+        .collect()
+}
+
+/// Split the options and check them. Options that yap sets and
+/// likely typos of known options are rejected. Unknown options
+/// are passed to the tool.
+pub fn get_opts(tool: Tool, opts: &str) -> Result<Vec<String>, String> {
+    let words = split_words(opts)?;
+    for name in words.iter().filter(|w| is_option(w)).map(|w| get_name(w)) {
+        if tool.get_managed_opts().contains(&name) {
+            return Err(format!(
+                "{} IS SET BY YAP FOR {}. USE {} INSTEAD",
+                name,
+                tool.name(),
+                get_source(name)
+            ));
+        }
+        if tool.is_known(name) {
+            continue;
+        }
+        if let Some(flag) = find_similar(name, &tool.get_long_flags()) {
+            return Err(format!(
+                "UNKNOWN {} OPTION {}. DID YOU MEAN {}?",
+                tool.name(),
+                name,
+                flag
+            ));
+        }
+    }
+    tool.check_values(&words)?;
+    Ok(words)
+}
+
+/// An option and its values.
+/// `--name=value` is the same as `--name value`.
+pub struct OptionValues<'a> {
+    pub name: &'a str,
+    pub values: Vec<&'a str>,
+}
+
+pub fn get_option_values(args: &[String]) -> Vec<OptionValues<'_>> {
+    let mut options: Vec<OptionValues> = Vec::new();
+    for arg in args {
+        if is_option(arg) {
+            let mut parts = arg.splitn(2, '=');
+            let name = parts.next().unwrap_or_default();
+            options.push(OptionValues {
+                name,
+                values: parts.collect(),
+            });
+        } else if let Some(option) = options.last_mut() {
+            option.values.push(arg);
+        }
+    }
+    options
+}
+
+/// The first value of an option.
+pub fn parse_value<T: FromStr>(name: &str, values: &[&str]) -> Result<T, String> {
+    let value = values
+        .first()
+        .ok_or_else(|| format!("MISSING VALUE FOR {}", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("INVALID VALUE {} FOR {}", value, name))
+}
+
+/// Split a string into words as a POSIX shell does, without expansions.
+/// Single quotes keep every character. Inside double quotes, a backslash
+/// only escapes `"`, `\`, `$`, `` ` ``, and a newline.
+pub fn split_words(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Tells an empty quoted word from no word.
+    let mut in_word = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(String::from("UNCLOSED SINGLE QUOTE")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => (),
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(String::from("UNCLOSED DOUBLE QUOTE")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(String::from("UNCLOSED DOUBLE QUOTE")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => (),
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => return Err(String::from("TRAILING BACKSLASH")),
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// Where yap takes a managed option from.
+fn get_source(opt: &str) -> &str {
+    match opt {
+        "-w" | "--thread" | "-t" | "--threads" => "--threads",
+        "-o" | "--out1" | "-O" | "--out2" | "-h" | "--html" | "-j" | "--json" => "--output",
+        "-a" | "--adapter_sequence" | "--adapter_sequence_r2" => {
+            "THE i5 AND i7 SAMPLE SHEET COLUMNS"
+        }
+        "--continue" | "--restart-from" => "--resume",
+        _ => "THE SAMPLE SHEET",
+    }
+}
+
+// An option and the values that follow it.
+fn group_opts(opts: Vec<String>) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    for opt in opts {
+        match groups.last_mut() {
+            Some(group) if !is_option(&opt) => group.push(opt),
+            _ => groups.push(vec![opt]),
+        }
+    }
+    groups
+}
+
+fn get_key(tool: Tool, group: &[String]) -> Option<String> {
+    let first = group.first()?;
+    if !is_option(first) {
+        return None;
+    }
+    Some(tool.get_key(get_name(first)).to_string())
+}
+
+// The option name without an `=value`.
+fn get_name(opt: &str) -> &str {
+    opt.split('=').next().unwrap_or_default()
+}
+
+// Long options one or two edits away from a known option.
+fn find_similar<'a>(name: &str, flags: &[&'a str]) -> Option<&'a str> {
+    if !name.starts_with("--") {
+        return None;
+    }
+    flags
+        .iter()
+        .map(|flag| (get_edit_distance(name, flag), *flag))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, flag)| flag)
+}
+
+fn get_edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Negative numbers are values, not options.
+fn is_option(opt: &str) -> bool {
+    opt.len() > 1 && opt.starts_with('-') && opt.parse::<f64>().is_err()
+}
+
+/// The command line of a command, for logs and dry runs.
+pub fn format_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(quote_arg)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn quote_arg(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || "'\"\\$`".contains(c)) {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    } else {
+        arg.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_words(opts: &str) -> Vec<String> {
+        split_words(opts).unwrap()
+    }
+
+    #[test]
+    fn merge_opts_test() {
+        let global = to_words("--length_required 50 --cut_right -W 4");
+        let sample = Some("-l 30 --cut_right_mean_quality 15");
+        let expected = vec![
+            "--cut_right",
+            "-W",
+            "4",
+            "-l",
+            "30",
+            "--cut_right_mean_quality",
+            "15",
+        ];
+
+        assert_eq!(expected, merge_opts(Tool::Fastp, &global, sample).unwrap());
+        assert_eq!(
+            vec!["--careful"],
+            merge_opts(Tool::Spades, &to_words("--careful"), Some("")).unwrap()
+        );
+        assert_eq!(
+            vec!["-k", "21"],
+            merge_opts(Tool::Spades, &[], Some("-k 21")).unwrap()
+        );
+        assert_eq!(
+            vec!["--isolate"],
+            merge_opts(Tool::Spades, &to_words("--careful"), Some("--isolate")).unwrap()
+        );
+    }
+
+    #[test]
+    fn replace_opts_test() {
+        let opts = merge_opts(
+            Tool::Spades,
+            &to_words("--careful"),
+            Some("replace: --isolate"),
+        );
+
+        assert_eq!(vec!["--isolate"], opts.unwrap());
+    }
+
+    #[test]
+    fn global_opts_test() {
+        let typed = to_words("--length_required 50 --dedup");
+        let opts = get_global_opts(Tool::Fastp, typed, Some("-l 30"));
+
+        assert_eq!(to_words("--dedup -l 30"), opts.unwrap());
+        assert!(get_global_opts(Tool::Spades, Vec::new(), Some("-k 21,22")).is_err());
+    }
+
+    #[test]
+    fn typo_opts_test() {
+        assert_eq!(
+            Err(String::from(
+                "UNKNOWN SPAdes OPTION --carefull. DID YOU MEAN --careful?"
+            )),
+            get_opts(Tool::Spades, "--carefull")
+        );
+        assert!(get_opts(Tool::Fastp, "--cut_rigth").is_err());
+        assert!(get_opts(Tool::Fastp, "--some_new_option 5").is_ok());
+        assert!(get_opts(Tool::Spades, "--pe2-12 reads.fq").is_ok());
+    }
+
+    #[test]
+    fn split_words_test() {
+        let words = split_words(
+            r#"--adapter_fasta "my adapters.fa" -m 'a "b"' a\ b "x\"y\z" '' --cut_front"#,
+        );
+        let expected = vec![
+            "--adapter_fasta",
+            "my adapters.fa",
+            "-m",
+            "a \"b\"",
+            "a b",
+            "x\"y\\z",
+            "",
+            "--cut_front",
+        ];
+
+        assert_eq!(expected, words.unwrap());
+        assert_eq!(
+            Err(String::from("UNCLOSED SINGLE QUOTE")),
+            split_words("-m 'abc")
+        );
+        assert_eq!(
+            Err(String::from("TRAILING BACKSLASH")),
+            split_words("-m \\")
+        );
+    }
+
+    #[test]
+    fn managed_opts_test() {
+        assert_eq!(
+            Err(String::from(
+                "-o IS SET BY YAP FOR SPAdes. USE --output INSTEAD"
+            )),
+            get_opts(Tool::Spades, "--careful -o out")
+        );
+        assert!(get_opts(Tool::Fastp, "--thread=4").is_err());
+        assert!(get_opts(Tool::Fastp, "--trim_front1 -5").is_ok());
+        assert!(merge_opts(Tool::Fastp, &[], Some("replace: -i reads.fq")).is_err());
+    }
+
+    #[test]
+    fn format_command_test() {
+        let mut cmd = Command::new("fastp");
+        cmd.arg("-i").arg("my reads.fq").arg("--cut_right");
+
+        assert_eq!("fastp -i 'my reads.fq' --cut_right", format_command(&cmd));
+    }
+}
@@ -94,10 +94,7 @@ fn get_os_name() -> String {
 }
 
 fn get_os_version() -> String {
-    match System::os_version() {
-        Some(i) => i,
-        None => String::from(""),
-    }
+    System::os_version().unwrap_or_default()
 }
 
 fn get_kernel_version() -> String {
@@ -138,7 +135,7 @@ impl PrettyHeader {
     fn get_with_symbol(&mut self) -> String {
         let mut sym = self.get_symbols();
         let header = format!("Processing {}", self.text);
-        if self.text_len % 2 != 0 {
+        if !self.text_len.is_multiple_of(2) {
             sym.push(self.sym);
         }
 